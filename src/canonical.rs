@@ -0,0 +1,134 @@
+//! Frénicle standard form and canonical hashing of squares.
+//!
+//! Two squares are *essentially the same* when one can be turned into the other by one of the
+//! 8 symmetries of the square (4 rotations, each optionally mirrored). The Frénicle standard
+//! form picks one representative per class:
+//! 1. The top-left cell holds the smallest of the four corners.
+//! 2. The cell to the right of it is smaller than the cell below it.
+//!
+//! For squares with distinct entries this is exactly the lexicographically smallest of the 8
+//! variants of the flat row-major grid, which is what [`canonicalize`] computes; the
+//! lexicographic rule also gives a well-defined form for grids with repeated values.
+
+use crate::transform::{Symmetry, Transform};
+
+/// Returns the 8 variants of a flat $n \times n$ grid under rotations and reflections.
+/// The first variant is the grid itself.
+pub fn symmetries(grid: &[u32], n: usize) -> Vec<Vec<u32>> {
+    Transform::dihedral(n).iter().map(|t| t.apply(grid)).collect()
+}
+
+/// Returns the Frénicle standard form of a flat $n \times n$ grid.
+pub fn canonicalize(grid: &[u32], n: usize) -> Vec<u32> {
+    if n == 0 {
+        return Vec::new();
+    }
+    variant(grid, n, smallest_variant(grid, n)).collect()
+}
+
+/// Returns whether the grid is already in Frénicle standard form.
+pub fn is_canonical(grid: &[u32], n: usize) -> bool {
+    // Ties keep the identity first, so it is chosen whenever no variant is smaller.
+    n == 0 || smallest_variant(grid, n) == Symmetry::Identity
+}
+
+/// Stable 64-bit hash of the Frénicle standard form.
+///
+/// Squares that are rotations or reflections of each other hash to the same value. The hash is
+/// FNV-1a over the order and the little-endian cells, so it does not change between runs,
+/// platforms or builds, and can be stored alongside squares.
+pub fn canonical_hash(grid: &[u32], n: usize) -> u64 {
+    if n == 0 {
+        return fnv1a(std::iter::empty(), n);
+    }
+    fnv1a(variant(grid, n, smallest_variant(grid, n)), n)
+}
+
+/// The value that `symmetry` moves to row `r` and column `c`.
+fn cell(grid: &[u32], n: usize, symmetry: Symmetry, r: usize, c: usize) -> u32 {
+    let (sr, sc) = symmetry.source(n, r, c);
+    grid[sr * n + sc]
+}
+
+/// The cells of `symmetry` applied to the grid, in row-major order.
+fn variant(grid: &[u32], n: usize, symmetry: Symmetry) -> impl Iterator<Item = u32> + '_ {
+    (0..n * n).map(move |i| cell(grid, n, symmetry, i / n, i % n))
+}
+
+/// The symmetry giving the lexicographically smallest variant. The variants are compared
+/// cell by cell without being built, so large squares need no extra memory; for distinct
+/// entries the first cell or two decide.
+fn smallest_variant(grid: &[u32], n: usize) -> Symmetry {
+    let mut candidates = Symmetry::ALL.to_vec();
+    for i in 0..n * n {
+        let (r, c) = (i / n, i % n);
+        let Some(min) = candidates.iter().map(|&s| cell(grid, n, s, r, c)).min() else { break };
+        candidates.retain(|&s| cell(grid, n, s, r, c) == min);
+        if candidates.len() == 1 {
+            break;
+        }
+    }
+    candidates[0]
+}
+
+fn fnv1a(cells: impl Iterator<Item = u32>, n: usize) -> u64 {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let mut hash = OFFSET;
+    let order = (n as u64).to_le_bytes();
+    let bytes = order.iter().copied().chain(cells.flat_map(|v| v.to_le_bytes()));
+    for byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(PRIME);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LO_SHU: [u32; 9] = [4, 9, 2, 3, 5, 7, 8, 1, 6];
+
+    #[test]
+    fn lo_shu_standard_form() {
+        // Frénicle form of the only order-3 square.
+        assert_eq!(canonicalize(&LO_SHU, 3), vec![2, 7, 6, 9, 5, 1, 4, 3, 8]);
+    }
+
+    #[test]
+    fn lazy_form_matches_the_smallest_variant() {
+        // Repeated values leave several variants tied for many cells.
+        let grids: [(&[u32], usize); 3] =
+            [(&[1, 1, 2, 1, 1, 2, 3, 3, 3], 3), (&[5, 5, 5, 5], 2), (&(1..=25).rev().collect::<Vec<_>>(), 5)];
+        for (grid, n) in grids {
+            let smallest = symmetries(grid, n).into_iter().min().unwrap();
+            assert_eq!(canonicalize(grid, n), smallest);
+            assert_eq!(canonical_hash(grid, n), fnv1a(smallest.clone().into_iter(), n));
+            assert!(is_canonical(&smallest, n));
+            assert_eq!(is_canonical(grid, n), grid == smallest.as_slice());
+        }
+        assert!(canonicalize(&[], 0).is_empty());
+        assert!(is_canonical(&[], 0));
+    }
+
+    #[test]
+    fn symmetric_variants_share_a_hash() {
+        let hash = canonical_hash(&LO_SHU, 3);
+        let variants = symmetries(&LO_SHU, 3);
+        assert_eq!(variants.len(), 8);
+        for variant in &variants {
+            assert_eq!(canonical_hash(variant, 3), hash);
+        }
+        let canon: Vec<&Vec<u32>> = variants.iter().filter(|v| is_canonical(v, 3)).collect();
+        assert_eq!(canon.len(), 1);
+    }
+
+    #[test]
+    fn hash_is_stable() {
+        // Pinned so that stored hashes stay valid across releases.
+        assert_eq!(canonical_hash(&LO_SHU, 3), 0x087e_12f3_0496_b217);
+        assert_ne!(canonical_hash(&LO_SHU, 3), canonical_hash(&[1, 2, 3, 4, 5, 6, 7, 8, 9], 3));
+    }
+}
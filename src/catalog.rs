@@ -0,0 +1,228 @@
+//! A catalog of famous magic squares, for tests and teaching.
+//!
+//! Each [`Entry`] holds a historical square in the flat row-major layout the validator uses,
+//! with a short note on where it comes from. The properties reported by [`Entry::summary`] are
+//! computed, not stored. Some entries are not normal squares (their values are not $1..n^2$),
+//! so only their common line sum is reported.
+
+use crate::validator::{self, Properties};
+
+/// A square from the catalog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    /// Short name used to look the square up, e.g. `durer`.
+    pub key: &'static str,
+    /// Other accepted names.
+    pub aliases: &'static [&'static str],
+    /// Display name.
+    pub name: &'static str,
+    /// The order of the square.
+    pub n: usize,
+    /// The flat row-major grid.
+    pub grid: &'static [u32],
+    /// Where the square comes from and what it is known for.
+    pub description: &'static str,
+}
+
+impl Entry {
+    /// The magic properties of the square (all false for squares that are not normal).
+    pub fn properties(&self) -> Properties {
+        validator::classify(self.grid, self.n)
+    }
+
+    /// The sum shared by every row, column and main diagonal, if there is one.
+    pub fn line_sum(&self) -> Option<u64> {
+        validator::common_line_sum(self.grid, self.n)
+    }
+
+    /// A one-line account of the computed properties, e.g.
+    /// `"Order 4, line sum 34. Magic, pandiagonal."`
+    pub fn summary(&self) -> String {
+        let p = self.properties();
+        let sum = match self.line_sum() {
+            Some(sum) => format!("line sum {}", sum),
+            None if p.semi_magic => format!("row and column sum {}", validator::magic_constant(self.n)),
+            None => "no common line sum".to_string(),
+        };
+        let mut traits = Vec::new();
+        if p.magic {
+            traits.push("magic");
+        } else if p.semi_magic {
+            traits.push("semi-magic");
+        } else {
+            traits.push(if self.line_sum().is_some() { "not normal (values are not 1 to n²)" } else { "not magic" });
+        }
+        if p.pandiagonal {
+            traits.push("pandiagonal");
+        }
+        if p.franklin {
+            traits.push("Franklin");
+        }
+        if p.trimagic {
+            traits.push("trimagic");
+        } else if p.bimagic {
+            traits.push("bimagic");
+        }
+        let traits = traits.join(", ");
+        format!("Order {}, {}. {}{}.", self.n, sum, traits[..1].to_uppercase(), &traits[1..])
+    }
+}
+
+/// The Lo Shu square of Chinese legend, the only normal magic square of order 3 up to
+/// rotations and reflections.
+pub const LO_SHU: Entry = Entry {
+    key: "lo-shu",
+    aliases: &["loshu"],
+    name: "Lo Shu",
+    n: 3,
+    grid: &[4, 9, 2, 3, 5, 7, 8, 1, 6],
+    description: "Chinese legend places it on the shell of a turtle rising from the Lo river. \
+        It is the only normal order-3 magic square up to rotations and reflections.",
+};
+
+/// The square in Albrecht Dürer's engraving *Melencolia I*.
+pub const DURER: Entry = Entry {
+    key: "durer",
+    aliases: &["melencolia"],
+    name: "Dürer's Melencolia I",
+    n: 4,
+    grid: &[16, 3, 2, 13, 5, 10, 11, 8, 9, 6, 7, 12, 4, 15, 14, 1],
+    description: "From Albrecht Dürer's 1514 engraving Melencolia I. The middle cells of the \
+        bottom row give the year, and the four quadrants, the centre and the corners also sum to 34.",
+};
+
+/// The pandiagonal square inscribed in the Parshvanatha temple at Khajuraho.
+pub const CHAUTISA: Entry = Entry {
+    key: "chautisa",
+    aliases: &["jaina", "khajuraho"],
+    name: "Jaina Chautisa Yantra",
+    n: 4,
+    grid: &[7, 12, 1, 14, 2, 13, 8, 11, 16, 3, 10, 5, 9, 6, 15, 4],
+    description: "Inscribed in the Parshvanatha Jain temple at Khajuraho, India, in the 10th \
+        century. Chautisa means thirty-four; every broken diagonal also sums to 34.",
+};
+
+/// Benjamin Franklin's 8x8 square.
+pub const FRANKLIN_8: Entry = Entry {
+    key: "franklin-8",
+    aliases: &["franklin8", "franklin"],
+    name: "Franklin's 8×8 square",
+    n: 8,
+    grid: &[
+        52, 61, 4, 13, 20, 29, 36, 45, //
+        14, 3, 62, 51, 46, 35, 30, 19, //
+        53, 60, 5, 12, 21, 28, 37, 44, //
+        11, 6, 59, 54, 43, 38, 27, 22, //
+        55, 58, 7, 10, 23, 26, 39, 42, //
+        9, 8, 57, 56, 41, 40, 25, 24, //
+        50, 63, 2, 15, 18, 31, 34, 47, //
+        16, 1, 64, 49, 48, 33, 32, 17,
+    ],
+    description: "Published by Benjamin Franklin in a 1769 letter. The main diagonals do not \
+        sum to 260, but every bent diagonal does, as do half rows, half columns and 2×2 blocks.",
+};
+
+/// Benjamin Franklin's 16x16 square.
+pub const FRANKLIN_16: Entry = Entry {
+    key: "franklin-16",
+    aliases: &["franklin16"],
+    name: "Franklin's 16×16 square",
+    n: 16,
+    grid: &[
+        200, 217, 232, 249, 8, 25, 40, 57, 72, 89, 104, 121, 136, 153, 168, 185, //
+        58, 39, 26, 7, 250, 231, 218, 199, 186, 167, 154, 135, 122, 103, 90, 71, //
+        198, 219, 230, 251, 6, 27, 38, 59, 70, 91, 102, 123, 134, 155, 166, 187, //
+        60, 37, 28, 5, 252, 229, 220, 197, 188, 165, 156, 133, 124, 101, 92, 69, //
+        201, 216, 233, 248, 9, 24, 41, 56, 73, 88, 105, 120, 137, 152, 169, 184, //
+        55, 42, 23, 10, 247, 234, 215, 202, 183, 170, 151, 138, 119, 106, 87, 74, //
+        203, 214, 235, 246, 11, 22, 43, 54, 75, 86, 107, 118, 139, 150, 171, 182, //
+        53, 44, 21, 12, 245, 236, 213, 204, 181, 172, 149, 140, 117, 108, 85, 76, //
+        205, 212, 237, 244, 13, 20, 45, 52, 77, 84, 109, 116, 141, 148, 173, 180, //
+        51, 46, 19, 14, 243, 238, 211, 206, 179, 174, 147, 142, 115, 110, 83, 78, //
+        207, 210, 239, 242, 15, 18, 47, 50, 79, 82, 111, 114, 143, 146, 175, 178, //
+        49, 48, 17, 16, 241, 240, 209, 208, 177, 176, 145, 144, 113, 112, 81, 80, //
+        196, 221, 228, 253, 4, 29, 36, 61, 68, 93, 100, 125, 132, 157, 164, 189, //
+        62, 35, 30, 3, 254, 227, 222, 195, 190, 163, 158, 131, 126, 99, 94, 67, //
+        194, 223, 226, 255, 2, 31, 34, 63, 66, 95, 98, 127, 130, 159, 162, 191, //
+        64, 33, 32, 1, 256, 225, 224, 193, 192, 161, 160, 129, 128, 97, 96, 65,
+    ],
+    description: "Sent by Benjamin Franklin to Peter Collinson around 1752, who called it the \
+        most magically magical of any magic square. Like the 8×8 square it has bent diagonals \
+        instead of magic main diagonals, and every 4×4 block sums to 2056.",
+};
+
+/// The cryptogram on the Passion façade of the Sagrada Família.
+pub const SAGRADA_FAMILIA: Entry = Entry {
+    key: "sagrada-familia",
+    aliases: &["sagrada", "subirachs"],
+    name: "Sagrada Família cryptogram",
+    n: 4,
+    grid: &[1, 14, 14, 4, 11, 7, 6, 9, 8, 10, 10, 5, 13, 2, 3, 15],
+    description: "Carved by Josep Maria Subirachs on the Passion façade of the Sagrada Família \
+        in Barcelona. Lines sum to 33, the age of Jesus at the Passion; 10 and 14 repeat and \
+        12 and 16 are missing.",
+};
+
+/// Srinivasa Ramanujan's square, whose top row is his birth date.
+pub const RAMANUJAN: Entry = Entry {
+    key: "ramanujan",
+    aliases: &["date"],
+    name: "Ramanujan's date square",
+    n: 4,
+    grid: &[22, 12, 18, 87, 88, 17, 9, 25, 10, 24, 89, 16, 19, 86, 23, 11],
+    description: "Attributed to Srinivasa Ramanujan. The top row reads his birth date, \
+        22/12/1887, and every line sums to 139.",
+};
+
+/// Every square in the catalog.
+pub const ENTRIES: &[Entry] = &[LO_SHU, DURER, CHAUTISA, FRANKLIN_8, FRANKLIN_16, SAGRADA_FAMILIA, RAMANUJAN];
+
+/// Looks a square up by key or alias, ignoring case.
+pub fn find(name: &str) -> Option<&'static Entry> {
+    let name = name.to_lowercase();
+    ENTRIES.iter().find(|e| e.key == name || e.aliases.contains(&name.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normal_entries_are_magic() {
+        for entry in [LO_SHU, DURER, CHAUTISA] {
+            assert_eq!(entry.grid.len(), entry.n * entry.n);
+            assert!(entry.properties().magic, "{}", entry.name);
+        }
+        assert!(CHAUTISA.properties().pandiagonal);
+        assert!(!DURER.properties().pandiagonal);
+    }
+
+    #[test]
+    fn franklin_squares_are_semi_magic() {
+        for entry in [FRANKLIN_8, FRANKLIN_16] {
+            let p = entry.properties();
+            assert!(p.semi_magic && !p.magic && p.franklin, "{}", entry.name);
+            assert_eq!(entry.line_sum(), None);
+        }
+        assert_eq!(FRANKLIN_8.summary(), "Order 8, row and column sum 260. Semi-magic, Franklin.");
+    }
+
+    #[test]
+    fn special_squares_have_a_line_sum() {
+        assert_eq!(SAGRADA_FAMILIA.line_sum(), Some(33));
+        assert_eq!(RAMANUJAN.line_sum(), Some(139));
+        assert!(!RAMANUJAN.properties().semi_magic);
+        assert_eq!(RAMANUJAN.summary(), "Order 4, line sum 139. Not normal (values are not 1 to n²).");
+        assert_eq!(CHAUTISA.summary(), "Order 4, line sum 34. Magic, pandiagonal.");
+    }
+
+    #[test]
+    fn finds_entries_by_key_or_alias() {
+        assert_eq!(find("durer"), Some(&DURER));
+        assert_eq!(find("Jaina").map(|e| e.key), Some("chautisa"));
+        assert!(find("unknown").is_none());
+        for entry in ENTRIES {
+            assert_eq!(find(entry.key).map(|e| e.key), Some(entry.key));
+        }
+    }
+}
@@ -0,0 +1,608 @@
+//! Parallel backtracking engine that counts every normal magic square of a small order.
+//!
+//! Squares are counted up to the 8 symmetries of the square (rotations and reflections),
+//! so the engine reports 1 square of order 3, 880 of order 4 and 275,305,224 of order 5.
+//! Symmetry is broken with the Frénicle conditions on the corners: the top-left corner is
+//! the smallest corner and the top-right corner is smaller than the bottom-left one.
+//!
+//! The search tree is split into independent work units: every unit fixes the first cells of
+//! the fill order (the centre for odd orders, then the start of the main diagonal). Units are
+//! handed to a pool of worker threads and their counts are streamed back over a channel, the
+//! same way the verification mode of the CLI distributes orders. Finished units can be
+//! appended to a checkpoint file so that an interrupted run resumes where it left off.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+/// Largest order the engine accepts. Values are tracked in a 64-bit availability mask.
+pub const MAX_ORDER: usize = 5;
+
+/// First line of every checkpoint file.
+const CHECKPOINT_HEADER: &str = "# magic_squares enumerate checkpoint v1";
+
+/// Options for a counting run.
+#[derive(Debug, Clone)]
+pub struct EnumerateConfig {
+    /// Order of the squares to count.
+    pub n: usize,
+    /// Number of worker threads. `0` uses all available cores.
+    pub threads: usize,
+    /// Number of leading cells of the fill order fixed by each work unit.
+    /// `None` picks a depth that yields a few thousand units for order 5.
+    pub split_depth: Option<usize>,
+    /// File used to record finished work units and to resume from.
+    pub checkpoint: Option<PathBuf>,
+}
+
+impl EnumerateConfig {
+    /// Creates a configuration for order `n` using every core and no checkpoint.
+    pub fn new(n: usize) -> Self {
+        Self { n, threads: 0, split_depth: None, checkpoint: None }
+    }
+}
+
+/// Snapshot handed to the progress callback after every finished work unit.
+#[derive(Debug, Clone, Copy)]
+pub struct Progress {
+    /// Work units finished so far, including those restored from a checkpoint.
+    pub completed: usize,
+    /// Total number of work units.
+    pub total: usize,
+    /// Squares counted so far (up to symmetry).
+    pub count: u64,
+}
+
+/// Result of a finished counting run.
+#[derive(Debug, Clone, Copy)]
+pub struct Enumeration {
+    /// Order of the counted squares.
+    pub n: usize,
+    /// Number of essentially different squares (up to rotations and reflections).
+    pub distinct: u64,
+    /// Number of squares when every rotation and reflection is counted separately.
+    pub total: u64,
+    /// Number of work units the search was split into.
+    pub units: usize,
+    /// Number of work units restored from the checkpoint instead of being searched.
+    pub resumed: usize,
+}
+
+/// Errors reported by [`count_magic_squares`].
+#[derive(Debug)]
+pub enum EnumerateError {
+    /// The order is 0 or larger than [`MAX_ORDER`].
+    UnsupportedOrder(usize),
+    /// The checkpoint file could not be read or written.
+    Io(io::Error),
+    /// The checkpoint file belongs to a different run or is malformed.
+    Checkpoint(String),
+}
+
+impl fmt::Display for EnumerateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedOrder(n) => {
+                write!(f, "Order {} is not supported. Counting works for orders 1 to {}.", n, MAX_ORDER)
+            }
+            Self::Io(err) => write!(f, "Checkpoint I/O error: {}", err),
+            Self::Checkpoint(msg) => write!(f, "Invalid checkpoint: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for EnumerateError {}
+
+impl From<io::Error> for EnumerateError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// Counts the normal magic squares of order `config.n` up to symmetry.
+///
+/// `on_progress` is called on the calling thread each time a work unit finishes.
+pub fn count_magic_squares<F>(config: &EnumerateConfig, mut on_progress: F) -> Result<Enumeration, EnumerateError>
+where
+    F: FnMut(&Progress),
+{
+    let n = config.n;
+    if n == 0 || n > MAX_ORDER {
+        return Err(EnumerateError::UnsupportedOrder(n));
+    }
+
+    let plan = Arc::new(Plan::new(n));
+    let depth = config
+        .split_depth
+        .unwrap_or(if n % 2 == 1 { 3 } else { 2 })
+        .min(plan.order.len());
+    let units = plan.work_units(depth);
+
+    // Restore finished units from the checkpoint, if any.
+    let mut done: HashMap<usize, u64> = HashMap::new();
+    if let Some(path) = &config.checkpoint
+        && path.exists()
+    {
+        done = read_checkpoint(path, n, depth, units.len())?;
+    }
+    let mut writer = match &config.checkpoint {
+        Some(path) => Some(open_checkpoint(path, n, depth, units.len(), done.is_empty())?),
+        None => None,
+    };
+
+    let resumed = done.len();
+    let mut progress = Progress {
+        completed: resumed,
+        total: units.len(),
+        count: done.values().sum(),
+    };
+
+    let pending: Vec<usize> = (0..units.len()).filter(|i| !done.contains_key(i)).collect();
+    let num_threads = if config.threads > 0 {
+        config.threads
+    } else {
+        thread::available_parallelism().map(|n| n.get()).unwrap_or(4)
+    }
+    .min(pending.len().max(1));
+
+    let units = Arc::new(units);
+    let pending = Arc::new(pending);
+    let next = Arc::new(AtomicUsize::new(0));
+    let (tx, rx) = mpsc::channel();
+
+    // Workers pull the next pending unit until the queue is drained, so long and short
+    // units balance out without a fixed partition.
+    let handles: Vec<_> = (0..num_threads)
+        .map(|_| {
+            let plan = Arc::clone(&plan);
+            let units = Arc::clone(&units);
+            let pending = Arc::clone(&pending);
+            let next = Arc::clone(&next);
+            let tx = tx.clone();
+
+            thread::spawn(move || {
+                loop {
+                    let slot = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(&unit) = pending.get(slot) else { break };
+                    let count = plan.count_unit(&units[unit]);
+                    if tx.send((unit, count)).is_err() {
+                        break;
+                    }
+                }
+            })
+        })
+        .collect();
+
+    // Close the sender so the receiver knows when to stop
+    drop(tx);
+
+    for (unit, count) in rx {
+        if let Some(w) = writer.as_mut() {
+            writeln!(w, "done {} {}", unit, count)?;
+            w.flush()?;
+        }
+        progress.completed += 1;
+        progress.count += count;
+        on_progress(&progress);
+    }
+
+    for handle in handles {
+        handle.join().expect("enumeration worker panicked");
+    }
+
+    Ok(Enumeration {
+        n,
+        distinct: progress.count,
+        total: if n == 1 { progress.count } else { progress.count * 8 },
+        units: progress.total,
+        resumed,
+    })
+}
+
+fn read_checkpoint(path: &PathBuf, n: usize, depth: usize, units: usize) -> Result<HashMap<usize, u64>, EnumerateError> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut done = HashMap::new();
+    let expected = [("n", n), ("depth", depth), ("units", units)];
+    let mut seen_header = 0;
+
+    for line in reader.lines() {
+        let line = line?;
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.as_slice() {
+            [] => {}
+            [first, ..] if first.starts_with('#') => {}
+            ["done", unit, count] => {
+                let unit: usize = unit
+                    .parse()
+                    .map_err(|_| EnumerateError::Checkpoint(format!("bad unit index '{}'", unit)))?;
+                let count: u64 = count
+                    .parse()
+                    .map_err(|_| EnumerateError::Checkpoint(format!("bad count '{}'", count)))?;
+                if unit >= units {
+                    return Err(EnumerateError::Checkpoint(format!("unit {} out of range", unit)));
+                }
+                done.insert(unit, count);
+            }
+            [key, value] => {
+                let Some(&(_, want)) = expected.iter().find(|(k, _)| k == key) else {
+                    return Err(EnumerateError::Checkpoint(format!("unknown entry '{}'", line)));
+                };
+                if value.parse::<usize>().ok() != Some(want) {
+                    return Err(EnumerateError::Checkpoint(format!(
+                        "{} is {} but this run uses {}",
+                        key, value, want
+                    )));
+                }
+                seen_header += 1;
+            }
+            _ => return Err(EnumerateError::Checkpoint(format!("unreadable line '{}'", line))),
+        }
+    }
+
+    if seen_header != expected.len() && !done.is_empty() {
+        return Err(EnumerateError::Checkpoint("missing run parameters".to_string()));
+    }
+    Ok(done)
+}
+
+fn open_checkpoint(path: &PathBuf, n: usize, depth: usize, units: usize, fresh: bool) -> Result<File, EnumerateError> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(!fresh)
+        .write(true)
+        .truncate(fresh)
+        .open(path)?;
+    if fresh {
+        writeln!(file, "{}", CHECKPOINT_HEADER)?;
+        writeln!(file, "n {}", n)?;
+        writeln!(file, "depth {}", depth)?;
+        writeln!(file, "units {}", units)?;
+        file.flush()?;
+    }
+    Ok(file)
+}
+
+/// Static description of the search: the order in which cells are filled and, for each
+/// step, which lines are touched, closed, and which corner comparisons become checkable.
+struct Plan {
+    n: usize,
+    magic_constant: u32,
+    /// Cell indices in fill order.
+    order: Vec<usize>,
+    /// Lines (rows, columns, diagonals) passing through each cell.
+    cell_lines: Vec<Vec<usize>>,
+    /// Number of cells in each line that are filled after each step.
+    line_len: usize,
+    /// For each step, the line whose last cell is placed at that step, if any.
+    forced_by: Vec<Option<usize>>,
+    /// For each step, corner pairs `(smaller, larger)` whose second cell is placed at that step.
+    symmetry: Vec<Vec<(usize, usize)>>,
+}
+
+impl Plan {
+    fn new(n: usize) -> Self {
+        let cells = n * n;
+        let rows = 0..n;
+        let cols = n..2 * n;
+        let diag = 2 * n;
+        let anti = 2 * n + 1;
+
+        let mut cell_lines = vec![Vec::new(); cells];
+        for r in 0..n {
+            for c in 0..n {
+                let lines = &mut cell_lines[r * n + c];
+                lines.push(rows.start + r);
+                lines.push(cols.start + c);
+                if r == c {
+                    lines.push(diag);
+                }
+                if r + c == n - 1 {
+                    lines.push(anti);
+                }
+            }
+        }
+
+        // Fill order: centre, both diagonals, then alternate rows and columns from the
+        // outside in. Filling the diagonals first places the corners early, so symmetry
+        // breaking prunes near the root, and each later line closes after few free cells.
+        let mut order = Vec::with_capacity(cells);
+        let mut placed = vec![false; cells];
+        let mut push = |idx: usize, order: &mut Vec<usize>| {
+            if !placed[idx] {
+                placed[idx] = true;
+                order.push(idx);
+            }
+        };
+        if n % 2 == 1 {
+            push((n / 2) * n + n / 2, &mut order);
+        }
+        for i in 0..n {
+            push(i * n + i, &mut order);
+        }
+        for i in 0..n {
+            push(i * n + (n - 1 - i), &mut order);
+        }
+        let mut line_order = Vec::new();
+        for k in 0..n.div_ceil(2) {
+            line_order.push((true, k));
+            line_order.push((false, k));
+            line_order.push((true, n - 1 - k));
+            line_order.push((false, n - 1 - k));
+        }
+        for (is_row, k) in line_order {
+            for j in 0..n {
+                let idx = if is_row { k * n + j } else { j * n + k };
+                push(idx, &mut order);
+            }
+        }
+
+        // Work out which step closes each line.
+        let mut remaining = vec![n; 2 * n + 2];
+        let mut forced_by = vec![None; cells];
+        for (step, &idx) in order.iter().enumerate() {
+            for &line in &cell_lines[idx] {
+                remaining[line] -= 1;
+                if remaining[line] == 0 && forced_by[step].is_none() {
+                    forced_by[step] = Some(line);
+                }
+            }
+        }
+
+        // Frénicle symmetry breaking on the corners.
+        let (tl, tr, bl, br) = (0, n - 1, (n - 1) * n, cells - 1);
+        let pairs = if n > 1 { vec![(tl, tr), (tl, bl), (tl, br), (tr, bl)] } else { Vec::new() };
+        let position: Vec<usize> = {
+            let mut pos = vec![0; cells];
+            for (step, &idx) in order.iter().enumerate() {
+                pos[idx] = step;
+            }
+            pos
+        };
+        let mut symmetry = vec![Vec::new(); cells];
+        for (a, b) in pairs {
+            let step = position[a].max(position[b]);
+            symmetry[step].push((a, b));
+        }
+
+        Self {
+            n,
+            magic_constant: (n * (cells + 1) / 2) as u32,
+            order,
+            cell_lines,
+            line_len: n,
+            forced_by,
+            symmetry,
+        }
+    }
+
+    /// Enumerates every consistent assignment of the first `depth` cells of the fill order.
+    fn work_units(&self, depth: usize) -> Vec<Vec<u8>> {
+        let mut units = Vec::new();
+        let mut state = State::new(self);
+        let mut prefix = Vec::with_capacity(depth);
+        self.collect_units(&mut state, 0, depth, &mut prefix, &mut units);
+        units
+    }
+
+    fn collect_units(&self, state: &mut State, step: usize, depth: usize, prefix: &mut Vec<u8>, units: &mut Vec<Vec<u8>>) {
+        if step == depth {
+            units.push(prefix.clone());
+            return;
+        }
+        for value in self.candidates(state, step) {
+            if self.place(state, step, value) {
+                prefix.push(value);
+                self.collect_units(state, step + 1, depth, prefix, units);
+                prefix.pop();
+            }
+            self.unplace(state, step, value);
+        }
+    }
+
+    /// Counts the squares below a single work unit.
+    fn count_unit(&self, prefix: &[u8]) -> u64 {
+        let mut state = State::new(self);
+        for (step, &value) in prefix.iter().enumerate() {
+            if !self.place(&mut state, step, value) {
+                return 0;
+            }
+        }
+        self.count_from(&mut state, prefix.len())
+    }
+
+    fn count_from(&self, state: &mut State, step: usize) -> u64 {
+        if step == self.order.len() {
+            return 1;
+        }
+        let mut total = 0;
+        for value in self.candidates(state, step) {
+            if self.place(state, step, value) {
+                total += self.count_from(state, step + 1);
+            }
+            self.unplace(state, step, value);
+        }
+        total
+    }
+
+    /// Values worth trying at `step`: the forced value if the step closes a line,
+    /// otherwise every available value.
+    fn candidates(&self, state: &State, step: usize) -> Candidates {
+        if let Some(line) = self.forced_by[step] {
+            let need = self.magic_constant as i64 - state.line_sum[line] as i64;
+            let max = (self.n * self.n) as i64;
+            let mask = if need >= 1 && need <= max && state.available & (1u64 << need) != 0 {
+                1u64 << need
+            } else {
+                0
+            };
+            Candidates { mask }
+        } else {
+            Candidates { mask: state.available }
+        }
+    }
+
+    /// Places `value` at `step` and reports whether the partial square is still feasible.
+    /// The caller must always undo the placement with [`Plan::unplace`].
+    fn place(&self, state: &mut State, step: usize, value: u8) -> bool {
+        let idx = self.order[step];
+        state.cells[idx] = value;
+        state.available &= !(1u64 << value);
+
+        let mut ok = true;
+        for &line in &self.cell_lines[idx] {
+            state.line_sum[line] += value as u32;
+            state.line_filled[line] += 1;
+            if ok {
+                ok = self.line_feasible(state, line);
+            }
+        }
+        if ok {
+            ok = self.symmetry[step].iter().all(|&(a, b)| state.cells[a] < state.cells[b]);
+        }
+        ok
+    }
+
+    fn unplace(&self, state: &mut State, step: usize, value: u8) {
+        let idx = self.order[step];
+        state.cells[idx] = 0;
+        state.available |= 1u64 << value;
+        for &line in &self.cell_lines[idx] {
+            state.line_sum[line] -= value as u32;
+            state.line_filled[line] -= 1;
+        }
+    }
+
+    /// Checks that the cells still missing from `line` can reach the magic constant
+    /// with the smallest and largest values that are still available.
+    fn line_feasible(&self, state: &State, line: usize) -> bool {
+        let sum = state.line_sum[line];
+        if sum > self.magic_constant {
+            return false;
+        }
+        let left = self.line_len - state.line_filled[line] as usize;
+        let need = self.magic_constant - sum;
+        if left == 0 {
+            return need == 0;
+        }
+
+        let mut low = 0;
+        let mut mask = state.available;
+        for _ in 0..left {
+            if mask == 0 {
+                return false;
+            }
+            let v = mask.trailing_zeros();
+            low += v;
+            mask &= mask - 1;
+        }
+        if low > need {
+            return false;
+        }
+
+        let mut high = 0;
+        let mut mask = state.available;
+        for _ in 0..left {
+            let v = 63 - mask.leading_zeros();
+            high += v;
+            mask &= !(1u64 << v);
+        }
+        high >= need
+    }
+}
+
+/// Mutable search state for one worker.
+struct State {
+    cells: Vec<u8>,
+    /// Bit `v` is set while value `v` has not been placed yet.
+    available: u64,
+    line_sum: Vec<u32>,
+    line_filled: Vec<u8>,
+}
+
+impl State {
+    fn new(plan: &Plan) -> Self {
+        let cells = plan.n * plan.n;
+        Self {
+            cells: vec![0; cells],
+            available: ((1u64 << cells) - 1) << 1,
+            line_sum: vec![0; 2 * plan.n + 2],
+            line_filled: vec![0; 2 * plan.n + 2],
+        }
+    }
+}
+
+/// Iterator over the set bits of a value mask, lowest first.
+struct Candidates {
+    mask: u64,
+}
+
+impl Iterator for Candidates {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if self.mask == 0 {
+            return None;
+        }
+        let v = self.mask.trailing_zeros() as u8;
+        self.mask &= self.mask - 1;
+        Some(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(n: usize) -> Enumeration {
+        count_magic_squares(&EnumerateConfig::new(n), |_| {}).expect("order is supported")
+    }
+
+    #[test]
+    fn counts_small_orders() {
+        assert_eq!(count(1).distinct, 1);
+        assert_eq!(count(2).distinct, 0);
+        let order3 = count(3);
+        assert_eq!(order3.distinct, 1);
+        assert_eq!(order3.total, 8);
+    }
+
+    #[test]
+    fn counts_order_4() {
+        let result = count(4);
+        assert_eq!(result.distinct, 880);
+        assert_eq!(result.total, 7040);
+    }
+
+    #[test]
+    fn resumes_from_checkpoint() {
+        let path = std::env::temp_dir().join(format!("msq-enum-{}.ckpt", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let config = EnumerateConfig {
+            checkpoint: Some(path.clone()),
+            threads: 2,
+            ..EnumerateConfig::new(4)
+        };
+
+        // Keep only the header and the first few finished units to simulate an interruption.
+        count_magic_squares(&config, |_| {}).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let truncated: Vec<&str> = text.lines().take(10).collect();
+        std::fs::write(&path, truncated.join("\n") + "\n").unwrap();
+
+        let resumed = count_magic_squares(&config, |_| {}).unwrap();
+        assert_eq!(resumed.resumed, 6);
+        assert_eq!(resumed.distinct, 880);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_unsupported_orders() {
+        assert!(count_magic_squares(&EnumerateConfig::new(0), |_| {}).is_err());
+        assert!(count_magic_squares(&EnumerateConfig::new(6), |_| {}).is_err());
+    }
+}
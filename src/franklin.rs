@@ -0,0 +1,158 @@
+//! Franklin squares: semi-magic squares whose bent diagonals, half rows and half columns, and
+//! $2 \times 2$ blocks all sum to their share of the magic constant (see
+//! [`validator::is_franklin`](crate::validator::is_franklin)).
+//!
+//! Both of Benjamin Franklin's squares split into two digits, $v - 1 = nA + B$, with
+//!
+//! - $A_{r,c} = p(c)$ on even rows and $n - 1 - p(c)$ on odd rows, and
+//! - $B_{r,c} = q(r)$ on even columns and $n - 1 - q(r)$ on odd columns,
+//!
+//! for permutations $p$ and $q$ of $0..n$. Rows, columns, half columns, $2 \times 2$ blocks and
+//! half of the bent diagonals of $A$ then sum correctly whatever $p$ is. The rest need the
+//! first half of $p$ to sum to $n(n-1)/4$, and its alternating sum over the first half to equal
+//! the one over the second half. The same holds for $B$ with the roles of rows and columns
+//! swapped. Every value appears once when complementary values $v$ and $n - 1 - v$ sit at
+//! positions of opposite parity in one of $p$, $q$ and of the same parity in the other.
+//!
+//! The generator draws such $p$ and $q$ at random, and randomly swaps the two digits.
+
+use std::fmt;
+
+use crate::rng::Lcg;
+
+/// Random arrangements of the complementary pairs tried for each digit before giving up.
+/// For order 16 between one in 100 and one in 500 is balanced, depending on the parity layout.
+const DRAWS: usize = 100_000;
+
+/// Errors returned by [`generate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FranklinError {
+    /// Franklin squares are generated for orders 8 and 16 only.
+    UnsupportedOrder(usize),
+    /// No balanced digit was drawn.
+    NotFound(usize),
+}
+
+impl fmt::Display for FranklinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedOrder(n) => write!(f, "Order {} is not supported. Franklin squares are built for orders 8 and 16.", n),
+            Self::NotFound(n) => write!(f, "No Franklin square of order {} was found.", n),
+        }
+    }
+}
+
+impl std::error::Error for FranklinError {}
+
+/// Generates a random Franklin square of order 8 or 16.
+pub fn generate(n: usize, rng: &mut Lcg) -> Result<Vec<u32>, FranklinError> {
+    if n != 8 && n != 16 {
+        return Err(FranklinError::UnsupportedOrder(n));
+    }
+
+    let split = rng.next_u32() & 1 == 0;
+    let p = draw(n, split, rng).ok_or(FranklinError::NotFound(n))?;
+    let q = draw(n, !split, rng).ok_or(FranklinError::NotFound(n))?;
+    let swap = rng.next_u32() & 1 == 0;
+
+    let mut grid = vec![0; n * n];
+    for r in 0..n {
+        for c in 0..n {
+            let a = if r % 2 == 0 { p[c] } else { n - 1 - p[c] };
+            let b = if c % 2 == 0 { q[r] } else { n - 1 - q[r] };
+            let (high, low) = if swap { (b, a) } else { (a, b) };
+            grid[r * n + c] = (high * n + low) as u32 + 1;
+        }
+    }
+    Ok(grid)
+}
+
+/// Draws a balanced permutation of `0..n` in which each value $v$ and its complement
+/// $n - 1 - v$ sit at positions of opposite parity (`split`) or of the same parity.
+fn draw(n: usize, split: bool, rng: &mut Lcg) -> Option<Vec<usize>> {
+    let mut evens: Vec<usize> = (0..n).step_by(2).collect();
+    let mut odds: Vec<usize> = (1..n).step_by(2).collect();
+    let mut values: Vec<usize> = (0..n / 2).collect();
+    let mut p = vec![0; n];
+
+    for _ in 0..DRAWS {
+        rng.shuffle(&mut evens);
+        rng.shuffle(&mut odds);
+        rng.shuffle(&mut values);
+        let slots: Vec<(usize, usize)> = if split {
+            evens.iter().copied().zip(odds.iter().copied()).collect()
+        } else {
+            evens.chunks(2).chain(odds.chunks(2)).map(|pair| (pair[0], pair[1])).collect()
+        };
+        for (&(i, j), &v) in slots.iter().zip(&values) {
+            let (i, j) = if rng.next_u32() & 1 == 0 { (i, j) } else { (j, i) };
+            p[i] = v;
+            p[j] = n - 1 - v;
+        }
+        if balanced(&p) {
+            return Some(p);
+        }
+    }
+    None
+}
+
+/// Whether the first half of `p` sums to $n(n-1)/4$ and both halves have the same
+/// alternating sum.
+fn balanced(p: &[usize]) -> bool {
+    let n = p.len();
+    let half = n / 2;
+    let alternating = |range: std::ops::Range<usize>| -> i64 {
+        range.map(|i| if i % 2 == 0 { p[i] as i64 } else { -(p[i] as i64) }).sum()
+    };
+    p[..half].iter().sum::<usize>() == n * (n - 1) / 4 && alternating(0..half) == alternating(half..n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog;
+    use crate::validator::{has_bent_diagonals, has_block_sums, has_half_line_sums, is_franklin};
+
+    #[test]
+    fn generates_franklin_squares() {
+        let mut rng = Lcg::new_with_seed(1);
+        for n in [8, 16] {
+            for _ in 0..20 {
+                let grid = generate(n, &mut rng).unwrap_or_else(|e| panic!("order {}: {}", n, e));
+                assert!(is_franklin(&grid, n), "order {}: {:?}", n, grid);
+            }
+        }
+    }
+
+    #[test]
+    fn generated_squares_vary() {
+        let mut rng = Lcg::new_with_seed(2);
+        let first = generate(8, &mut rng).unwrap();
+        assert!((0..10).any(|_| generate(8, &mut rng).unwrap() != first));
+    }
+
+    #[test]
+    fn checks_each_franklin_property() {
+        for entry in [catalog::FRANKLIN_8, catalog::FRANKLIN_16] {
+            assert!(is_franklin(entry.grid, entry.n), "{}", entry.name);
+        }
+
+        // The Chautisa square is pandiagonal and its 2x2 blocks sum to 34, but its bent
+        // diagonals and half lines do not have their share of the constant.
+        let chautisa = catalog::CHAUTISA.grid;
+        assert!(has_block_sums(chautisa, 4));
+        assert!(!has_bent_diagonals(chautisa, 4) && !has_half_line_sums(chautisa, 4) && !is_franklin(chautisa, 4));
+
+        let mut grid = catalog::FRANKLIN_8.grid.to_vec();
+        grid.swap(0, 1);
+        assert!(!is_franklin(&grid, 8));
+    }
+
+    #[test]
+    fn rejects_unsupported_orders() {
+        let mut rng = Lcg::new_with_seed(3);
+        for n in [0, 4, 12, 32] {
+            assert_eq!(generate(n, &mut rng), Err(FranklinError::UnsupportedOrder(n)));
+        }
+    }
+}
@@ -1,4 +1,6 @@
+use crate::latin;
 use crate::rng::Lcg;
+use crate::transform::Symmetry;
 
 /// Trait defining the interface for a Magic Square Generator.
 /// Implementations of this trait handle specific cases based on the order $n$.
@@ -10,9 +12,9 @@ pub trait MagicGenerator {
 
 /// Factory function to create the appropriate generator based on the order n.
 pub fn create<'a>(n: usize, rng: &'a mut Lcg) -> Box<dyn MagicGenerator + 'a> {
-    if n % 2 != 0 {
+    if !n.is_multiple_of(2) {
         Box::new(OddGenerator::new(rng))
-    } else if n % 4 != 0 {
+    } else if !n.is_multiple_of(4) {
         Box::new(SinglyEvenGenerator::new(rng))
     } else {
         Box::new(DoublyEvenGenerator::new(rng))
@@ -32,6 +34,9 @@ impl<'a> OddGenerator<'a> {
 
     /// Generates two base arrays (A and B) used for constructing the final square.
     /// This variation allows for additional shuffling/transformations if needed.
+    ///
+    /// A and B are orthogonal Latin squares whose diagonals are either constant or a
+    /// permutation, which is what [`latin::combine`] needs to give a magic square.
     fn generate_base_arrays(&mut self, n: usize) -> (Vec<u32>, Vec<u32>) {
         let mut base_a = vec![0; n * n];
         let mut base_b = vec![0; n * n];
@@ -124,7 +129,7 @@ impl<'a> OddGenerator<'a> {
 
 impl<'a> MagicGenerator for OddGenerator<'a> {
     fn generate(&mut self, n: usize) -> Vec<u32> {
-        let (raw_a, raw_b) = self.generate_base_arrays(n);
+        let (mut raw_a, mut raw_b) = self.generate_base_arrays(n);
         
         // Check safety of the generated base arrays.
         let safe_a = self.is_safe_diag(&raw_a, n);
@@ -134,14 +139,11 @@ impl<'a> MagicGenerator for OddGenerator<'a> {
         let map_a = self.get_shuffled_mapping(n, safe_a);
         let map_b = self.get_shuffled_mapping(n, safe_b);
 
-        let mut grid = vec![0; n * n];
-        for i in 0..(n * n) {
-            let val_a = map_a[raw_a[i] as usize];
-            let val_b = map_b[raw_b[i] as usize];
-            // Combine the two Greaco-Latin squares: Final = n * A + B + 1
-            grid[i] = (n as u32 * val_a) + val_b + 1;
-        }
-        grid
+        // Relabel the symbols in place rather than in fresh copies.
+        raw_a.iter_mut().for_each(|v| *v = map_a[*v as usize]);
+        raw_b.iter_mut().for_each(|v| *v = map_b[*v as usize]);
+        // Combine the two Greaco-Latin squares: Final = n * A + B + 1
+        latin::combine(&raw_a, &raw_b, n)
     }
 }
 
@@ -242,13 +244,14 @@ impl<'a> DoublyEvenGenerator<'a> {
 impl<'a> MagicGenerator for DoublyEvenGenerator<'a> {
     fn generate(&mut self, n: usize) -> Vec<u32> {
         let mut grid = vec![0; n * n];
-        
-        let do_transpose = self.rng.next_range(0, 2) == 1;
-        let do_flip_r = self.rng.next_range(0, 2) == 1;
-        let do_flip_c = self.rng.next_range(0, 2) == 1;
 
-        for r in 0..n {
-            for c in 0..n {
+        // A random rotation or reflection varies the result. Each output cell looks up the
+        // cell it comes from, so no position table is built.
+        let symmetry = Symmetry::ALL[self.rng.next_range(0, 8)];
+
+        for tr in 0..n {
+            for tc in 0..n {
+                let (r, c) = symmetry.source(n, tr, tc);
                 let val_seq = (r * n + c + 1) as u32;
                 let val_inv = ((n * n) as u32 + 1) - val_seq;
 
@@ -257,19 +260,7 @@ impl<'a> MagicGenerator for DoublyEvenGenerator<'a> {
                 let c4 = c % 4;
                 let is_diag = (r4 == c4) || (r4 + c4 == 3);
 
-                let val = if is_diag { val_inv } else { val_seq };
-
-                // Apply random variations to target indices
-                let mut tr = r;
-                let mut tc = c;
-                
-                if do_flip_r { tr = n - 1 - tr; }
-                if do_flip_c { tc = n - 1 - tc; }
-                if do_transpose { 
-                   let temp = tr; tr = tc; tc = temp; 
-                }
-                
-                grid[tr * n + tc] = val;
+                grid[tr * n + tc] = if is_diag { val_inv } else { val_seq };
             }
         }
         grid
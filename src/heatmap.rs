@@ -0,0 +1,231 @@
+//! Heatmap images of squares, for orders whose numbers are too small to read.
+//!
+//! Each cell becomes a block of `scale` x `scale` pixels coloured by its value, from the
+//! first colour of a [`Gradient`] for the smallest value to the last for the largest. The
+//! images make the structure of the constructions visible at a glance: the diagonal bands of
+//! the Siamese method, the quadrants of LUX squares and the truth-grid pattern of doubly even
+//! orders. They are written as binary PPM (`P6`) or as PNG through [`crate::png`].
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::png;
+
+/// Images wider or taller than this are refused, as most viewers cannot open them.
+pub const MAX_SIDE: usize = 32_768;
+
+/// Side in pixels aimed at by the default scale: small squares are enlarged to about this
+/// size, squares larger than this get one pixel per cell.
+pub const DEFAULT_SIDE: usize = 512;
+
+/// Colours interpolated linearly between evenly spaced stops.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gradient {
+    stops: Vec<[u8; 3]>,
+}
+
+impl Gradient {
+    /// The named gradients accepted by [`Gradient::from_str`].
+    pub const NAMES: [&'static str; 4] = ["viridis", "inferno", "grayscale", "slate"];
+
+    /// A gradient through `stops`, which needs at least one colour.
+    pub fn new(stops: Vec<[u8; 3]>) -> Option<Self> {
+        (!stops.is_empty()).then_some(Self { stops })
+    }
+
+    /// Matplotlib's perceptually uniform purple-to-yellow gradient.
+    pub fn viridis() -> Self {
+        Self { stops: vec![[0x44, 0x01, 0x54], [0x3B, 0x52, 0x8B], [0x21, 0x91, 0x8C], [0x5E, 0xC9, 0x62], [0xFD, 0xE7, 0x25]] }
+    }
+
+    /// Matplotlib's black-to-yellow gradient through purple and orange.
+    pub fn inferno() -> Self {
+        Self { stops: vec![[0x00, 0x00, 0x04], [0x57, 0x10, 0x6E], [0xBC, 0x37, 0x54], [0xF9, 0x8E, 0x09], [0xFC, 0xFF, 0xA4]] }
+    }
+
+    /// Black to white.
+    pub fn grayscale() -> Self {
+        Self { stops: vec![[0, 0, 0], [0xFF, 0xFF, 0xFF]] }
+    }
+
+    /// The web app's colours: card background, accent, then text.
+    pub fn slate() -> Self {
+        Self { stops: vec![[0x1E, 0x29, 0x3B], [0x38, 0xBD, 0xF8], [0xF8, 0xFA, 0xFC]] }
+    }
+
+    /// The colour at `t` in `0.0..=1.0`.
+    pub fn color(&self, t: f64) -> [u8; 3] {
+        let segments = self.stops.len() - 1;
+        if segments == 0 {
+            return self.stops[0];
+        }
+        let x = t.clamp(0.0, 1.0) * segments as f64;
+        let i = (x as usize).min(segments - 1);
+        let f = x - i as f64;
+        let (a, b) = (self.stops[i], self.stops[i + 1]);
+        [0, 1, 2].map(|k| (a[k] as f64 + (b[k] as f64 - a[k] as f64) * f).round() as u8)
+    }
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        Self::viridis()
+    }
+}
+
+impl FromStr for Gradient {
+    type Err = ();
+
+    /// Parses a gradient name, or comma-separated `#rrggbb` stops such as `#000000,#ff0000`.
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "viridis" => return Ok(Self::viridis()),
+            "inferno" => return Ok(Self::inferno()),
+            "grayscale" | "greyscale" => return Ok(Self::grayscale()),
+            "slate" => return Ok(Self::slate()),
+            _ => {}
+        }
+        let stops = s
+            .split(',')
+            .map(|stop| {
+                let hex = stop.trim().strip_prefix('#').filter(|hex| hex.len() == 6 && hex.is_ascii()).ok_or(())?;
+                let channel = |k: usize| u8::from_str_radix(&hex[2 * k..2 * k + 2], 16).map_err(|_| ());
+                Ok([channel(0)?, channel(1)?, channel(2)?])
+            })
+            .collect::<Result<Vec<_>, ()>>()?;
+        Self::new(stops).ok_or(())
+    }
+}
+
+/// Image formats written by [`render`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+}
+
+/// Errors returned by [`render`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeatmapError {
+    /// The grid does not hold $n^2$ values.
+    WrongLength { n: usize, len: usize },
+    /// The scale is 0, or the image would be wider than [`MAX_SIDE`] pixels.
+    InvalidSize { n: usize, scale: usize },
+}
+
+impl fmt::Display for HeatmapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WrongLength { n, len } => write!(f, "An order {} square has {} cells, not {}.", n, n * n, len),
+            Self::InvalidSize { n, scale } => write!(
+                f,
+                "An order {} square at {} pixels per cell does not fit in 1..={} pixels.",
+                n, scale, MAX_SIDE
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HeatmapError {}
+
+/// RGB pixels of the heatmap, `3 * (n * scale)^2` bytes in row-major order.
+pub fn pixels(grid: &[u32], n: usize, gradient: &Gradient, scale: usize) -> Result<Vec<u8>, HeatmapError> {
+    if grid.len() != n * n {
+        return Err(HeatmapError::WrongLength { n, len: grid.len() });
+    }
+    let side = n.checked_mul(scale).filter(|&side| (1..=MAX_SIDE).contains(&side));
+    let side = side.ok_or(HeatmapError::InvalidSize { n, scale })?;
+
+    let min = grid.iter().copied().min().unwrap_or(0);
+    let max = grid.iter().copied().max().unwrap_or(0);
+    let range = (max - min).max(1) as f64;
+
+    let mut out = Vec::with_capacity(3 * side * side);
+    let mut row = Vec::with_capacity(3 * side);
+    for cells in grid.chunks(n) {
+        row.clear();
+        for &v in cells {
+            let color = gradient.color((v - min) as f64 / range);
+            for _ in 0..scale {
+                row.extend_from_slice(&color);
+            }
+        }
+        for _ in 0..scale {
+            out.extend_from_slice(&row);
+        }
+    }
+    Ok(out)
+}
+
+/// Renders the heatmap as a PPM or PNG file.
+pub fn render(grid: &[u32], n: usize, gradient: &Gradient, scale: usize, format: ImageFormat) -> Result<Vec<u8>, HeatmapError> {
+    let rgb = pixels(grid, n, gradient, scale)?;
+    let side = n * scale;
+    Ok(match format {
+        ImageFormat::Ppm => {
+            let mut ppm = format!("P6\n{} {}\n255\n", side, side).into_bytes();
+            ppm.extend_from_slice(&rgb);
+            ppm
+        }
+        ImageFormat::Png => png::encode_rgb(side as u32, side as u32, &rgb),
+    })
+}
+
+/// Pixels per cell that make an image about `target` pixels wide, at least 1.
+pub fn scale_for(n: usize, target: usize) -> usize {
+    (target / n.max(1)).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog;
+
+    #[test]
+    fn interpolates_gradients() {
+        let gray = Gradient::grayscale();
+        assert_eq!((gray.color(0.0), gray.color(0.5), gray.color(1.0)), ([0; 3], [128; 3], [255; 3]));
+        assert_eq!(gray.color(-1.0), [0; 3]);
+
+        let custom: Gradient = "#ff0000, #00ff00,#0000ff".parse().unwrap();
+        assert_eq!((custom.color(0.5), custom.color(0.75)), ([0, 255, 0], [0, 128, 128]));
+        assert_eq!(Gradient::viridis().color(1.0), [0xFD, 0xE7, 0x25]);
+        for name in Gradient::NAMES {
+            assert!(name.parse::<Gradient>().is_ok(), "{}", name);
+        }
+        assert_eq!("#12345".parse::<Gradient>(), Err(()));
+        assert_eq!("rainbow".parse::<Gradient>(), Err(()));
+    }
+
+    #[test]
+    fn maps_values_to_scaled_pixels() {
+        // Lo Shu: 4 9 2 / 3 5 7 / 8 1 6, so 1 is black and 9 white in grayscale.
+        let rgb = pixels(catalog::LO_SHU.grid, 3, &Gradient::grayscale(), 2).unwrap();
+        assert_eq!(rgb.len(), 3 * 6 * 6);
+        let at = |x: usize, y: usize| &rgb[3 * (y * 6 + x)..3 * (y * 6 + x) + 3];
+        assert_eq!((at(2, 0), at(3, 1)), (&[255u8; 3][..], &[255u8; 3][..]));
+        assert_eq!((at(2, 4), at(3, 5)), (&[0u8; 3][..], &[0u8; 3][..]));
+        assert_eq!(at(0, 0), &[96u8; 3][..]);
+    }
+
+    #[test]
+    fn writes_ppm_and_png() {
+        let grid = catalog::DURER.grid;
+        let ppm = render(grid, 4, &Gradient::default(), 3, ImageFormat::Ppm).unwrap();
+        assert!(ppm.starts_with(b"P6\n12 12\n255\n"));
+        assert_eq!(ppm.len(), 13 + 3 * 144);
+
+        let png = render(grid, 4, &Gradient::default(), 3, ImageFormat::Png).unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert_eq!(png[16..24], [0, 0, 0, 12, 0, 0, 0, 12]);
+    }
+
+    #[test]
+    fn rejects_invalid_sizes() {
+        let grid = catalog::LO_SHU.grid;
+        assert_eq!(pixels(grid, 3, &Gradient::default(), 0), Err(HeatmapError::InvalidSize { n: 3, scale: 0 }));
+        assert_eq!(pixels(grid, 3, &Gradient::default(), MAX_SIDE), Err(HeatmapError::InvalidSize { n: 3, scale: MAX_SIDE }));
+        assert_eq!(pixels(grid, 2, &Gradient::default(), 1), Err(HeatmapError::WrongLength { n: 2, len: 9 }));
+        assert_eq!((scale_for(3, 600), scale_for(7000, 600)), (200, 1));
+    }
+}
@@ -0,0 +1,327 @@
+//! Magic cubes and $d$-dimensional magic hypercubes.
+//!
+//! A magic hypercube of order $n$ and dimension $d$ holds $1..n^d$ once each. Every line
+//! parallel to an axis, and each of the $2^{d-1}$ space diagonals through opposite corners,
+//! sums to $n(n^d+1)/2$. A magic cube is the case $d = 3$, and $d = 2$ gives a magic square.
+//!
+//! Cells are stored in a flat `Vec` like the square generators: the cell with coordinates
+//! $(x_0, ..., x_{d-1})$ is at index $\sum_i x_i n^i$, so $x_0$ is the column and for $d = 2$
+//! $x_1$ is the row.
+//!
+//! - **Prime orders**: each base-$n$ digit of a value is a linear form of the coordinates
+//!   mod $n$ (a Latin hypercube), chosen so that the forms are independent and every
+//!   coefficient is non-zero.
+//! - **Other odd orders**: hypercubes of the prime factors are combined (Kronecker product).
+//! - **Doubly-even orders**: cells are numbered in order and a fixed pattern of them is
+//!   replaced by the complement $n^d + 1 - x$, as for doubly-even squares.
+//!
+//! Singly-even orders are not supported.
+
+use std::fmt;
+
+use crate::rng::Lcg;
+
+/// Random coefficient matrices tried per prime before giving up.
+const MATRIX_ATTEMPTS: usize = 1000;
+
+/// Errors reported by [`MagicCube::generate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HypercubeError {
+    /// Only odd and doubly-even orders are supported.
+    UnsupportedOrder(usize),
+    /// Hypercubes need at least two dimensions.
+    UnsupportedDimension(usize),
+    /// $n^d$ does not fit the `u32` values.
+    TooLarge { n: usize, dimensions: usize },
+}
+
+impl fmt::Display for HypercubeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedOrder(n) => {
+                write!(f, "Order {} is not supported. Hypercubes are generated for odd and doubly-even orders.", n)
+            }
+            Self::UnsupportedDimension(d) => {
+                write!(f, "Dimension {} is not supported. Hypercubes need at least 2 dimensions.", d)
+            }
+            Self::TooLarge { n, dimensions } => {
+                write!(f, "Order {} in {} dimensions is too large: {}^{} does not fit in u32.", n, dimensions, n, dimensions)
+            }
+        }
+    }
+}
+
+impl std::error::Error for HypercubeError {}
+
+/// Number of cells $n^d$ of a hypercube, if the values fit in `u32`.
+pub fn cell_count(n: usize, dimensions: usize) -> Option<usize> {
+    let cells = (n as u64).checked_pow(dimensions as u32)?;
+    (cells <= u32::MAX as u64).then_some(cells as usize)
+}
+
+/// Flat index of the cell with the given coordinates, $\sum_i x_i n^i$.
+pub fn index(coords: &[usize], n: usize) -> usize {
+    coords.iter().rev().fold(0, |acc, &x| acc * n + x)
+}
+
+/// Generator for magic cubes, or hypercubes of any dimension.
+pub struct MagicCube<'a> {
+    rng: &'a mut Lcg,
+    dimensions: usize,
+}
+
+impl<'a> MagicCube<'a> {
+    /// A generator for three-dimensional cubes.
+    pub fn new(rng: &'a mut Lcg) -> Self {
+        Self::with_dimensions(rng, 3)
+    }
+
+    /// A generator for hypercubes of `dimensions` dimensions.
+    pub fn with_dimensions(rng: &'a mut Lcg, dimensions: usize) -> Self {
+        Self { rng, dimensions }
+    }
+
+    /// The number of dimensions of the generated hypercubes.
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    /// Generates a magic hypercube of order $n$ as a flat vector of $n^d$ cells.
+    pub fn generate(&mut self, n: usize) -> Result<Vec<u32>, HypercubeError> {
+        let d = self.dimensions;
+        if d < 2 {
+            return Err(HypercubeError::UnsupportedDimension(d));
+        }
+        if n == 0 || n % 4 == 2 {
+            return Err(HypercubeError::UnsupportedOrder(n));
+        }
+        cell_count(n, d).ok_or(HypercubeError::TooLarge { n, dimensions: d })?;
+
+        let cells = if n.is_multiple_of(4) {
+            doubly_even(n, d)
+        } else {
+            let mut cells = vec![1];
+            let mut order = 1;
+            for p in prime_factors(n) {
+                let factor = prime_order(p, d, self.rng).ok_or(HypercubeError::UnsupportedOrder(n))?;
+                cells = compose(&cells, order, &factor, p, d);
+                order *= p;
+            }
+            cells
+        };
+        Ok(self.symmetry(&cells, n))
+    }
+
+    /// Applies a random symmetry of the hypercube: the axes are permuted and some are reversed.
+    /// This maps every line and every space diagonal to another one.
+    fn symmetry(&mut self, cells: &[u32], n: usize) -> Vec<u32> {
+        let d = self.dimensions;
+        let mut axes: Vec<usize> = (0..d).collect();
+        self.rng.shuffle(&mut axes);
+        let reversed: Vec<bool> = (0..d).map(|_| self.rng.next_range(0, 2) == 1).collect();
+
+        let mut out = vec![0; cells.len()];
+        let mut source = vec![0; d];
+        for_each_coords(n, d, |i, x| {
+            for (axis, &to) in axes.iter().enumerate() {
+                source[to] = if reversed[axis] { n - 1 - x[axis] } else { x[axis] };
+            }
+            out[i] = cells[index(&source, n)];
+        });
+        out
+    }
+}
+
+/// Calls `f(index, coords)` for every cell, in index order.
+fn for_each_coords(n: usize, d: usize, mut f: impl FnMut(usize, &[usize])) {
+    let cells = n.pow(d as u32);
+    let mut x = vec![0; d];
+    for i in 0..cells {
+        f(i, &x);
+        for c in x.iter_mut() {
+            *c += 1;
+            if *c < n {
+                break;
+            }
+            *c = 0;
+        }
+    }
+}
+
+/// Doubly-even order: cell $i$ holds $i + 1$, or its complement when an odd number of its
+/// coordinates are $1$ or $2$ mod $4$.
+///
+/// Along any line half of the cells are complemented, and the complemented positions sum to
+/// the same as the others, so the line sum is the average. Space diagonals work the same way.
+fn doubly_even(n: usize, d: usize) -> Vec<u32> {
+    let total = n.pow(d as u32) as u32;
+    let mut cells = vec![0; total as usize];
+    for_each_coords(n, d, |i, x| {
+        let flip = x.iter().filter(|&&c| matches!(c % 4, 1 | 2)).count() % 2 == 1;
+        cells[i] = if flip { total - i as u32 } else { i as u32 + 1 };
+    });
+    cells
+}
+
+/// Prime order: digit $k$ of a value is $(\sum_i a_{k,i} x_i + c_k) \bmod p$.
+///
+/// With every $a_{k,i}$ non-zero, each digit runs through all residues along every line. A
+/// space diagonal moves each digit by a fixed step, which is either a unit (all residues) or
+/// zero, and the constants $c_k$ put the middle digit at the centre cell, which lies on every
+/// diagonal. An invertible matrix makes the values distinct.
+fn prime_order(p: usize, d: usize, rng: &mut Lcg) -> Option<Vec<u32>> {
+    if p == 2 {
+        return None;
+    }
+    let p64 = p as u64;
+    let matrix = (0..MATRIX_ATTEMPTS)
+        .map(|_| (0..d * d).map(|_| rng.next_range(1, p) as u64).collect::<Vec<_>>())
+        .find(|m| is_invertible(m, d, p64))?;
+
+    let middle = (p64 - 1) / 2;
+    let offsets: Vec<u64> = (0..d)
+        .map(|k| {
+            let at_centre = matrix[k * d..(k + 1) * d].iter().map(|&a| a * middle % p64).sum::<u64>() % p64;
+            (middle + p64 - at_centre) % p64
+        })
+        .collect();
+
+    let mut cells = vec![0; p.pow(d as u32)];
+    for_each_coords(p, d, |i, x| {
+        let mut value = 0;
+        for k in (0..d).rev() {
+            let row = &matrix[k * d..(k + 1) * d];
+            let digit = (row.iter().zip(x).map(|(&a, &c)| a * c as u64).sum::<u64>() + offsets[k]) % p64;
+            value = value * p64 + digit;
+        }
+        cells[i] = value as u32 + 1;
+    });
+    Some(cells)
+}
+
+/// Whether a $d \times d$ matrix is invertible mod the prime `p` (Gaussian elimination).
+fn is_invertible(matrix: &[u64], d: usize, p: u64) -> bool {
+    let mut m = matrix.to_vec();
+    for col in 0..d {
+        let Some(pivot) = (col..d).find(|&r| !m[r * d + col].is_multiple_of(p)) else {
+            return false;
+        };
+        for c in 0..d {
+            m.swap(col * d + c, pivot * d + c);
+        }
+        let inverse = pow_mod(m[col * d + col], p - 2, p);
+        for r in col + 1..d {
+            let factor = m[r * d + col] * inverse % p;
+            for c in col..d {
+                m[r * d + c] = (m[r * d + c] + (p - factor) * m[col * d + c]) % p;
+            }
+        }
+    }
+    true
+}
+
+fn pow_mod(mut base: u64, mut exp: u64, p: u64) -> u64 {
+    let mut result = 1;
+    base %= p;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = result * base % p;
+        }
+        base = base * base % p;
+        exp >>= 1;
+    }
+    result
+}
+
+/// Kronecker product of two hypercubes: the order-$pq$ cell at $x$ holds
+/// $(a - 1) q^d + b$, where $a$ is the cell of `outer` at $x / q$ and $b$ the cell of `inner`
+/// at $x \bmod q$. Lines and diagonals of the result run along lines and diagonals of both.
+fn compose(outer: &[u32], p: usize, inner: &[u32], q: usize, d: usize) -> Vec<u32> {
+    let n = p * q;
+    let inner_cells = inner.len() as u32;
+    let mut cells = vec![0; n.pow(d as u32)];
+    let (mut high, mut low) = (vec![0; d], vec![0; d]);
+    for_each_coords(n, d, |i, x| {
+        for axis in 0..d {
+            high[axis] = x[axis] / q;
+            low[axis] = x[axis] % q;
+        }
+        cells[i] = (outer[index(&high, p)] - 1) * inner_cells + inner[index(&low, q)];
+    });
+    cells
+}
+
+/// Prime factors of `n` in increasing order, with repetition.
+fn prime_factors(mut n: usize) -> Vec<usize> {
+    let mut factors = Vec::new();
+    let mut p = 2;
+    while p * p <= n {
+        while n.is_multiple_of(p) {
+            factors.push(p);
+            n /= p;
+        }
+        p += 1;
+    }
+    if n > 1 {
+        factors.push(n);
+    }
+    factors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validator::{check_hypercube_properties, check_magic_properties};
+
+    #[test]
+    fn magic_cubes() {
+        let mut rng = Lcg::new_with_seed(1);
+        let mut generator = MagicCube::new(&mut rng);
+        for n in [1, 3, 4, 5, 7, 8, 9, 12, 15] {
+            let cube = generator.generate(n).unwrap();
+            assert!(check_hypercube_properties(&cube, n, 3), "order {}", n);
+        }
+    }
+
+    #[test]
+    fn hypercubes_and_squares() {
+        let mut rng = Lcg::new_with_seed(2);
+        for (n, d) in [(3, 4), (4, 4), (5, 4), (3, 5), (4, 5), (3, 6)] {
+            let cells = MagicCube::with_dimensions(&mut rng, d).generate(n).unwrap();
+            assert!(check_hypercube_properties(&cells, n, d), "order {} in {} dimensions", n, d);
+        }
+        for n in [3, 4, 5, 9] {
+            let square = MagicCube::with_dimensions(&mut rng, 2).generate(n).unwrap();
+            assert!(check_magic_properties(&square, n));
+        }
+    }
+
+    #[test]
+    fn validator_rejects_broken_cubes() {
+        let mut rng = Lcg::new_with_seed(3);
+        let mut cube = MagicCube::new(&mut rng).generate(3).unwrap();
+        // Swapping two cells of one line breaks the lines through them in other directions.
+        cube.swap(0, 1);
+        assert!(!check_hypercube_properties(&cube, 3, 3));
+        // Cell numbering in plain order fails the lines along the slowest axis.
+        let plain: Vec<u32> = (1..=27).collect();
+        assert!(!check_hypercube_properties(&plain, 3, 3));
+        assert!(!check_hypercube_properties(&plain[..26], 3, 3));
+    }
+
+    #[test]
+    fn rejects_unsupported_shapes() {
+        let mut rng = Lcg::new_with_seed(4);
+        assert_eq!(MagicCube::new(&mut rng).generate(6), Err(HypercubeError::UnsupportedOrder(6)));
+        assert_eq!(MagicCube::new(&mut rng).generate(2), Err(HypercubeError::UnsupportedOrder(2)));
+        assert_eq!(
+            MagicCube::with_dimensions(&mut rng, 1).generate(3),
+            Err(HypercubeError::UnsupportedDimension(1))
+        );
+        assert_eq!(
+            MagicCube::new(&mut rng).generate(1627),
+            Err(HypercubeError::TooLarge { n: 1627, dimensions: 3 })
+        );
+        assert_eq!(index(&[1, 2, 0], 3), 7);
+    }
+}
@@ -0,0 +1,461 @@
+//! Reading squares written by other tools.
+//!
+//! Three formats are understood, and [`parse`] tells them apart by looking at the input:
+//!
+//! - **Text**: whitespace-separated numbers, one square after another with a blank line in
+//!   between. The row layout is free.
+//! - **CSV**: one row per line with comma-separated values, squares separated by blank lines.
+//! - **JSON**: a square is an array of rows (`[[2,7,6],[9,5,1],[4,3,8]]`), a flat array, or an
+//!   object with a `grid` (or `square`) field in either shape and an optional `n`. The input
+//!   may hold one square, an array of squares, or a stream of squares such as NDJSON.
+//!
+//! The order of each square is inferred from how many numbers it holds.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// A square read from input, in the flat row-major layout of the validator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Square {
+    /// The order of the square.
+    pub n: usize,
+    /// The flat row-major grid.
+    pub grid: Vec<u32>,
+    /// The line of the input the square starts on (1-based), or 0 for input without lines.
+    pub line: usize,
+}
+
+/// The input formats understood by [`parse_as`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Csv,
+    Json,
+}
+
+impl Format {
+    /// Guesses the format: JSON when the input starts with `[` or `{`, CSV when it holds a
+    /// comma, text otherwise.
+    pub fn detect(text: &str) -> Format {
+        match text.trim_start().chars().next() {
+            Some('[' | '{') => Format::Json,
+            _ if text.contains(',') => Format::Csv,
+            _ => Format::Text,
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "text" => Ok(Format::Text),
+            "csv" => Ok(Format::Csv),
+            "json" | "ndjson" => Ok(Format::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Errors found while reading squares.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputError {
+    /// A token is not a non-negative integer that fits in a `u32`.
+    InvalidNumber { line: usize, token: String },
+    /// The square starting on `line` holds a number of values that is not a perfect square,
+    /// or its rows do not form a square.
+    NotSquare { line: usize, values: usize },
+    /// The square starting on `line` declares an order `n` that does not match its values.
+    OrderMismatch { line: usize, n: usize, values: usize },
+    /// The JSON is malformed.
+    InvalidJson { line: usize, message: String },
+    /// A JSON value on `line` does not describe a square.
+    NotASquare { line: usize },
+    /// The input holds no square.
+    Empty,
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidNumber { line, token } => write!(f, "Line {}: '{}' is not a valid number.", line, token),
+            Self::NotSquare { line, values } => {
+                write!(f, "The square starting on line {} has {} values, which do not form a square.", line, values)
+            }
+            Self::OrderMismatch { line, n, values } => {
+                write!(f, "The square starting on line {} declares order {} but has {} values.", line, n, values)
+            }
+            Self::InvalidJson { line, message } => write!(f, "Line {}: invalid JSON: {}.", line, message),
+            Self::NotASquare { line } => write!(
+                f,
+                "Line {}: expected an array of rows, a flat array, or an object with a \"grid\" field.",
+                line
+            ),
+            Self::Empty => write!(f, "The input holds no square."),
+        }
+    }
+}
+
+impl std::error::Error for InputError {}
+
+/// Reads squares in the format found by [`Format::detect`].
+pub fn parse(text: &str) -> Result<Vec<Square>, InputError> {
+    parse_as(text, Format::detect(text))
+}
+
+/// Reads squares in the given format.
+pub fn parse_as(text: &str, format: Format) -> Result<Vec<Square>, InputError> {
+    match format {
+        Format::Text => parse_text(text),
+        Format::Csv => parse_csv(text),
+        Format::Json => parse_json(text),
+    }
+}
+
+/// Reads whitespace-separated squares, separated from each other by blank lines.
+pub fn parse_text(text: &str) -> Result<Vec<Square>, InputError> {
+    parse_lines(text, |line| line.split_whitespace().collect())
+}
+
+/// Reads comma-separated rows, with blank lines between squares. Spaces around values are
+/// ignored, and so is a trailing comma.
+pub fn parse_csv(text: &str) -> Result<Vec<Square>, InputError> {
+    parse_lines(text, |line| {
+        let line = line.trim();
+        if line.is_empty() {
+            return Vec::new();
+        }
+        line.strip_suffix(',').unwrap_or(line).split(',').map(str::trim).collect()
+    })
+}
+
+/// Reads blocks of lines separated by blank lines, splitting each line into tokens.
+fn parse_lines<'a>(text: &'a str, split: impl Fn(&'a str) -> Vec<&'a str>) -> Result<Vec<Square>, InputError> {
+    let mut squares = Vec::new();
+    let mut values = Vec::new();
+    let mut start = 0;
+
+    for (i, line) in text.lines().chain([""]).enumerate() {
+        let tokens = split(line);
+        if tokens.is_empty() {
+            if !values.is_empty() {
+                squares.push(to_square(std::mem::take(&mut values), start)?);
+            }
+            continue;
+        }
+        if values.is_empty() {
+            start = i + 1;
+        }
+        for token in tokens {
+            values.push(number(token, i + 1)?);
+        }
+    }
+
+    if squares.is_empty() {
+        return Err(InputError::Empty);
+    }
+    Ok(squares)
+}
+
+/// Reads one JSON square, an array of squares, or a stream of them (NDJSON).
+pub fn parse_json(text: &str) -> Result<Vec<Square>, InputError> {
+    let mut parser = JsonParser { bytes: text.as_bytes(), pos: 0, line: 1 };
+    let mut squares = Vec::new();
+
+    while parser.skip_whitespace() {
+        let value = parser.value()?;
+        match &value.kind {
+            // An array whose items are themselves squares (arrays of rows or objects).
+            Kind::Array(items) if items.iter().any(|item| item.is_square_like()) => {
+                for item in items {
+                    squares.push(item.to_square()?);
+                }
+            }
+            _ => squares.push(value.to_square()?),
+        }
+    }
+
+    if squares.is_empty() {
+        return Err(InputError::Empty);
+    }
+    Ok(squares)
+}
+
+fn number(token: &str, line: usize) -> Result<u32, InputError> {
+    token.parse().map_err(|_| InputError::InvalidNumber { line, token: token.to_string() })
+}
+
+/// Infers the order from the number of values.
+fn to_square(grid: Vec<u32>, line: usize) -> Result<Square, InputError> {
+    let n = grid.len().isqrt();
+    if n * n != grid.len() {
+        return Err(InputError::NotSquare { line, values: grid.len() });
+    }
+    Ok(Square { n, grid, line })
+}
+
+/// A parsed JSON value with the line it starts on.
+struct Value {
+    kind: Kind,
+    line: usize,
+}
+
+enum Kind {
+    /// The raw text of a number, checked only when it is used.
+    Number(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+    /// Strings, booleans and null, which never make up a square.
+    Other,
+}
+
+impl Value {
+    /// Whether the value is an object or an array of arrays, i.e. not a flat row of numbers.
+    fn is_square_like(&self) -> bool {
+        match &self.kind {
+            Kind::Object(_) => true,
+            Kind::Array(items) => items.iter().any(|item| matches!(item.kind, Kind::Array(_))),
+            _ => false,
+        }
+    }
+
+    fn to_square(&self) -> Result<Square, InputError> {
+        let not_a_square = InputError::NotASquare { line: self.line };
+        match &self.kind {
+            Kind::Array(items) if items.iter().all(|item| matches!(item.kind, Kind::Array(_))) => {
+                let mut grid = Vec::new();
+                for row in items {
+                    let Kind::Array(cells) = &row.kind else { unreachable!() };
+                    if cells.len() != items.len() {
+                        let values = items.iter().map(|r| if let Kind::Array(c) = &r.kind { c.len() } else { 0 }).sum();
+                        return Err(InputError::NotSquare { line: self.line, values });
+                    }
+                    for cell in cells {
+                        grid.push(cell.to_number().ok_or(InputError::NotASquare { line: cell.line })??);
+                    }
+                }
+                if grid.is_empty() {
+                    return Err(not_a_square);
+                }
+                Ok(Square { n: items.len(), grid, line: self.line })
+            }
+            Kind::Array(items) => {
+                let grid = items
+                    .iter()
+                    .map(|item| item.to_number().ok_or(InputError::NotASquare { line: item.line })?)
+                    .collect::<Result<Vec<u32>, InputError>>()?;
+                if grid.is_empty() {
+                    return Err(not_a_square);
+                }
+                to_square(grid, self.line)
+            }
+            Kind::Object(fields) => {
+                let field = |name: &str| fields.iter().find(|(key, _)| key == name).map(|(_, value)| value);
+                let grid = field("grid").or_else(|| field("square")).ok_or(not_a_square)?;
+                let mut square = grid.to_square()?;
+                square.line = self.line;
+                if let Some(n) = field("n") {
+                    let n = n.to_number().ok_or(InputError::NotASquare { line: n.line })?? as usize;
+                    if n != square.n {
+                        return Err(InputError::OrderMismatch { line: self.line, n, values: square.grid.len() });
+                    }
+                }
+                Ok(square)
+            }
+            _ => Err(not_a_square),
+        }
+    }
+
+    /// The value as a cell, or `None` if it is not a number at all.
+    fn to_number(&self) -> Option<Result<u32, InputError>> {
+        match &self.kind {
+            Kind::Number(token) => Some(number(token, self.line)),
+            _ => None,
+        }
+    }
+}
+
+/// A small JSON reader, enough for squares: it keeps numbers as text and does not decode
+/// string escapes beyond skipping them.
+struct JsonParser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    line: usize,
+}
+
+impl JsonParser<'_> {
+    /// Skips whitespace, returning whether anything is left.
+    fn skip_whitespace(&mut self) -> bool {
+        while let Some(&b) = self.bytes.get(self.pos) {
+            match b {
+                b'\n' => self.line += 1,
+                b' ' | b'\t' | b'\r' => {}
+                _ => return true,
+            }
+            self.pos += 1;
+        }
+        false
+    }
+
+    fn error(&self, message: &str) -> InputError {
+        InputError::InvalidJson { line: self.line, message: message.to_string() }
+    }
+
+    /// Consumes `byte` after optional whitespace.
+    fn expect(&mut self, byte: u8) -> Result<(), InputError> {
+        self.skip_whitespace();
+        if self.bytes.get(self.pos) != Some(&byte) {
+            return Err(self.error(&format!("expected '{}'", byte as char)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Value, InputError> {
+        if !self.skip_whitespace() {
+            return Err(self.error("unexpected end of input"));
+        }
+        let line = self.line;
+        let kind = match self.bytes[self.pos] {
+            b'[' => Kind::Array(self.sequence(b']', Self::value)?),
+            b'{' => Kind::Object(self.sequence(b'}', |parser| {
+                let key = parser.string()?;
+                parser.expect(b':')?;
+                Ok((key, parser.value()?))
+            })?),
+            b'"' => {
+                self.string()?;
+                Kind::Other
+            }
+            b'-' | b'0'..=b'9' => {
+                let start = self.pos;
+                while matches!(self.bytes.get(self.pos), Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')) {
+                    self.pos += 1;
+                }
+                Kind::Number(String::from_utf8_lossy(&self.bytes[start..self.pos]).into_owned())
+            }
+            _ => {
+                for literal in ["true", "false", "null"] {
+                    if self.bytes[self.pos..].starts_with(literal.as_bytes()) {
+                        self.pos += literal.len();
+                        return Ok(Value { kind: Kind::Other, line });
+                    }
+                }
+                return Err(self.error("unexpected character"));
+            }
+        };
+        Ok(Value { kind, line })
+    }
+
+    /// Reads the comma-separated items of an array or object, up to `close`.
+    fn sequence<T>(&mut self, close: u8, mut item: impl FnMut(&mut Self) -> Result<T, InputError>) -> Result<Vec<T>, InputError> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.bytes.get(self.pos) == Some(&close) {
+            self.pos += 1;
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            self.skip_whitespace();
+            match self.bytes.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(&b) if b == close => {
+                    self.pos += 1;
+                    return Ok(items);
+                }
+                _ => return Err(self.error(&format!("expected ',' or '{}'", close as char))),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, InputError> {
+        self.expect(b'"')?;
+        let start = self.pos;
+        loop {
+            match self.bytes.get(self.pos) {
+                None | Some(b'\n') => return Err(self.error("unterminated string")),
+                Some(b'"') => break,
+                Some(b'\\') => self.pos += 2,
+                Some(_) => self.pos += 1,
+            }
+        }
+        let text = String::from_utf8_lossy(&self.bytes[start..self.pos]).into_owned();
+        self.pos += 1;
+        Ok(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validator;
+
+    #[test]
+    fn reads_squares_separated_by_blank_lines() {
+        let text = "2 7 6\n9 5 1\n4 3 8\n\n\n16 3 2 13 5 10 11 8\n9 6 7 12 4 15 14 1\n";
+        let squares = parse(text).unwrap();
+        assert_eq!(squares.len(), 2);
+        assert_eq!(squares[0], Square { n: 3, grid: vec![2, 7, 6, 9, 5, 1, 4, 3, 8], line: 1 });
+        assert_eq!((squares[1].n, squares[1].line), (4, 6));
+
+        let csv = "2, 7, 6\n9,5,1,\n4,3,8\n\n1\n";
+        assert_eq!(Format::detect(csv), Format::Csv);
+        let squares = parse(csv).unwrap();
+        assert_eq!(squares[0].grid, vec![2, 7, 6, 9, 5, 1, 4, 3, 8]);
+        assert_eq!((squares[1].n, squares[1].line), (1, 5));
+    }
+
+    #[test]
+    fn reads_json_shapes_and_streams() {
+        let lo_shu = vec![2, 7, 6, 9, 5, 1, 4, 3, 8];
+        for text in [
+            "[[2,7,6],[9,5,1],[4,3,8]]",
+            " [2, 7, 6, 9, 5, 1, 4, 3, 8]",
+            r#"{"n": 3, "method": "siamese", "grid": [[2,7,6],[9,5,1],[4,3,8]]}"#,
+            r#"{"square": [2,7,6,9,5,1,4,3,8], "note": "a \"quoted\" word"}"#,
+        ] {
+            assert_eq!(parse(text).unwrap()[0].grid, lo_shu, "{}", text);
+        }
+
+        let ndjson = "{\"grid\": [[1]]}\n[[2,7,6],[9,5,1],[4,3,8]]\n\n{\"n\": 3, \"grid\": [2,7,6,9,5,1,4,3,8]}\n";
+        let squares = parse(ndjson).unwrap();
+        assert_eq!(squares.iter().map(|s| (s.n, s.line)).collect::<Vec<_>>(), vec![(1, 1), (3, 2), (3, 4)]);
+
+        let array = "[\n  [[1]],\n  {\"grid\": [2,7,6,9,5,1,4,3,8]}\n]";
+        let squares = parse(array).unwrap();
+        assert_eq!(squares.iter().map(|s| (s.n, s.line)).collect::<Vec<_>>(), vec![(1, 2), (3, 3)]);
+    }
+
+    #[test]
+    fn reports_bad_input() {
+        assert_eq!(parse_text("1 2\n3 x\n"), Err(InputError::InvalidNumber { line: 2, token: "x".to_string() }));
+        assert_eq!(parse_text("1\n\n1 2 3\n"), Err(InputError::NotSquare { line: 3, values: 3 }));
+        assert_eq!(parse_text(" \n\n"), Err(InputError::Empty));
+        assert_eq!(parse_csv("1,,2,3\n"), Err(InputError::InvalidNumber { line: 1, token: String::new() }));
+
+        assert_eq!(parse("[[1,2],[3]]"), Err(InputError::NotSquare { line: 1, values: 3 }));
+        assert_eq!(parse("[1, -2, 3, 4]"), Err(InputError::InvalidNumber { line: 1, token: "-2".to_string() }));
+        assert_eq!(parse("{\"n\": 2, \"grid\": [1]}"), Err(InputError::OrderMismatch { line: 1, n: 2, values: 1 }));
+        assert_eq!(parse("{\"rows\": [1]}"), Err(InputError::NotASquare { line: 1 }));
+        assert!(matches!(parse("[[1, 2]\n[3, 4]]"), Err(InputError::InvalidJson { line: 2, .. })));
+    }
+
+    #[test]
+    fn diagnoses_squares_read_from_input() {
+        let squares = parse("[[2,7,6],[9,5,1],[4,3,8]]\n[[2,7,6],[9,5,1],[4,3,9]]\n[[0,2],[2,3]]").unwrap();
+        assert!(validator::diagnose(&squares[0].grid, 3).is_magic());
+
+        let diagnosis = validator::diagnose(&squares[1].grid, 3);
+        assert!(diagnosis.out_of_range.is_empty());
+        assert_eq!(diagnosis.repeated, vec![9]);
+        let lines: Vec<_> = diagnosis.wrong_lines.iter().map(|&(line, _)| line.to_string()).collect();
+        assert_eq!(lines, vec!["row 3", "column 3", "main diagonal"]);
+        assert!(!validator::check_magic_properties(&squares[1].grid, 3));
+
+        let diagnosis = validator::diagnose(&squares[2].grid, 2);
+        assert_eq!((diagnosis.out_of_range, diagnosis.repeated), (vec![0], vec![2]));
+    }
+}
@@ -0,0 +1,379 @@
+//! Latin squares and mutually orthogonal Latin squares (MOLS).
+//!
+//! A Latin square of order $n$ holds the symbols $0..n$ once in every row and column. Two
+//! Latin squares $A$ and $B$ are orthogonal when the pairs $(A_{r,c}, B_{r,c})$ are all
+//! distinct. Orthogonal squares whose diagonals also sum to $n(n-1)/2$ (for instance diagonal
+//! Latin squares, with every symbol once on each main diagonal) combine into the magic square
+//! $nA + B + 1$. The odd-order generator builds its squares this way from the Siamese
+//! "runs" and "cycles".
+//!
+//! - [`random`] draws a random Latin square with the Jacobson-Matthews Markov chain.
+//! - [`mols`] builds the complete set of $n - 1$ MOLS for a prime power $n = p^k$: over the
+//!   finite field $\mathbb{F}_n$, the squares $L_a(r, c) = ar + c$ for $a \ne 0$ are pairwise
+//!   orthogonal.
+//! - [`magic_square`] picks two of them that are also diagonal Latin squares, which exist for
+//!   every prime power from 4 on.
+//!
+//! Squares use the flat row-major layout of the validator, with symbols $0..n$.
+
+use std::fmt;
+
+use crate::rng::Lcg;
+
+/// Largest order drawn by [`random`]. The chain stores an $n^3$ incidence cube and takes
+/// $n^3$ steps.
+pub const MAX_RANDOM_ORDER: usize = 128;
+
+/// Largest order for [`mols`], which returns $(n - 1) n^2$ cells.
+pub const MAX_MOLS_ORDER: usize = 128;
+
+/// Errors returned by the Latin square constructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LatinError {
+    /// The order is 0 or above the supported maximum.
+    UnsupportedOrder(usize),
+    /// Finite fields, and so the MOLS construction, only exist for prime powers.
+    NotPrimePower(usize),
+    /// No two orthogonal diagonal Latin squares come out of the field for this order (3).
+    NoDiagonalPair(usize),
+    /// One of the grids is not a Latin square of the given order.
+    NotLatin,
+    /// The two squares are not orthogonal.
+    NotOrthogonal,
+    /// A main diagonal does not sum to $n(n-1)/2$, so the combination would not be magic.
+    DiagonalSum,
+}
+
+impl fmt::Display for LatinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedOrder(n) => write!(f, "Order {} is not supported.", n),
+            Self::NotPrimePower(n) => write!(f, "Order {} is not a prime power, so there is no finite field of that size.", n),
+            Self::NoDiagonalPair(n) => write!(f, "There are no two orthogonal diagonal Latin squares of order {}.", n),
+            Self::NotLatin => write!(f, "The grid is not a Latin square."),
+            Self::NotOrthogonal => write!(f, "The squares are not orthogonal."),
+            Self::DiagonalSum => write!(f, "A main diagonal does not sum to n(n-1)/2."),
+        }
+    }
+}
+
+impl std::error::Error for LatinError {}
+
+/// Whether the grid holds each symbol $0..n$ once in every row and every column.
+pub fn is_latin(square: &[u32], n: usize) -> bool {
+    if square.len() != n * n {
+        return false;
+    }
+    (0..n).all(|i| is_permutation((0..n).map(|j| square[i * n + j]), n) && is_permutation((0..n).map(|j| square[j * n + i]), n))
+}
+
+/// Whether the grid is a Latin square that also holds every symbol once on each main diagonal.
+pub fn is_diagonal_latin(square: &[u32], n: usize) -> bool {
+    is_latin(square, n)
+        && is_permutation((0..n).map(|i| square[i * n + i]), n)
+        && is_permutation((0..n).map(|i| square[i * n + n - 1 - i]), n)
+}
+
+/// Whether two Latin squares are orthogonal: superimposed, every ordered pair of symbols
+/// appears exactly once.
+pub fn are_orthogonal(a: &[u32], b: &[u32], n: usize) -> bool {
+    if !is_latin(a, n) || !is_latin(b, n) {
+        return false;
+    }
+    let mut seen = vec![false; n * n];
+    a.iter().zip(b).all(|(&x, &y)| !std::mem::replace(&mut seen[x as usize * n + y as usize], true))
+}
+
+/// Combines two grids of symbols $0..n$ into $nA + B + 1$ without checking them.
+pub fn combine(a: &[u32], b: &[u32], n: usize) -> Vec<u32> {
+    a.iter().zip(b).map(|(&x, &y)| n as u32 * x + y + 1).collect()
+}
+
+/// Combines two orthogonal Latin squares into the magic square $nA + B + 1$. Both main
+/// diagonals of each square must sum to $n(n-1)/2$, as they do for diagonal Latin squares.
+pub fn combine_magic(a: &[u32], b: &[u32], n: usize) -> Result<Vec<u32>, LatinError> {
+    if !is_latin(a, n) || !is_latin(b, n) {
+        return Err(LatinError::NotLatin);
+    }
+    if !are_orthogonal(a, b, n) {
+        return Err(LatinError::NotOrthogonal);
+    }
+    let target = (n * (n - 1) / 2) as u64;
+    let diagonals_balanced = |s: &[u32]| {
+        (0..n).map(|i| s[i * n + i] as u64).sum::<u64>() == target
+            && (0..n).map(|i| s[i * n + n - 1 - i] as u64).sum::<u64>() == target
+    };
+    if !diagonals_balanced(a) || !diagonals_balanced(b) {
+        return Err(LatinError::DiagonalSum);
+    }
+    Ok(combine(a, b, n))
+}
+
+/// Draws a random Latin square of order `n` by running the Jacobson-Matthews Markov chain
+/// for $n^3$ steps from the cyclic square.
+///
+/// The square is seen as an $n \times n \times n$ cube of 0/1 entries with a single 1 on every
+/// line. A step adds and removes 1s on the corners of a small sub-cube; it can leave one
+/// entry at $-1$, which the next steps move around until the cube is proper again.
+pub fn random(n: usize, rng: &mut Lcg) -> Result<Vec<u32>, LatinError> {
+    if n == 0 || n > MAX_RANDOM_ORDER {
+        return Err(LatinError::UnsupportedOrder(n));
+    }
+    if n == 1 {
+        return Ok(vec![0]);
+    }
+
+    let at = |r: usize, c: usize, s: usize| (r * n + c) * n + s;
+    let mut cube = vec![0i8; n * n * n];
+    for r in 0..n {
+        for c in 0..n {
+            cube[at(r, c, (r + c) % n)] = 1;
+        }
+    }
+
+    let mut improper: Option<(usize, usize, usize)> = None;
+    let mut step = 0;
+    while step < n * n * n || improper.is_some() {
+        step += 1;
+        let (r, c, s) = match improper {
+            Some(cell) => cell,
+            None => loop {
+                let cell = (rng.next_range(0, n), rng.next_range(0, n), rng.next_range(0, n));
+                if cube[at(cell.0, cell.1, cell.2)] == 0 {
+                    break cell;
+                }
+            },
+        };
+
+        // On each line through (r, c, s), pick a 1: the only one in a proper cube, one of the
+        // two around the -1 otherwise.
+        let mut pick = |ones: Vec<usize>| if ones.len() == 1 { ones[0] } else { ones[rng.next_range(0, ones.len())] };
+        let r2 = pick((0..n).filter(|&x| cube[at(x, c, s)] == 1).collect());
+        let c2 = pick((0..n).filter(|&x| cube[at(r, x, s)] == 1).collect());
+        let s2 = pick((0..n).filter(|&x| cube[at(r, c, x)] == 1).collect());
+
+        for (cell, delta) in [
+            (at(r, c, s), 1),
+            (at(r, c2, s2), 1),
+            (at(r2, c, s2), 1),
+            (at(r2, c2, s), 1),
+            (at(r, c, s2), -1),
+            (at(r, c2, s), -1),
+            (at(r2, c, s), -1),
+            (at(r2, c2, s2), -1),
+        ] {
+            cube[cell] += delta;
+        }
+        improper = (cube[at(r2, c2, s2)] == -1).then_some((r2, c2, s2));
+    }
+
+    let mut square = vec![0; n * n];
+    for r in 0..n {
+        for c in 0..n {
+            square[r * n + c] = (0..n).find(|&s| cube[at(r, c, s)] == 1).unwrap() as u32;
+        }
+    }
+    Ok(square)
+}
+
+/// Builds the complete set of $n - 1$ mutually orthogonal Latin squares of a prime-power order
+/// $n$: $L_a(r, c) = ar + c$ over $\mathbb{F}_n$, for $a = 1..n$. Field elements are numbered
+/// by their coefficients in base $p$, so row $r$ and column $c$ are read as field elements.
+pub fn mols(n: usize) -> Result<Vec<Vec<u32>>, LatinError> {
+    if !(2..=MAX_MOLS_ORDER).contains(&n) {
+        return Err(LatinError::UnsupportedOrder(n));
+    }
+    let field = Field::new(n).ok_or(LatinError::NotPrimePower(n))?;
+    Ok((1..n).map(|a| field.square(a)).collect())
+}
+
+/// Generates a magic square of prime-power order `n >= 4` from two orthogonal diagonal Latin
+/// squares of the field construction, with their symbols randomly relabelled.
+///
+/// $L_a$ has its main diagonal $(a + 1)r$ and its anti-diagonal $(a - 1)r + u$, with $u$ the
+/// element numbered $n - 1$, so it is a diagonal Latin square whenever $a \ne 0, 1, -1$.
+pub fn magic_square(n: usize, rng: &mut Lcg) -> Result<Vec<u32>, LatinError> {
+    if !(2..=MAX_MOLS_ORDER).contains(&n) {
+        return Err(LatinError::UnsupportedOrder(n));
+    }
+    let field = Field::new(n).ok_or(LatinError::NotPrimePower(n))?;
+    let minus_one = field.neg(1);
+    let mut multipliers: Vec<usize> = (2..n).filter(|&a| a != minus_one).collect();
+    if multipliers.len() < 2 {
+        return Err(LatinError::NoDiagonalPair(n));
+    }
+    rng.shuffle(&mut multipliers);
+
+    let mut relabelled = |a: usize| {
+        let mut labels: Vec<u32> = (0..n as u32).collect();
+        rng.shuffle(&mut labels);
+        field.square(a).iter().map(|&s| labels[s as usize]).collect::<Vec<u32>>()
+    };
+    let a = relabelled(multipliers[0]);
+    let b = relabelled(multipliers[1]);
+    combine_magic(&a, &b, n)
+}
+
+/// Whether the values are exactly $0..n$ in some order.
+fn is_permutation(values: impl Iterator<Item = u32>, n: usize) -> bool {
+    let mut seen = vec![false; n];
+    let mut count = 0;
+    for v in values {
+        if v as usize >= n || std::mem::replace(&mut seen[v as usize], true) {
+            return false;
+        }
+        count += 1;
+    }
+    count == n
+}
+
+/// The finite field of order $n = p^k$. Element $x$ stands for the polynomial whose
+/// coefficients are the base-$p$ digits of $x$, reduced modulo an irreducible polynomial of
+/// degree $k$.
+struct Field {
+    n: usize,
+    p: usize,
+    /// `mul[x * n + y]` is the product $xy$.
+    mul: Vec<usize>,
+}
+
+impl Field {
+    /// Builds the field of order `n`, if `n` is a prime power.
+    fn new(n: usize) -> Option<Self> {
+        let p = (2..=n).find(|&d| n.is_multiple_of(d))?;
+        let mut k = 0;
+        let mut rest = n;
+        while rest.is_multiple_of(p) {
+            rest /= p;
+            k += 1;
+        }
+        if rest != 1 {
+            return None;
+        }
+
+        // The monic polynomial of degree k with lower coefficients `low`, for the first `low`
+        // that has no monic factor of degree 1..=k/2.
+        let digits = |x: usize, len: usize| (0..len).map(|i| x / p.pow(i as u32) % p).collect::<Vec<usize>>();
+        let monic = |low: usize, degree: usize| {
+            let mut poly = digits(low, degree);
+            poly.push(1);
+            poly
+        };
+        let modulus = (0..n)
+            .map(|low| monic(low, k))
+            .find(|poly| (1..=k / 2).all(|d| (0..p.pow(d as u32)).all(|low| !poly_rem(poly, &monic(low, d), p).iter().all(|&x| x == 0))))?;
+
+        let mut mul = vec![0; n * n];
+        for x in 0..n {
+            for y in 0..n {
+                let (dx, dy) = (digits(x, k), digits(y, k));
+                let mut product = vec![0; 2 * k - 1];
+                for (i, &a) in dx.iter().enumerate() {
+                    for (j, &b) in dy.iter().enumerate() {
+                        product[i + j] = (product[i + j] + a * b) % p;
+                    }
+                }
+                let reduced = poly_rem(&product, &modulus, p);
+                mul[x * n + y] = reduced.iter().rev().fold(0, |acc, &d| acc * p + d);
+            }
+        }
+        Some(Self { n, p, mul })
+    }
+
+    /// Digit-wise sum modulo $p$.
+    fn add(&self, mut x: usize, mut y: usize) -> usize {
+        let (mut sum, mut place) = (0, 1);
+        while place < self.n {
+            sum += (x % self.p + y % self.p) % self.p * place;
+            x /= self.p;
+            y /= self.p;
+            place *= self.p;
+        }
+        sum
+    }
+
+    /// The additive inverse of `x`.
+    fn neg(&self, x: usize) -> usize {
+        (0..self.n).find(|&y| self.add(x, y) == 0).unwrap()
+    }
+
+    /// The Latin square $L_a(r, c) = ar + c$.
+    fn square(&self, a: usize) -> Vec<u32> {
+        let n = self.n;
+        (0..n * n).map(|i| self.add(self.mul[a * n + i / n], i % n) as u32).collect()
+    }
+}
+
+/// Remainder of the polynomial division of `poly` by the monic `divisor`, with coefficients
+/// modulo `p`, lowest degree first. The result has `divisor.len() - 1` coefficients.
+fn poly_rem(poly: &[usize], divisor: &[usize], p: usize) -> Vec<usize> {
+    let degree = divisor.len() - 1;
+    let mut rem = poly.to_vec();
+    for top in (degree..rem.len()).rev() {
+        let factor = rem[top];
+        if factor == 0 {
+            continue;
+        }
+        for (i, &d) in divisor.iter().enumerate() {
+            let at = top - degree + i;
+            rem[at] = (rem[at] + p * p - factor * d % p) % p;
+        }
+    }
+    rem.resize(degree, 0);
+    rem
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validator::check_magic_properties;
+
+    #[test]
+    fn random_squares_are_latin() {
+        let mut rng = Lcg::new_with_seed(1);
+        for n in [1, 2, 3, 5, 8, 12] {
+            let square = random(n, &mut rng).unwrap();
+            assert!(is_latin(&square, n), "order {}", n);
+        }
+        let first = random(6, &mut rng).unwrap();
+        assert!((0..5).any(|_| random(6, &mut rng).unwrap() != first));
+    }
+
+    #[test]
+    fn complete_sets_of_mols() {
+        for n in [2, 3, 4, 5, 7, 8, 9, 16, 25, 27] {
+            let squares = mols(n).unwrap();
+            assert_eq!(squares.len(), n - 1);
+            for (i, a) in squares.iter().enumerate() {
+                for b in &squares[i + 1..] {
+                    assert!(are_orthogonal(a, b, n), "order {}", n);
+                }
+            }
+        }
+        assert_eq!(mols(6), Err(LatinError::NotPrimePower(6)));
+        assert_eq!(mols(12), Err(LatinError::NotPrimePower(12)));
+    }
+
+    #[test]
+    fn magic_squares_from_orthogonal_pairs() {
+        let mut rng = Lcg::new_with_seed(2);
+        for n in [4, 5, 7, 8, 9, 16, 25, 27, 32] {
+            let grid = magic_square(n, &mut rng).unwrap_or_else(|e| panic!("order {}: {}", n, e));
+            assert!(check_magic_properties(&grid, n), "order {}", n);
+        }
+        assert_eq!(magic_square(3, &mut rng), Err(LatinError::NoDiagonalPair(3)));
+        assert_eq!(magic_square(10, &mut rng), Err(LatinError::NotPrimePower(10)));
+    }
+
+    #[test]
+    fn checks_combination_inputs() {
+        let cyclic: Vec<u32> = (0..9).map(|i| ((i / 3 + i % 3) % 3) as u32).collect();
+        let back: Vec<u32> = (0..9).map(|i| ((2 * (i / 3) + i % 3) % 3) as u32).collect();
+        assert!(is_latin(&cyclic, 3) && !is_diagonal_latin(&cyclic, 3));
+        assert!(are_orthogonal(&cyclic, &back, 3));
+        assert!(!are_orthogonal(&cyclic, &cyclic, 3));
+        assert_eq!(combine_magic(&cyclic, &cyclic, 3), Err(LatinError::NotOrthogonal));
+        assert_eq!(combine_magic(&cyclic, &back, 3), Err(LatinError::DiagonalSum));
+        assert_eq!(combine_magic(&[0, 0, 1, 1], &[0, 1, 0, 1], 2), Err(LatinError::NotLatin));
+    }
+}
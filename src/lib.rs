@@ -1,10 +1,36 @@
 pub mod rng;
 pub mod generator;
-#[cfg(not(target_arch = "wasm32"))]
+pub mod canonical;
+pub mod transform;
 pub mod validator;
+pub mod solver;
+pub mod puzzle;
+pub mod session;
+pub mod rectangle;
+pub mod hypercube;
+pub mod multimagic;
+pub mod catalog;
+pub mod franklin;
+pub mod multiplicative;
+pub mod restricted;
+pub mod latin;
+pub mod input;
+pub mod output;
+pub mod svg;
+pub mod checksum;
+pub mod png;
+pub mod heatmap;
+pub mod msq;
+pub mod packed;
+pub mod stream;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod enumerate;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod orbit;
 
 use wasm_bindgen::prelude::*;
 use rng::Lcg;
+use puzzle::Difficulty;
 
 
 /// Represents the result of a magic square generation.
@@ -38,6 +64,13 @@ impl MagicSquareResult {
     }
 }
 
+impl MagicSquareResult {
+    /// Returns a copy of the flat grid for Rust-side callers.
+    pub fn grid(&self) -> Vec<u32> {
+        self.grid.clone()
+    }
+}
+
 /// Main entry point for generating a magic square from Javascript.
 ///
 /// # Arguments
@@ -98,6 +131,482 @@ pub fn generate_magic_square(n: usize) -> Result<MagicSquareResult, JsError> {
     })
 }
 
+/// A generated square packed at the minimal bit width, for large orders kept in memory.
+///
+/// An order 7000 square takes 159 MB instead of the 196 MB of [`MagicSquareResult`]; cells are
+/// read one at a time or a row at a time rather than through a shared buffer.
+#[wasm_bindgen]
+pub struct PackedSquareResult {
+    square: packed::PackedSquare,
+}
+
+#[wasm_bindgen]
+impl PackedSquareResult {
+    /// Returns the order (n) of the square.
+    #[wasm_bindgen(getter)]
+    pub fn n(&self) -> usize {
+        self.square.n()
+    }
+
+    /// Returns the number of bits per cell.
+    #[wasm_bindgen(getter)]
+    pub fn bits(&self) -> u32 {
+        self.square.bits()
+    }
+
+    /// Returns the bytes used by the packed cells.
+    #[wasm_bindgen(getter)]
+    pub fn byte_len(&self) -> usize {
+        self.square.byte_len()
+    }
+
+    /// Returns the value in row `r` and column `c` (0-based).
+    pub fn get(&self, r: usize, c: usize) -> Result<u32, JsError> {
+        self.check(r)?;
+        if c >= self.square.n() {
+            return Err(JsError::new(&format!("Column {} is outside an order {} square.", c, self.square.n())));
+        }
+        Ok(self.square.get(r, c))
+    }
+
+    /// Returns row `r` (0-based) as a new array.
+    pub fn row(&self, r: usize) -> Result<Vec<u32>, JsError> {
+        self.check(r)?;
+        Ok(self.square.row(r).collect())
+    }
+
+    fn check(&self, r: usize) -> Result<(), JsError> {
+        if r >= self.square.n() {
+            return Err(JsError::new(&format!("Row {} is outside an order {} square.", r, self.square.n())));
+        }
+        Ok(())
+    }
+}
+
+impl PackedSquareResult {
+    /// Returns the packed square for Rust-side callers.
+    pub fn square(&self) -> &packed::PackedSquare {
+        &self.square
+    }
+}
+
+/// Generates a magic square straight into packed cells, with the same order cap as
+/// [`generate_magic_square`].
+///
+/// The cells come from the closed formulas of [`stream::RowSource`] (Siamese, LUX or
+/// truth-grid, under a random symmetry), so no unpacked grid is ever built.
+#[wasm_bindgen]
+pub fn generate_packed_square(n: usize) -> Result<PackedSquareResult, JsError> {
+    const MAX_SAFE_ORDER: usize = 7000;
+    if n > MAX_SAFE_ORDER {
+        return Err(JsError::new(&format!(
+            "Order {} is too large for browser memory safety. Capped at {}.",
+            n, MAX_SAFE_ORDER
+        )));
+    }
+
+    let seed = Lcg::new().next_u32() as u64;
+    let source = stream::RowSource::new(n, seed).map_err(|e| JsError::new(&e.to_string()))?;
+    let mut square = packed::PackedSquare::new(n);
+    for r in 0..n {
+        for c in 0..n {
+            square.set(r, c, source.cell(r, c) as u32);
+        }
+    }
+    Ok(PackedSquareResult { square })
+}
+
+/// Represents a generated magic rectangle, or any grid with a separate row and column count.
+/// The grid is flat and row-major, like `MagicSquareResult`.
+#[wasm_bindgen]
+pub struct MagicRectangleResult {
+    grid: Vec<u32>,
+    rows: usize,
+    cols: usize,
+}
+
+#[wasm_bindgen]
+impl MagicRectangleResult {
+    /// Returns a raw pointer to the grid buffer.
+    #[wasm_bindgen]
+    pub fn get_grid_ptr(&self) -> *const u32 {
+        self.grid.as_ptr()
+    }
+
+    /// Returns the number of elements in the grid.
+    #[wasm_bindgen]
+    pub fn get_grid_len(&self) -> usize {
+        self.grid.len()
+    }
+
+    /// Returns the number of rows.
+    #[wasm_bindgen(getter)]
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Returns the number of columns.
+    #[wasm_bindgen(getter)]
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+}
+
+impl MagicRectangleResult {
+    /// Returns a copy of the flat grid for Rust-side callers.
+    pub fn grid(&self) -> Vec<u32> {
+        self.grid.clone()
+    }
+}
+
+/// Generates a `rows` x `cols` magic rectangle from Javascript.
+///
+/// Both sides must be even or both odd. Every shape is built directly, so the time grows with
+/// the number of cells only.
+#[wasm_bindgen]
+pub fn generate_magic_rectangle(rows: usize, cols: usize) -> Result<MagicRectangleResult, JsError> {
+    // Same memory cap as `generate_magic_square`.
+    const MAX_SAFE_CELLS: usize = 7000 * 7000;
+    if rows.saturating_mul(cols) > MAX_SAFE_CELLS {
+        return Err(JsError::new(&format!(
+            "A {}x{} rectangle is too large for browser memory safety. Capped at {} cells.",
+            rows, cols, MAX_SAFE_CELLS
+        )));
+    }
+
+    let mut lcg = Lcg::new();
+    let grid = rectangle::generate(rows, cols, &mut lcg).map_err(|e| JsError::new(&e.to_string()))?;
+    Ok(MagicRectangleResult { grid, rows, cols })
+}
+
+/// Generates a semi-magic square of order `n` from Javascript: rows and columns sum to the
+/// magic constant, the diagonals generally do not.
+#[wasm_bindgen]
+pub fn generate_semi_magic_square(n: usize) -> Result<MagicSquareResult, JsError> {
+    if n > 7000 {
+        return Err(JsError::new(&format!("Order {} is too large for browser memory safety. Capped at 7000.", n)));
+    }
+
+    let mut lcg = Lcg::new();
+    let grid = rectangle::generate_semi_magic(n, &mut lcg).map_err(|e| JsError::new(&e.to_string()))?;
+    Ok(MagicSquareResult { grid, n })
+}
+
+/// Generates a square that stays magic when its entries are squared (`power = 2`, bimagic) or
+/// also cubed (`power = 3`, trimagic) from Javascript. Bimagic squares are built for orders 8,
+/// 16, 32, ... and trimagic squares for orders 32, 64, ...
+#[wasm_bindgen]
+pub fn generate_multimagic_square(n: usize, power: u32) -> Result<MagicSquareResult, JsError> {
+    let mut lcg = Lcg::new();
+    let grid = multimagic::generate(n, power, &mut lcg).map_err(|e| JsError::new(&e.to_string()))?;
+    Ok(MagicSquareResult { grid, n })
+}
+
+/// Generates a Franklin square of order 8 or 16 from Javascript: rows, columns, bent
+/// diagonals, half rows and columns, and 2x2 blocks all have their share of the magic
+/// constant, while the main diagonals generally do not.
+#[wasm_bindgen]
+pub fn generate_franklin_square(n: usize) -> Result<MagicSquareResult, JsError> {
+    let mut lcg = Lcg::new();
+    let grid = franklin::generate(n, &mut lcg).map_err(|e| JsError::new(&e.to_string()))?;
+    Ok(MagicSquareResult { grid, n })
+}
+
+/// Represents a generated magic hypercube: $n^d$ flat cells, with the cell at coordinates
+/// $(x_0, ..., x_{d-1})$ stored at index $\sum_i x_i n^i$.
+#[wasm_bindgen]
+pub struct MagicCubeResult {
+    cells: Vec<u32>,
+    n: usize,
+    dimensions: usize,
+}
+
+#[wasm_bindgen]
+impl MagicCubeResult {
+    /// Returns a raw pointer to the cell buffer.
+    #[wasm_bindgen]
+    pub fn get_grid_ptr(&self) -> *const u32 {
+        self.cells.as_ptr()
+    }
+
+    /// Returns the number of cells ($n^d$).
+    #[wasm_bindgen]
+    pub fn get_grid_len(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Returns the order (n) of the hypercube.
+    #[wasm_bindgen(getter)]
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    /// Returns the number of dimensions (3 for a cube).
+    #[wasm_bindgen(getter)]
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+}
+
+impl MagicCubeResult {
+    /// Returns a copy of the flat cells for Rust-side callers.
+    pub fn cells(&self) -> Vec<u32> {
+        self.cells.clone()
+    }
+}
+
+/// Generates a magic hypercube of order `n` in `dimensions` dimensions from Javascript
+/// (`dimensions = 3` for a magic cube). Odd and doubly-even orders are supported.
+#[wasm_bindgen]
+pub fn generate_magic_hypercube(n: usize, dimensions: usize) -> Result<MagicCubeResult, JsError> {
+    // Same memory cap as `generate_magic_square`.
+    const MAX_SAFE_CELLS: usize = 7000 * 7000;
+    if hypercube::cell_count(n, dimensions).is_some_and(|cells| cells > MAX_SAFE_CELLS) {
+        return Err(JsError::new(&format!(
+            "Order {} in {} dimensions is too large for browser memory safety. Capped at {} cells.",
+            n, dimensions, MAX_SAFE_CELLS
+        )));
+    }
+
+    let mut lcg = Lcg::new();
+    let cells = hypercube::MagicCube::with_dimensions(&mut lcg, dimensions)
+        .generate(n)
+        .map_err(|e| JsError::new(&e.to_string()))?;
+    Ok(MagicCubeResult { cells, n, dimensions })
+}
+
+/// A square from the built-in catalog, exported to WASM with its description.
+#[wasm_bindgen]
+pub struct CatalogResult {
+    entry: &'static catalog::Entry,
+}
+
+#[wasm_bindgen]
+impl CatalogResult {
+    /// Returns a raw pointer to the grid buffer.
+    #[wasm_bindgen]
+    pub fn get_grid_ptr(&self) -> *const u32 {
+        self.entry.grid.as_ptr()
+    }
+
+    /// Returns the number of elements in the grid.
+    #[wasm_bindgen]
+    pub fn get_grid_len(&self) -> usize {
+        self.entry.grid.len()
+    }
+
+    /// Returns the order (n) of the square.
+    #[wasm_bindgen(getter)]
+    pub fn n(&self) -> usize {
+        self.entry.n
+    }
+
+    /// Returns the display name, e.g. "Dürer's Melencolia I".
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> String {
+        self.entry.name.to_string()
+    }
+
+    /// Returns the historical note on the square.
+    #[wasm_bindgen(getter)]
+    pub fn description(&self) -> String {
+        self.entry.description.to_string()
+    }
+
+    /// Returns the computed properties as a sentence, e.g. "Order 4, line sum 34. Magic."
+    #[wasm_bindgen(getter)]
+    pub fn summary(&self) -> String {
+        self.entry.summary()
+    }
+}
+
+impl CatalogResult {
+    /// Returns a copy of the flat grid for Rust-side callers.
+    pub fn grid(&self) -> Vec<u32> {
+        self.entry.grid.to_vec()
+    }
+}
+
+/// Looks up a famous square by name (e.g. `durer`, `lo-shu`, `franklin-8`) from Javascript.
+#[wasm_bindgen]
+pub fn catalog_square(key: &str) -> Result<CatalogResult, JsError> {
+    catalog::find(key)
+        .map(|entry| CatalogResult { entry })
+        .ok_or_else(|| JsError::new(&format!("No catalog square named '{}'.", key)))
+}
+
+/// Lists the keys accepted by [`catalog_square`].
+#[wasm_bindgen]
+pub fn catalog_keys() -> Vec<String> {
+    catalog::ENTRIES.iter().map(|e| e.key.to_string()).collect()
+}
+
+/// Formats a flat grid from Javascript as `text`, `csv`, `tsv`, `json`, `ndjson`, `markdown`
+/// `latex` or `svg`, for copying or downloading. Use [`format_square_binary`] for the binary
+/// format, [`save_msq`] for `.msq` files and [`render_heatmap`] for PNG and PPM images.
+#[wasm_bindgen]
+pub fn format_square(grid: &[u32], n: usize, format: &str) -> Result<String, JsError> {
+    let format = match format.parse() {
+        Ok(output::Format::Binary) => return Err(JsError::new("Use format_square_binary for the binary format.")),
+        Ok(output::Format::Msq) => return Err(JsError::new("Use save_msq for the .msq format.")),
+        Ok(output::Format::Ppm | output::Format::Png) => return Err(JsError::new("Use render_heatmap for PNG and PPM images.")),
+        Ok(format) => format,
+        Err(()) => return Err(JsError::new(&format!("Unknown format '{}'.", format))),
+    };
+    check_grid_len(grid, n)?;
+    let bytes = output::format_square(grid, n, format, &output::Metadata::default());
+    Ok(String::from_utf8(bytes).expect("text formats are UTF-8"))
+}
+
+/// Encodes a flat grid from Javascript in the compact binary format: the order, the cell
+/// width, then little-endian cells.
+#[wasm_bindgen]
+pub fn format_square_binary(grid: &[u32], n: usize) -> Result<Vec<u8>, JsError> {
+    check_grid_len(grid, n)?;
+    Ok(output::binary(grid, n))
+}
+
+/// Encodes a flat grid from Javascript as a `.msq` file, recording how it was made.
+///
+/// # Arguments
+///
+/// * `method` - The construction, e.g. `latin`, or `None` if unknown.
+/// * `seed` - The seed that reproduces the square, or `None`.
+#[wasm_bindgen]
+pub fn save_msq(grid: &[u32], n: usize, method: Option<String>, seed: Option<u64>) -> Result<Vec<u8>, JsError> {
+    check_grid_len(grid, n)?;
+    let verified = validator::check_magic_properties(grid, n);
+    let metadata = output::Metadata { method, seed };
+    msq::to_bytes(grid, n, &metadata, verified).map_err(|e| JsError::new(&e.to_string()))
+}
+
+/// Loads the first square of a `.msq` file, checking its checksum.
+#[wasm_bindgen]
+pub fn load_msq(mut bytes: &[u8]) -> Result<MagicSquareResult, JsError> {
+    let (header, grid) = msq::read_square(&mut bytes).map_err(|e| JsError::new(&e.to_string()))?;
+    Ok(MagicSquareResult { grid, n: header.n })
+}
+
+/// Renders a flat grid from Javascript as an SVG image.
+///
+/// # Arguments
+///
+/// * `highlight` - A line to shade, such as `row 2`, `column 3`, `diagonal` or `anti-diagonal`.
+/// * `magic_line` - Whether to draw the path through the cells holding 1, 2, ..., n².
+#[wasm_bindgen]
+pub fn render_svg(grid: &[u32], n: usize, highlight: Option<String>, magic_line: bool) -> Result<String, JsError> {
+    let highlight = match highlight.as_deref() {
+        None | Some("") => None,
+        Some(name) => Some(name.parse().map_err(|()| JsError::new(&format!("Unknown line '{}'.", name)))?),
+    };
+    let options = svg::SvgOptions { highlight, magic_line, ..svg::SvgOptions::default() };
+    svg::render(grid, n, &options).map_err(|e| JsError::new(&e.to_string()))
+}
+
+/// Renders a flat grid from Javascript as a heatmap image, returned as the bytes of the file.
+///
+/// # Arguments
+///
+/// * `format` - `png` or `ppm`.
+/// * `gradient` - `viridis` (the default), `inferno`, `grayscale`, `slate`, or `#rrggbb` stops
+///   separated by commas.
+/// * `scale` - Pixels per cell, or 0 for an image about 512 pixels wide.
+#[wasm_bindgen]
+pub fn render_heatmap(grid: &[u32], n: usize, format: &str, gradient: Option<String>, scale: usize) -> Result<Vec<u8>, JsError> {
+    let format = match format {
+        "png" => heatmap::ImageFormat::Png,
+        "ppm" => heatmap::ImageFormat::Ppm,
+        _ => return Err(JsError::new(&format!("Unknown image format '{}'. Use png or ppm.", format))),
+    };
+    let gradient = match gradient.as_deref() {
+        None | Some("") => heatmap::Gradient::default(),
+        Some(name) => name.parse().map_err(|()| JsError::new(&format!("Unknown gradient '{}'.", name)))?,
+    };
+    let scale = if scale == 0 { heatmap::scale_for(n, heatmap::DEFAULT_SIDE) } else { scale };
+    heatmap::render(grid, n, &gradient, scale, format).map_err(|e| JsError::new(&e.to_string()))
+}
+
+fn check_grid_len(grid: &[u32], n: usize) -> Result<(), JsError> {
+    if grid.len() != n * n {
+        return Err(JsError::new(&format!("An order {} square has {} cells, not {}.", n, n * n, grid.len())));
+    }
+    Ok(())
+}
+
+/// A fill-in puzzle exported to WASM: the clues shown to the player and the solution.
+/// Empty cells are `0` in the clue buffer.
+#[wasm_bindgen]
+pub struct PuzzleResult {
+    clues: Vec<u32>,
+    solution: Vec<u32>,
+    n: usize,
+    difficulty: Difficulty,
+}
+
+#[wasm_bindgen]
+impl PuzzleResult {
+    /// Returns a raw pointer to the clue buffer (`0` for empty cells).
+    #[wasm_bindgen]
+    pub fn get_clues_ptr(&self) -> *const u32 {
+        self.clues.as_ptr()
+    }
+
+    /// Returns a raw pointer to the solution buffer.
+    #[wasm_bindgen]
+    pub fn get_solution_ptr(&self) -> *const u32 {
+        self.solution.as_ptr()
+    }
+
+    /// Returns the number of elements in each buffer.
+    #[wasm_bindgen]
+    pub fn get_grid_len(&self) -> usize {
+        self.clues.len()
+    }
+
+    /// Returns the order (n) of the square.
+    #[wasm_bindgen(getter)]
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    /// Returns the rated difficulty, which can be below the requested one.
+    #[wasm_bindgen(getter)]
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+}
+
+impl PuzzleResult {
+    /// Returns a copy of the clues for Rust-side callers.
+    pub fn clues(&self) -> Vec<u32> {
+        self.clues.clone()
+    }
+
+    /// Returns a copy of the solution for Rust-side callers.
+    pub fn solution(&self) -> Vec<u32> {
+        self.solution.clone()
+    }
+}
+
+/// Generates a "solve the square" puzzle from Javascript.
+///
+/// # Arguments
+///
+/// * `n` - The order of the square (3 to `puzzle::MAX_ORDER`).
+/// * `difficulty` - The requested difficulty.
+/// * `seed` - The same seed always gives the same puzzle.
+#[wasm_bindgen]
+pub fn generate_puzzle(n: usize, difficulty: Difficulty, seed: u32) -> Result<PuzzleResult, JsError> {
+    let puzzle = puzzle::make_puzzle(n, difficulty, seed as u64).map_err(|e| JsError::new(&e.to_string()))?;
+
+    Ok(PuzzleResult {
+        clues: puzzle.clues.iter().map(|c| c.unwrap_or(0)).collect(),
+        solution: puzzle.solution,
+        n,
+        difficulty: puzzle.difficulty,
+    })
+}
+
 /// Verifies if a given grid is a valid magic square.
 /// This function is NOT exported to WASM to save size, effectively removed from web build.
 /// It remains available for other targets (like tests or CLI if we were to expose it there).
@@ -112,7 +621,7 @@ mod tests {
     use super::*;
     use wasm_bindgen_test::*;
 
-    #[wasm_bindgen_test]
+    #[wasm_bindgen_test(unsupported = test)]
     fn test_generate_order_3() {
         let result = generate_magic_square(3).expect("Should generate order 3");
         assert_eq!(result.n(), 3);
@@ -122,7 +631,7 @@ mod tests {
         assert!(verify_magic_square(3, result.grid()));
     }
 
-    #[wasm_bindgen_test]
+    #[wasm_bindgen_test(unsupported = test)]
     fn test_generate_order_4() {
         let result = generate_magic_square(4).expect("Should generate order 4");
         assert_eq!(result.n(), 4);
@@ -132,7 +641,7 @@ mod tests {
         assert!(verify_magic_square(4, result.grid()));
     }
 
-    #[wasm_bindgen_test]
+    #[wasm_bindgen_test(unsupported = test)]
     fn test_generate_order_6() {
         let result = generate_magic_square(6).expect("Should generate order 6");
         assert_eq!(result.n(), 6);
@@ -142,6 +651,119 @@ mod tests {
         assert!(verify_magic_square(6, result.grid()));
     }
 
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_generate_puzzle() {
+        let result = generate_puzzle(4, Difficulty::Medium, 1).expect("Should generate a puzzle");
+        assert_eq!(result.n(), 4);
+        assert_eq!(result.get_grid_len(), 16);
+        let clues = result.clues();
+        let solution = result.solution();
+        assert!(clues.iter().zip(&solution).all(|(&c, &s)| c == 0 || c == s));
+        assert!(clues.contains(&0));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_generate_magic_rectangle() {
+        let result = generate_magic_rectangle(4, 6).expect("Should generate a 4x6 rectangle");
+        assert_eq!((result.rows(), result.cols()), (4, 6));
+        assert!(validator::check_magic_rectangle(&result.grid(), 4, 6));
+
+        let result = generate_semi_magic_square(5).expect("Should generate order 5");
+        assert!(validator::check_semi_magic_properties(&result.grid(), 5));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_generate_magic_hypercube() {
+        let result = generate_magic_hypercube(5, 3).expect("Should generate an order 5 cube");
+        assert_eq!((result.n(), result.dimensions(), result.get_grid_len()), (5, 3, 125));
+        assert!(validator::check_hypercube_properties(&result.cells(), 5, 3));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_generate_multimagic_square() {
+        let result = generate_multimagic_square(8, 2).expect("Should generate a bimagic square");
+        assert!(validator::classify(&result.grid(), 8).bimagic);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_generate_franklin_square() {
+        let result = generate_franklin_square(16).expect("Should generate a Franklin square");
+        assert!(validator::classify(&result.grid(), 16).franklin);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_catalog_square() {
+        let keys = catalog_keys();
+        assert!(keys.contains(&"durer".to_string()));
+        for key in keys {
+            let result = catalog_square(&key).expect("Listed keys should resolve");
+            assert_eq!(result.get_grid_len(), result.n() * result.n());
+        }
+        let durer = catalog_square("Durer").expect("Lookup ignores case");
+        #[cfg(not(target_arch = "wasm32"))]
+        assert!(verify_magic_square(4, durer.grid()));
+        assert_eq!(durer.summary(), "Order 4, line sum 34. Magic.");
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_format_square() {
+        let lo_shu = catalog_square("lo-shu").expect("Lo Shu is in the catalog").grid();
+        assert_eq!(format_square(&lo_shu, 3, "csv").expect("CSV is a text format"), "4,9,2\n3,5,7\n8,1,6\n");
+        let json = format_square(&lo_shu, 3, "json").expect("JSON is a text format");
+        assert!(json.contains("\"magic_constant\": 15"));
+
+        let bytes = format_square_binary(&lo_shu, 3).expect("The grid has 9 cells");
+        assert_eq!(output::read_binary(&bytes).expect("Valid record"), vec![(3, lo_shu)]);
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_msq_round_trip() {
+        let square = generate_magic_square(12).expect("Should generate order 12").grid();
+        let bytes = save_msq(&square, 12, Some("latin".to_string()), Some(7)).expect("The grid has 144 cells");
+        let loaded = load_msq(&bytes).expect("Freshly written file");
+        assert_eq!((loaded.n(), loaded.grid()), (12, square));
+
+        let header = msq::MsqReader::new(&bytes[..]).expect("Valid header").header().clone();
+        assert!(header.verified);
+        assert_eq!(header.metadata.seed, Some(7));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_packed_square() {
+        let packed = generate_packed_square(31).expect("Should generate order 31");
+        assert_eq!((packed.n(), packed.bits()), (31, 10));
+        assert!(packed.byte_len() < 31 * 31 * 4 / 3 + 8);
+        let grid = packed.square().to_vec();
+        #[cfg(not(target_arch = "wasm32"))]
+        assert!(verify_magic_square(31, grid.clone()));
+        assert_eq!(packed.row(30).expect("Last row"), grid[30 * 31..]);
+        assert_eq!(packed.get(0, 5).expect("In range"), grid[5]);
+
+        for n in [30, 32] {
+            let packed = generate_packed_square(n).expect("Even orders pack too");
+            #[cfg(not(target_arch = "wasm32"))]
+            assert!(verify_magic_square(n, packed.square().to_vec()));
+        }
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_render_svg() {
+        let durer = catalog_square("durer").expect("Dürer is in the catalog").grid();
+        let svg = render_svg(&durer, 4, Some("row 1".to_string()), true).expect("Row 1 exists");
+        assert!(svg.starts_with("<svg") && svg.contains("<circle"));
+        assert_eq!(svg.matches("<text ").count(), 16);
+        assert!(render_svg(&durer, 4, None, false).expect("No highlight").contains("</svg>"));
+    }
+
+    #[wasm_bindgen_test(unsupported = test)]
+    fn test_render_heatmap() {
+        let square = generate_magic_square(10).expect("Should generate order 10").grid();
+        let png = render_heatmap(&square, 10, "png", None, 0).expect("Default options");
+        assert!(png.starts_with(b"\x89PNG"));
+        let ppm = render_heatmap(&square, 10, "ppm", Some("#000000,#ffffff".to_string()), 2).expect("Custom gradient");
+        assert!(ppm.starts_with(b"P6\n20 20\n255\n"));
+    }
+
     #[wasm_bindgen_test]
     fn test_invalid_orders() {
         assert!(generate_magic_square(0).is_err());
@@ -1,136 +1,899 @@
 use magic_squares::rng::Lcg;
 use magic_squares::generator;
 use magic_squares::validator;
+use magic_squares::canonical;
+use magic_squares::enumerate::{self, EnumerateConfig};
+use magic_squares::orbit;
+use magic_squares::catalog;
+use magic_squares::input::{self, Square};
+use magic_squares::output::{self, Metadata};
+use magic_squares::svg::{self, SvgOptions};
+use magic_squares::heatmap::{self, Gradient, ImageFormat};
+use magic_squares::msq;
+use magic_squares::stream::{self, RowSource};
+use magic_squares::{franklin, latin, multimagic, rectangle};
 use std::env;
+use std::fmt;
+use std::io::{Read, Write};
+use std::path::PathBuf;
 use std::collections::HashSet;
+use std::str::FromStr;
+use std::time::Instant;
 
+/// Top-level help, followed by the list of commands.
+const USAGE: &str = "\
+Magic square generator and validator.
 
+Usage: magic_squares <COMMAND> [OPTIONS]
+       magic_squares <COMMAND> --help
+
+Exit codes: 0 on success, 1 when a check fails or the command cannot complete,
+2 when the command line is invalid.
+
+Commands:";
+
+/// A subcommand of the CLI.
+struct Command {
+    name: &'static str,
+    summary: &'static str,
+    help: &'static str,
+    run: fn(&[String]) -> Result<(), CliError>,
+}
+
+const COMMANDS: &[Command] = &[
+    Command {
+        name: "generate",
+        summary: "Generate magic squares",
+        help: "\
+Usage: magic_squares generate -n <ORDER> [OPTIONS]
+
+Options:
+  -n, --order <N>     Order of the squares (required)
+  --method <METHOD>   auto (default), latin, franklin, bimagic, trimagic or semi-magic
+  --seed <SEED>       Seed for reproducible output (default: drawn from the current time)
+  --count <K>         Number of squares to generate (default: 1)
+  --format <FORMAT>   text (default), csv, tsv, json, ndjson, markdown, latex, svg, ppm,
+                      png, binary or msq
+  --stream            Write the rows as they are computed, for orders too large to hold
+                      in memory (one square, text, csv, tsv or msq). The rows come from
+                      the Siamese, LUX or truth-grid formulas, so the square differs from
+                      the one plain generate makes with the same seed
+
+Image formats (svg, ppm, png) hold one square.
+
+SVG options:
+  --highlight <LINE>  Shade a line: 'row 2', 'column 3', 'diagonal' or 'anti-diagonal'
+  --magic-line        Draw the path through the cells holding 1, 2, ..., n²
+  --cell-size <PX>    Side of a cell in pixels (default: 40)
+  --no-numbers        Leave the cells blank
+
+Heatmap options (ppm, png):
+  --gradient <NAME>   viridis (default), inferno, grayscale, slate, or #rrggbb stops
+                      separated by commas
+  --scale <PX>        Pixels per cell (default: about 512 pixels wide, at least 1 per cell)
+
+Text formats print the squares one after another, separated by blank lines. JSON records
+n, the method, the seed and the magic constant, as one object per square or an array of
+them; NDJSON puts one object on each line. Binary writes, per square, the order as a
+little-endian u32, the cell width in bytes, then the little-endian cells. MSQ writes
+.msq files, which also record the method, the seed and a checksum, and are read back by
+`verify` and `analyze`.",
+        run: run_generate,
+    },
+    Command {
+        name: "verify",
+        summary: "Check squares read from a file or stdin",
+        help: "\
+Usage: magic_squares verify [FILE] [OPTIONS]
+
+Options:
+  --input-format <FORMAT>   auto (default), text, csv, json or msq
+  --stream                  Check a single square row by row, keeping only the line sums
+                            and a bitmap of the values seen (text, csv or msq)
+
+Reads squares from FILE, or from stdin when FILE is missing or '-'. The formats are
+  text   whitespace-separated numbers, squares separated by blank lines
+  csv    comma-separated rows, squares separated by blank lines
+  json   an array of rows, a flat array, or an object with a \"grid\" field; one square,
+         an array of squares, or one square per line (NDJSON)
+  msq    .msq files, as written by `generate --format msq`, detected by their magic bytes
+The order of each square is inferred from its number of values.
+
+Prints a report for each square, listing the values and lines that break the magic
+conditions, and exits with 1 if any square is not a magic square.",
+        run: run_verify,
+    },
+    Command {
+        name: "analyze",
+        summary: "Report the properties of squares read from a file or stdin",
+        help: "\
+Usage: magic_squares analyze [FILE] [OPTIONS]
+
+Options:
+  --input-format <FORMAT>   auto (default), text, csv, json or msq
+
+Reads squares like `verify` and reports, for each one, its line sum and whether it is
+semi-magic, magic, pandiagonal, bimagic, trimagic and a Franklin square.",
+        run: run_analyze,
+    },
+    Command {
+        name: "bench",
+        summary: "Generate and verify many squares of each order, with timings",
+        help: "\
+Usage: magic_squares bench [OPTIONS]
+
+Options:
+  --orders <A..B>     Orders to run, as a range or a single order, optionally every STEP-th
+                      order with A..B/STEP (default: 1..100)
+  --samples <K>       Squares generated per order (default: 100)
+  --threads <T>       Worker threads (default: all cores)
+  --compare           Then time the validator's line sums and uniqueness check against the
+                      strided sums and sorted copy they replaced, and against their
+                      parallel versions
+
+Order 2 is skipped. Exits with 1 if any generated square is invalid.",
+        run: run_bench,
+    },
+    Command {
+        name: "enumerate",
+        summary: "Count every magic square of a small order",
+        help: "\
+Usage: magic_squares enumerate -n <ORDER> [OPTIONS]
+
+Options:
+  -n, --order <N>       Order to count, up to 5 (required)
+  --threads <T>         Worker threads (default: all cores)
+  --checkpoint <FILE>   Save progress to FILE and resume from it
+
+Progress is reported on stderr.",
+        run: run_enumerate,
+    },
+    Command {
+        name: "orbit",
+        summary: "Explore the squares reachable from a generated square",
+        help: "\
+Usage: magic_squares orbit -n <ORDER> [OPTIONS]
+
+Options:
+  -n, --order <N>     Order of the generated square (required)
+  --limit <K>         Stop after K squares (default: 100000)
+  --seed <SEED>       Seed for the generated square
+
+Applies symmetries, symmetric row/column pair permutations and (for pandiagonal squares)
+cyclic shifts until no new square appears.",
+        run: run_orbit,
+    },
+    Command {
+        name: "show",
+        summary: "Show a famous square from the catalog",
+        help: "\
+Usage: magic_squares show [NAME]
+
+Without NAME, lists the squares in the catalog.",
+        run: run_show,
+    },
+];
 
 /// Main entry point for the Command Line Interface (CLI) version of the Magic Square Generator.
-/// 
+///
 /// Usage:
-///     magic_squares.exe -n <ORDER>
+///     magic_squares.exe generate -n <ORDER> [--method <METHOD>] [--seed <SEED>] [--count <K>]
+///     magic_squares.exe verify [FILE] [--input-format <FORMAT>] [--stream]
+///     magic_squares.exe analyze [FILE] [--input-format <FORMAT>]
+///     magic_squares.exe bench [--orders <A..B>] [--samples <K>] [--threads <T>]
+///     magic_squares.exe enumerate -n <ORDER> [--threads <COUNT>] [--checkpoint <FILE>]
+///     magic_squares.exe orbit -n <ORDER> [--limit <COUNT>]
+///     magic_squares.exe show [NAME]
 ///
 /// Example:
-///     magic_squares.exe -n 7
+///     magic_squares.exe generate -n 7
+///     magic_squares.exe generate -n 8 --method franklin | magic_squares.exe analyze
+///     magic_squares.exe generate -n 100000 --stream --format msq > big.msq
+///     magic_squares.exe enumerate -n 5 --checkpoint order5.ckpt
 ///
-/// If no arguments are provided, it runs a verification suite for orders 1-100.
+/// Every command takes `--help`. The exit code is 0 on success, 1 on failure and 2 on an
+/// invalid command line.
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let mut target_n = 0;
-
-    // Parse arguments
-    for i in 0..args.len() {
-        if args[i] == "-n" && i + 1 < args.len() {
-            if let Ok(n) = args[i+1].parse::<usize>() {
-                target_n = n;
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let code = match args.first().map(String::as_str) {
+        None => {
+            eprintln!("{}", usage());
+            2
+        }
+        Some("help" | "-h" | "--help") => {
+            println!("{}", usage());
+            0
+        }
+        Some(name) => match COMMANDS.iter().find(|c| c.name == name) {
+            None => {
+                eprintln!("Error: unknown command '{}'.\n\n{}", name, usage());
+                2
             }
+            Some(command) if args[1..].iter().any(|a| a == "--help" || a == "-h") => {
+                println!("{}", command.help);
+                0
+            }
+            Some(command) => match (command.run)(&args[1..]) {
+                Ok(()) => 0,
+                Err(err) => {
+                    eprintln!("Error: {}", err);
+                    if let CliError::Usage(_) = err {
+                        eprintln!("Run `magic_squares {} --help` for usage.", command.name);
+                    }
+                    err.exit_code()
+                }
+            },
+        },
+    };
+    std::process::exit(code);
+}
+
+/// The top-level help with the command list.
+fn usage() -> String {
+    let mut text = USAGE.to_string();
+    for command in COMMANDS {
+        text += &format!("\n  {:<10} {}", command.name, command.summary);
+    }
+    text
+}
+
+/// Why a command stopped.
+enum CliError {
+    /// The command line is invalid (exit code 2).
+    Usage(String),
+    /// A check failed or the work could not be done (exit code 1).
+    Failed(String),
+}
+
+impl CliError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            Self::Usage(_) => 2,
+            Self::Failed(_) => 1,
         }
     }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Usage(msg) | Self::Failed(msg) => write!(f, "{}", msg),
+        }
+    }
+}
 
-    let mut lcg = Lcg::new();
+/// The arguments of a command: options with their values, flags, and positional arguments.
+struct Args {
+    values: Vec<(&'static str, String)>,
+    flags: Vec<&'static str>,
+    positional: Vec<String>,
+}
 
-    if target_n > 0 {
-        // Single Generation Mode
-        if target_n == 2 {
-             println!("Order 2 Magic Square is impossible.");
-             return;
+impl Args {
+    /// Parses `args` against the `options` a command accepts, each of which takes a value.
+    /// `-n` is short for `--order`, and `-` is a positional argument (stdin).
+    fn parse(args: &[String], options: &[&'static str]) -> Result<Self, CliError> {
+        Self::parse_with_flags(args, options, &[])
+    }
+
+    /// Like [`Args::parse`], also accepting `flags`, which take no value.
+    fn parse_with_flags(args: &[String], options: &[&'static str], flags: &[&'static str]) -> Result<Self, CliError> {
+        let mut parsed = Args { values: Vec::new(), flags: Vec::new(), positional: Vec::new() };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let name = if arg == "-n" { "--order" } else { arg.as_str() };
+            if let Some(&option) = options.iter().find(|&&o| o == name) {
+                let value = iter.next().ok_or_else(|| CliError::Usage(format!("{} needs a value.", option)))?;
+                parsed.values.push((option, value.clone()));
+            } else if let Some(&flag) = flags.iter().find(|&&f| f == name) {
+                parsed.flags.push(flag);
+            } else if name.starts_with('-') && name != "-" {
+                return Err(CliError::Usage(format!("unknown option '{}'.", arg)));
+            } else {
+                parsed.positional.push(arg.clone());
+            }
         }
-        let mut magic_gen = generator::create(target_n, &mut lcg);
-        let sq = magic_gen.generate(target_n);
-        print_square(&sq, target_n);
-        
-        if validator::check_magic_properties(&sq, target_n) {
-            println!("\nVerified: This is a valid magic square.");
-        } else {
-            println!("\nError: The generated square is invalid!");
-        }
-
-    } else {
-        // Parallel Verification Mode (Orders 1 to 100)
-        use std::thread;
-        use std::sync::mpsc;
-        
-        println!("Running Parallel Verification for Orders 1 to 100 (100 samples each)...");
-        
-        // Determine number of worker threads
-        let num_threads = thread::available_parallelism()
-            .map(|n| n.get())
-            .unwrap_or(4);
-        
-        println!("Using {} worker threads", num_threads);
-        
-        // Collect all orders to process (excluding n=2)
-        let orders: Vec<usize> = (1..=100).filter(|&n| n != 2).collect();
-        let chunk_size = (orders.len() + num_threads - 1) / num_threads;
-        
-        let (tx, rx) = mpsc::channel();
-        
-        // Spawn worker threads
-        let handles: Vec<_> = orders
-            .chunks(chunk_size)
-            .enumerate()
-            .map(|(thread_id, chunk)| {
-                let chunk = chunk.to_vec();
-                let tx = tx.clone();
-                
-                thread::spawn(move || {
-                    for &n in &chunk {
-                        // Each thread gets its own RNG seeded with thread_id + n
-                        let mut lcg = Lcg::new_with_seed((thread_id * 1000 + n) as u64);
-                        let mut unique_squares = HashSet::new();
-                        let mut all_valid = true;
-                        
-                        for _ in 0..100 {
-                            let mut magic_gen = generator::create(n, &mut lcg);
-                            let sq = magic_gen.generate(n);
-                            
-                            if !validator::check_magic_properties(&sq, n) {
-                                all_valid = false;
-                                break;
-                            }
-                            unique_squares.insert(sq);
+        Ok(parsed)
+    }
+
+    /// The last value given for `option`, parsed.
+    fn get<T: FromStr>(&self, option: &str) -> Result<Option<T>, CliError> {
+        match self.values.iter().rev().find(|(o, _)| *o == option) {
+            None => Ok(None),
+            Some((_, value)) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| CliError::Usage(format!("invalid value '{}' for {}.", value, option))),
+        }
+    }
+
+    /// Like [`Args::get`], for an option that must be given.
+    fn require<T: FromStr>(&self, option: &str) -> Result<T, CliError> {
+        self.get(option)?.ok_or_else(|| CliError::Usage(format!("{} is required.", option)))
+    }
+
+    /// Whether `flag` was given.
+    fn flag(&self, flag: &str) -> bool {
+        self.flags.contains(&flag)
+    }
+
+    /// At most `max` positional arguments.
+    fn positional(&self, max: usize) -> Result<&[String], CliError> {
+        match self.positional.get(max) {
+            Some(extra) => Err(CliError::Usage(format!("unexpected argument '{}'.", extra))),
+            None => Ok(&self.positional),
+        }
+    }
+
+    /// The optional input file of `verify` and `analyze`.
+    fn input(&self) -> Result<Option<&str>, CliError> {
+        Ok(self.positional(1)?.first().map(String::as_str))
+    }
+}
+
+/// An RNG seeded with `--seed`, or with the current time.
+fn seeded_rng(args: &Args) -> Result<Lcg, CliError> {
+    Ok(match args.get::<u64>("--seed")? {
+        Some(seed) => Lcg::new_with_seed(seed),
+        None => Lcg::new(),
+    })
+}
+
+/// Construction used by `generate`.
+#[derive(Clone, Copy)]
+enum Method {
+    Auto,
+    Latin,
+    Franklin,
+    Bimagic,
+    Trimagic,
+    SemiMagic,
+}
+
+impl FromStr for Method {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "latin" => Ok(Self::Latin),
+            "franklin" => Ok(Self::Franklin),
+            "bimagic" => Ok(Self::Bimagic),
+            "trimagic" => Ok(Self::Trimagic),
+            "semi-magic" => Ok(Self::SemiMagic),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Method {
+    fn generate(self, n: usize, rng: &mut Lcg) -> Result<Vec<u32>, String> {
+        match self {
+            Self::Auto if n == 0 || n == 2 => Err(format!("there is no magic square of order {}.", n)),
+            Self::Auto => Ok(generator::create(n, rng).generate(n)),
+            Self::Latin => latin::magic_square(n, rng).map_err(|e| e.to_string()),
+            Self::Franklin => franklin::generate(n, rng).map_err(|e| e.to_string()),
+            Self::Bimagic => multimagic::generate(n, 2, rng).map_err(|e| e.to_string()),
+            Self::Trimagic => multimagic::generate(n, 3, rng).map_err(|e| e.to_string()),
+            Self::SemiMagic => rectangle::generate_semi_magic(n, rng).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// Generates `--count` squares with the chosen method and writes them in the chosen format.
+fn run_generate(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse_with_flags(
+        args,
+        &["--order", "--method", "--seed", "--count", "--format", "--highlight", "--cell-size", "--gradient", "--scale"],
+        &["--magic-line", "--no-numbers", "--stream"],
+    )?;
+    args.positional(0)?;
+    let n: usize = args.require("--order")?;
+    if args.flag("--stream") {
+        return run_generate_stream(n, &args);
+    }
+    let method_name = args.get::<String>("--method")?.unwrap_or_else(|| "auto".to_string());
+    let method: Method = method_name.parse().map_err(|_| CliError::Usage(format!("unknown method '{}'.", method_name)))?;
+    let count = args.get::<usize>("--count")?.unwrap_or(1);
+    let format = match args.get::<String>("--format")? {
+        None => output::Format::Text,
+        Some(name) => name.parse().map_err(|_| CliError::Usage(format!("unknown format '{}'.", name)))?,
+    };
+    // Draw a seed up front so that it can be reported and the output reproduced.
+    let seed = args.get::<u64>("--seed")?.unwrap_or_else(|| Lcg::new().next_u32() as u64);
+    let mut rng = Lcg::new_with_seed(seed);
+
+    let write_error = |e: std::io::Error| CliError::Failed(format!("cannot write the output: {}.", e));
+    if format.is_image() {
+        if count != 1 {
+            return Err(CliError::Usage(format!("--format {} writes a single square; drop --count.", format.name())));
+        }
+        let grid = method.generate(n, &mut rng).map_err(CliError::Failed)?;
+        let bytes = render_image(&grid, n, format, &args)?;
+        return std::io::stdout().write_all(&bytes).map_err(write_error);
+    }
+
+    // Each square is written as soon as it is made, so memory stays at one square whatever
+    // the count.
+    let metadata = Metadata { method: Some(method_name), seed: Some(seed) };
+    let out = std::io::BufWriter::new(std::io::stdout().lock());
+    let mut writer = output::SquareWriter::new(out, n, format, metadata, count);
+    for _ in 0..count {
+        let grid = method.generate(n, &mut rng).map_err(CliError::Failed)?;
+        writer.write_square(&grid).map_err(write_error)?;
+    }
+    writer.finish().map_err(write_error)?;
+    Ok(())
+}
+
+/// Writes one square row by row, without holding it in memory.
+fn run_generate_stream(n: usize, args: &Args) -> Result<(), CliError> {
+    if args.get::<String>("--method")?.is_some_and(|method| method != "auto") {
+        return Err(CliError::Usage("--stream computes Siamese, LUX or truth-grid squares; drop --method.".to_string()));
+    }
+    if args.get::<usize>("--count")?.is_some_and(|count| count != 1) {
+        return Err(CliError::Usage("--stream writes a single square; drop --count.".to_string()));
+    }
+    let format = match args.get::<String>("--format")? {
+        None => output::Format::Text,
+        Some(name) => name.parse().map_err(|_| CliError::Usage(format!("unknown format '{}'.", name)))?,
+    };
+    let seed = args.get::<u64>("--seed")?.unwrap_or_else(|| Lcg::new().next_u32() as u64);
+    let source = RowSource::new(n, seed).map_err(|e| CliError::Failed(e.to_string()))?;
+    let metadata = Metadata { method: Some(source.method().to_string()), seed: Some(seed) };
+    stream::write(&source, format, &metadata, std::io::stdout().lock()).map_err(|e| match e {
+        stream::StreamError::UnsupportedFormat(_) => CliError::Usage(e.to_string()),
+        e => CliError::Failed(format!("cannot write the output: {}", e)),
+    })
+}
+
+/// Renders an SVG, PPM or PNG image with the options of `generate`.
+fn render_image(grid: &[u32], n: usize, format: output::Format, args: &Args) -> Result<Vec<u8>, CliError> {
+    if format == output::Format::Svg {
+        let svg = svg::render(grid, n, &svg_options(args)?).map_err(|e| CliError::Failed(e.to_string()))?;
+        return Ok(svg.into_bytes());
+    }
+    let gradient = match args.get::<String>("--gradient")? {
+        None => Gradient::default(),
+        Some(name) => name.parse().map_err(|()| CliError::Usage(format!("unknown gradient '{}'.", name)))?,
+    };
+    let scale = args.get("--scale")?.unwrap_or_else(|| heatmap::scale_for(n, heatmap::DEFAULT_SIDE));
+    let image = if format == output::Format::Ppm { ImageFormat::Ppm } else { ImageFormat::Png };
+    heatmap::render(grid, n, &gradient, scale, image).map_err(|e| CliError::Failed(e.to_string()))
+}
+
+/// The SVG options of `generate`.
+fn svg_options(args: &Args) -> Result<SvgOptions, CliError> {
+    let highlight = match args.get::<String>("--highlight")? {
+        None => None,
+        Some(name) => Some(name.parse().map_err(|()| CliError::Usage(format!("unknown line '{}'.", name)))?),
+    };
+    Ok(SvgOptions {
+        cell_size: args.get("--cell-size")?.unwrap_or(SvgOptions::default().cell_size),
+        numbers: !args.flag("--no-numbers"),
+        highlight,
+        magic_line: args.flag("--magic-line"),
+    })
+}
+
+/// Reads the squares of the input file, or of stdin for no file or `-`, in the
+/// `--input-format` (detected from the input by default).
+fn read_squares(args: &Args) -> Result<Vec<Square>, CliError> {
+    let format = args.get::<String>("--input-format")?;
+    let path = args.input()?;
+    let bytes = match path {
+        None | Some("-") => {
+            let mut bytes = Vec::new();
+            std::io::stdin()
+                .read_to_end(&mut bytes)
+                .map_err(|e| CliError::Failed(format!("cannot read stdin: {}.", e)))?;
+            bytes
+        }
+        Some(path) => std::fs::read(path).map_err(|e| CliError::Failed(format!("cannot read '{}': {}.", path, e)))?,
+    };
+
+    let format = match format.as_deref() {
+        None | Some("auto") if msq::is_msq(&bytes) => return read_msq(&bytes),
+        Some("msq") => return read_msq(&bytes),
+        None | Some("auto") => None,
+        Some(name) => Some(name.parse::<input::Format>().map_err(|_| CliError::Usage(format!("unknown input format '{}'.", name)))?),
+    };
+    let text = String::from_utf8(bytes).map_err(|_| CliError::Failed("the input is not UTF-8 text.".to_string()))?;
+    let squares = match format {
+        Some(format) => input::parse_as(&text, format),
+        None => input::parse(&text),
+    };
+    squares.map_err(|e| CliError::Failed(e.to_string()))
+}
+
+/// Reads concatenated `.msq` files. They have no lines, so every square is at line 0.
+fn read_msq(bytes: &[u8]) -> Result<Vec<Square>, CliError> {
+    let squares = msq::from_bytes(bytes).map_err(|e| CliError::Failed(e.to_string()))?;
+    Ok(squares.into_iter().map(|(header, grid)| Square { n: header.n, grid, line: 0 }).collect())
+}
+
+/// "Square i (order n, line l)", without the line for binary input.
+fn heading(i: usize, square: &Square) -> String {
+    match square.line {
+        0 => format!("Square {} (order {})", i + 1, square.n),
+        line => format!("Square {} (order {}, line {})", i + 1, square.n, line),
+    }
+}
+
+/// Checks every square of the input and fails if any is not a magic square.
+fn run_verify(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse_with_flags(args, &["--input-format"], &["--stream"])?;
+    if args.flag("--stream") {
+        return run_verify_stream(&args);
+    }
+    let squares = read_squares(&args)?;
+
+    let mut failed = 0;
+    for (i, square) in squares.iter().enumerate() {
+        let (grid, n) = (&square.grid, square.n);
+        let heading = heading(i, square);
+        if validator::check_magic_properties(grid, n) {
+            println!("{}: valid, line sum {}", heading, validator::magic_constant(n));
+            continue;
+        }
+
+        failed += 1;
+        println!("{}: INVALID", heading);
+        let diagnosis = validator::diagnose(grid, n);
+        if !diagnosis.out_of_range.is_empty() {
+            println!("  values outside 1..{}: {}", n * n, list(&diagnosis.out_of_range));
+        }
+        if !diagnosis.repeated.is_empty() {
+            println!("  repeated values: {}", list(&diagnosis.repeated));
+        }
+        if !diagnosis.wrong_lines.is_empty() {
+            let lines: Vec<String> = diagnosis.wrong_lines.iter().map(|(line, sum)| format!("{} sums to {}", line, sum)).collect();
+            println!("  lines not summing to {}: {}", validator::magic_constant(n), list(&lines));
+        }
+    }
+
+    if failed > 0 {
+        return Err(CliError::Failed(format!("{} of {} squares are not magic squares.", failed, squares.len())));
+    }
+    Ok(())
+}
+
+/// Checks a single square row by row, without holding it in memory.
+fn run_verify_stream(args: &Args) -> Result<(), CliError> {
+    if args.get::<String>("--input-format")?.is_some() {
+        return Err(CliError::Usage("--stream detects .msq and text input by itself; drop --input-format.".to_string()));
+    }
+    let report = match args.input()? {
+        None | Some("-") => stream::validate(std::io::stdin().lock()),
+        Some(path) => {
+            let file = std::fs::File::open(path).map_err(|e| CliError::Failed(format!("cannot read '{}': {}.", path, e)))?;
+            stream::validate(std::io::BufReader::new(file))
+        }
+    }
+    .map_err(|e| CliError::Failed(e.to_string()))?;
+
+    let n = report.n;
+    if report.is_magic() {
+        println!("Square 1 (order {}): valid, line sum {}", n, validator::magic_constant(n));
+        return Ok(());
+    }
+    println!("Square 1 (order {}): INVALID", n);
+    if report.out_of_range > 0 {
+        println!("  values outside 1..{}: {}", n as u64 * n as u64, report.out_of_range);
+    }
+    if report.repeated > 0 {
+        println!("  repeated values: {}", report.repeated);
+    }
+    if !report.wrong_lines.is_empty() {
+        let lines: Vec<String> = report.wrong_lines.iter().map(|(line, sum)| format!("{} sums to {}", line, sum)).collect();
+        println!("  lines not summing to {}: {}", validator::magic_constant(n), list(&lines));
+    }
+    Err(CliError::Failed("the square is not a magic square.".to_string()))
+}
+
+/// Comma-separated items, cut short after the first ten.
+fn list<T: fmt::Display>(items: &[T]) -> String {
+    const SHOWN: usize = 10;
+    let mut text = items.iter().take(SHOWN).map(T::to_string).collect::<Vec<_>>().join(", ");
+    if items.len() > SHOWN {
+        text += &format!(" and {} more", items.len() - SHOWN);
+    }
+    text
+}
+
+/// Reports the line sum and the properties of every square of the input.
+fn run_analyze(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &["--input-format"])?;
+    let squares = read_squares(&args)?;
+
+    for (i, square) in squares.iter().enumerate() {
+        let (grid, n) = (&square.grid, square.n);
+        let p = validator::classify(grid, n);
+        if i > 0 {
+            println!();
+        }
+        println!("{}", heading(i, square));
+        match validator::common_line_sum(grid, n) {
+            Some(sum) => println!("  line sum:    {}", sum),
+            None => println!("  line sum:    none"),
+        }
+        for (name, holds) in [
+            ("semi-magic", p.semi_magic),
+            ("magic", p.magic),
+            ("pandiagonal", p.pandiagonal),
+            ("bimagic", p.bimagic),
+            ("trimagic", p.trimagic),
+            ("franklin", p.franklin),
+        ] {
+            println!("  {:<12} {}", format!("{}:", name), if holds { "yes" } else { "no" });
+        }
+    }
+    Ok(())
+}
+
+/// Parses `A..B`, `A..=B` or a single order, leaving out order 2.
+fn parse_orders(text: &str) -> Option<Vec<usize>> {
+    let (range, step) = match text.split_once('/') {
+        Some((range, step)) => (range, step.parse().ok().filter(|&step| step > 0)?),
+        None => (text, 1),
+    };
+    let (low, high): (usize, usize) = match range.split_once("..") {
+        Some((low, high)) => (low.parse().ok()?, high.trim_start_matches('=').parse().ok()?),
+        None => {
+            let n = range.parse().ok()?;
+            (n, n)
+        }
+    };
+    let orders: Vec<usize> = (low.max(1)..=high).step_by(step).filter(|&n| n != 2).collect();
+    (!orders.is_empty()).then_some(orders)
+}
+
+/// Generates and verifies `--samples` squares of each order in parallel, with timings.
+fn run_bench(args: &[String]) -> Result<(), CliError> {
+    use std::thread;
+    use std::sync::mpsc;
+
+    let args = Args::parse_with_flags(args, &["--orders", "--samples", "--threads"], &["--compare"])?;
+    args.positional(0)?;
+    let orders = match args.get::<String>("--orders")? {
+        None => (1..=100).filter(|&n| n != 2).collect(),
+        Some(text) => {
+            parse_orders(&text).ok_or_else(|| CliError::Usage(format!("invalid value '{}' for --orders.", text)))?
+        }
+    };
+    let samples = args.get::<usize>("--samples")?.unwrap_or(100).max(1);
+    // Determine number of worker threads
+    let num_threads = match args.get::<usize>("--threads")? {
+        Some(t) if t > 0 => t,
+        _ => thread::available_parallelism().map(|n| n.get()).unwrap_or(4),
+    };
+
+    println!("Generating and verifying {} squares of each order with {} worker threads...", samples, num_threads);
+
+    let chunk_size = orders.len().div_ceil(num_threads);
+    let (tx, rx) = mpsc::channel();
+
+    // Spawn worker threads
+    let handles: Vec<_> = orders
+        .chunks(chunk_size)
+        .enumerate()
+        .map(|(thread_id, chunk)| {
+            let chunk = chunk.to_vec();
+            let tx = tx.clone();
+
+            thread::spawn(move || {
+                for &n in &chunk {
+                    // Each thread gets its own RNG seeded with thread_id + n
+                    let mut lcg = Lcg::new_with_seed((thread_id * 1000 + n) as u64);
+                    let mut unique_squares = HashSet::new();
+                    let mut distinct_squares = HashSet::new();
+                    let mut valid = 0;
+                    let start = Instant::now();
+
+                    for _ in 0..samples {
+                        let mut magic_gen = generator::create(n, &mut lcg);
+                        let sq = magic_gen.generate(n);
+
+                        if validator::check_magic_properties(&sq, n) {
+                            valid += 1;
                         }
-                        
-                        tx.send((n, all_valid, unique_squares.len())).unwrap();
+                        distinct_squares.insert(canonical::canonical_hash(&sq, n));
+                        unique_squares.insert(sq);
                     }
-                })
+
+                    tx.send((n, valid, unique_squares.len(), distinct_squares.len(), start.elapsed())).unwrap();
+                }
             })
-            .collect();
-        
-        // Close the sender so receiver knows when to stop
-        drop(tx);
-        
-        // Collect results from all threads
-        let mut results: Vec<_> = rx.iter().collect();
-        results.sort_by_key(|&(n, _, _)| n);
-        
-        // Wait for all threads to complete
-        for handle in handles {
-            handle.join().unwrap();
-        }
-        
-        // Print results in order
-        for (n, valid, unique_count) in results {
-            if valid {
-                println!("Order {}: 100/100 Valid. Unique Variations: {}", n, unique_count);
-            } else {
-                println!("Order {}: FAILED VALIDATION", n);
-            }
+        })
+        .collect();
+
+    // Close the sender so receiver knows when to stop
+    drop(tx);
+
+    // Collect results from all threads
+    let mut results: Vec<_> = rx.iter().collect();
+    results.sort_by_key(|&(n, ..)| n);
+
+    // Wait for all threads to complete
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    // Print results in order
+    let mut failed = 0;
+    for (n, valid, unique_count, distinct_count, elapsed) in results {
+        if valid == samples {
+            let per_square = elapsed.as_secs_f64() * 1e6 / samples as f64;
+            println!(
+                "Order {}: {}/{} valid, {} unique ({} essentially different), {:.1} µs per square",
+                n, valid, samples, unique_count, distinct_count, per_square
+            );
+        } else {
+            println!("Order {}: FAILED VALIDATION ({}/{} valid)", n, valid, samples);
+            failed += 1;
         }
     }
+
+    if args.flag("--compare") {
+        compare_validators(&orders, samples, num_threads);
+    }
+
+    if failed > 0 {
+        return Err(CliError::Failed(format!("{} orders produced invalid squares.", failed)));
+    }
+    Ok(())
 }
 
-fn print_square(grid: &[u32], n: usize) {
-    if grid.is_empty() { return; }
-    let max_val = n * n;
-    let width = max_val.to_string().len() + 1; // +1 for spacing
+/// Times the line sums and the uniqueness check of `validator::check_magic_properties`
+/// against the implementations they replaced, and against their parallel versions with
+/// `threads` threads.
+fn compare_validators(orders: &[usize], samples: usize, threads: usize) {
+    /// The previous line sums: every row, then every column striding through the grid, then
+    /// both diagonals.
+    fn strided_sums(grid: &[u32], n: usize) -> bool {
+        let target = validator::magic_constant(n);
+        (0..n).all(|r| grid[r * n..(r + 1) * n].iter().map(|&v| v as u64).sum::<u64>() == target)
+            && (0..n).all(|c| (0..n).map(|r| grid[r * n + c] as u64).sum::<u64>() == target)
+            && (0..n).map(|i| grid[i * n + i] as u64).sum::<u64>() == target
+            && (0..n).map(|i| grid[i * n + (n - 1 - i)] as u64).sum::<u64>() == target
+    }
 
-    for r in 0..n {
-        for c in 0..n {
-            let val = grid[r * n + c];
-            print!("{:width$}", val, width = width);
+    /// The previous uniqueness check: sort a copy of the grid and compare it with 1..n².
+    fn sorted_copy(grid: &[u32], _: usize) -> bool {
+        let mut flat = grid.to_vec();
+        flat.sort_unstable();
+        flat.iter().enumerate().all(|(i, &v)| v as usize == i + 1)
+    }
+
+    fn time(samples: usize, grid: &[u32], n: usize, check: &dyn Fn(&[u32], usize) -> bool) -> f64 {
+        let start = Instant::now();
+        for _ in 0..samples {
+            assert!(check(std::hint::black_box(grid), n), "a generated square failed the check");
         }
+        start.elapsed().as_secs_f64() * 1e6 / samples as f64
+    }
+
+    let squares: Vec<(usize, Vec<u32>)> =
+        orders.iter().map(|&n| (n, generator::create(n, &mut Lcg::new_with_seed(n as u64)).generate(n))).collect();
+    let sums_parallel = |grid: &[u32], n| validator::check_magic_sums_parallel(grid, n, threads);
+    let unique = |grid: &[u32], _| validator::is_permutation(grid);
+    let unique_parallel = |grid: &[u32], _| validator::is_permutation_parallel(grid, threads);
+    type Check<'a> = &'a dyn Fn(&[u32], usize) -> bool;
+    let tables: [(&str, [Check; 3]); 2] = [
+        ("Line sums", [&strided_sums, &validator::check_magic_sums, &sums_parallel]),
+        ("Uniqueness check", [&sorted_copy, &unique, &unique_parallel]),
+    ];
+    for (title, checks) in tables {
         println!();
+        println!("{}, µs per square (previous / current / {} threads):", title, threads);
+        let mut totals = [0.0; 3];
+        for (n, grid) in &squares {
+            let times = checks.map(|check| time(samples, grid, *n, check));
+            for (total, t) in totals.iter_mut().zip(times) {
+                *total += t;
+            }
+            println!(
+                "Order {}: {:.1} / {:.1} / {:.1} µs ({:.1}x, {:.1}x)",
+                n,
+                times[0],
+                times[1],
+                times[2],
+                times[0] / times[1],
+                times[0] / times[2]
+            );
+        }
+        println!(
+            "Total: {:.1} / {:.1} / {:.1} µs ({:.1}x, {:.1}x)",
+            totals[0],
+            totals[1],
+            totals[2],
+            totals[0] / totals[1],
+            totals[0] / totals[2]
+        );
+    }
+    if let Some((n, _)) = squares.last() {
+        println!("The sorted copy takes {} bytes at order {}, the bitset {}.", 4 * n * n, n, (n * n).div_ceil(64) * 8);
+    }
+}
+
+/// Counts every magic square of the requested order, reporting progress on stderr.
+fn run_enumerate(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &["--order", "--threads", "--checkpoint"])?;
+    args.positional(0)?;
+    let config = EnumerateConfig {
+        threads: args.get("--threads")?.unwrap_or(0),
+        checkpoint: args.get::<PathBuf>("--checkpoint")?,
+        ..EnumerateConfig::new(args.require("--order")?)
+    };
+    println!("Counting magic squares of order {} (up to rotations and reflections)...", config.n);
+
+    let mut last_percent = None;
+    let result = enumerate::count_magic_squares(&config, |p| {
+        let percent = p.completed * 100 / p.total.max(1);
+        if last_percent != Some(percent) {
+            last_percent = Some(percent);
+            eprintln!("  {:3}% ({}/{} units, {} squares so far)", percent, p.completed, p.total, p.count);
+        }
+    });
+
+    let e = result.map_err(|err| CliError::Failed(err.to_string()))?;
+    if e.resumed > 0 {
+        println!("Resumed {} of {} work units from checkpoint.", e.resumed, e.units);
     }
+    println!("Order {}: {} essentially different squares ({} counting rotations and reflections).", e.n, e.distinct, e.total);
+    Ok(())
+}
+
+/// Explores and reports the orbit of a generated square.
+fn run_orbit(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &["--order", "--limit", "--seed"])?;
+    args.positional(0)?;
+    let n: usize = args.require("--order")?;
+    let limit = args.get::<usize>("--limit")?.unwrap_or(orbit::DEFAULT_LIMIT).max(1);
+    if n == 0 || n == 2 {
+        return Err(CliError::Failed(format!("there is no magic square of order {}.", n)));
+    }
+    let mut rng = seeded_rng(&args)?;
+    let sq = generator::create(n, &mut rng).generate(n);
+    print_square(&sq, n);
+
+    let result = orbit::orbit(&sq, n, limit);
+    println!();
+    if result.pandiagonal {
+        println!("The square is pandiagonal: cyclic shifts are included.");
+    }
+    let bound = if result.complete { "" } else { "at least " };
+    println!(
+        "Orbit of order {}: {}{} essentially different squares ({}{} counting rotations and reflections).",
+        n, bound, result.size(), bound, result.raw_size
+    );
+    if !result.complete {
+        println!("Stopped at the limit of {} squares (raise it with --limit).", limit);
+    }
+    Ok(())
+}
+
+/// Prints a square from the catalog, or lists the catalog when no name is given.
+fn run_show(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &[])?;
+    let Some(name) = args.positional(1)?.first() else {
+        println!("Famous squares (use `show <NAME>`):");
+        for entry in catalog::ENTRIES {
+            println!("  {:<16} {}", entry.key, entry.name);
+        }
+        return Ok(());
+    };
+    let entry = catalog::find(name)
+        .ok_or_else(|| CliError::Failed(format!("no square named '{}' (run `show` to list them).", name)))?;
+
+    println!("{}\n", entry.name);
+    print_square(entry.grid, entry.n);
+    println!("\n{}", entry.summary());
+    println!("{}", entry.description);
+    Ok(())
+}
+
+fn print_square(grid: &[u32], n: usize) {
+    print!("{}", output::text(grid, n));
 }
@@ -0,0 +1,477 @@
+//! The `.msq` binary format: squares stored and reloaded without text parsing.
+//!
+//! A file holds one square; several files may be concatenated and read back in turn. All
+//! integers are little-endian.
+//!
+//! | Offset | Size  | Field                                                          |
+//! |--------|-------|----------------------------------------------------------------|
+//! | 0      | 8     | Magic bytes `89 4D 53 51 0D 0A 1A 0A` (`\x89MSQ\r\n\x1a\n`)    |
+//! | 8      | 2     | Format version, currently 1                                    |
+//! | 10     | 2     | Flags: bit 0 if the seed is set, bit 1 if the square was verified magic when written |
+//! | 12     | 4     | Order $n$                                                      |
+//! | 16     | 1     | Cell width in bytes: 1, 2, 4 or 8                              |
+//! | 17     | 1     | Length $m$ of the method name                                  |
+//! | 18     | 2     | Reserved, 0                                                    |
+//! | 20     | 8     | Seed (0 when unset)                                            |
+//! | 28     | $m$   | Method name, UTF-8 (e.g. `latin`), empty when unknown          |
+//! | 28 + m | $wn^2$ | Cells in row-major order, $w$ bytes each                      |
+//! | end    | 4     | CRC-32 of every byte before it, from the magic bytes on        |
+//!
+//! Like PNG, the magic bytes catch files mangled by text-mode transfers, and the CRC catches
+//! truncated or corrupted files. [`MsqWriter`] and [`MsqReader`] work a row at a time, so
+//! squares larger than memory can be streamed; [`to_bytes`] and [`from_bytes`] handle whole
+//! grids.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use crate::checksum::Crc32;
+use crate::output::Metadata;
+
+/// The first bytes of every `.msq` file.
+pub const MAGIC: [u8; 8] = [0x89, b'M', b'S', b'Q', b'\r', b'\n', 0x1A, b'\n'];
+/// The format version written by [`MsqWriter`].
+pub const VERSION: u16 = 1;
+
+const FLAG_SEED: u16 = 1;
+const FLAG_VERIFIED: u16 = 2;
+const FIXED_HEADER: usize = 28;
+
+/// The header of a `.msq` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    /// The order of the square.
+    pub n: usize,
+    /// Bytes per cell: 1, 2, 4 or 8.
+    pub cell_width: u8,
+    /// How the square was made, and the seed that reproduces it.
+    pub metadata: Metadata,
+    /// Whether the square was checked to be magic when it was written.
+    pub verified: bool,
+}
+
+impl Header {
+    /// A header with the narrowest cell width that holds `max_value`.
+    pub fn new(n: usize, max_value: u64, metadata: Metadata) -> Self {
+        Self { n, cell_width: cell_width(max_value), metadata, verified: false }
+    }
+
+    /// The size of the cells in bytes, $n^2$ times the cell width, or `None` if it overflows.
+    pub fn grid_len(&self) -> Option<usize> {
+        self.n.checked_mul(self.n)?.checked_mul(self.cell_width as usize)
+    }
+}
+
+/// The narrowest cell width, in bytes, that holds `max_value`.
+pub fn cell_width(max_value: u64) -> u8 {
+    match max_value {
+        0..=0xFF => 1,
+        0x100..=0xFFFF => 2,
+        0x1_0000..=0xFFFF_FFFF => 4,
+        _ => 8,
+    }
+}
+
+/// Errors returned while reading or writing `.msq` data.
+#[derive(Debug)]
+pub enum MsqError {
+    /// Reading or writing failed.
+    Io(io::Error),
+    /// The data ends before the square does.
+    Truncated,
+    /// The data does not start with [`MAGIC`].
+    BadMagic,
+    /// The file was written by a newer version of the format.
+    UnsupportedVersion(u16),
+    /// The cell width is not 1, 2, 4 or 8, or too narrow for a value.
+    InvalidCellWidth(u8),
+    /// The method name is not UTF-8 or is longer than 255 bytes.
+    InvalidMethod,
+    /// The order does not fit in the header, or the grid does not hold $n^2$ values.
+    InvalidOrder(usize),
+    /// The stored CRC does not match the data.
+    ChecksumMismatch { stored: u32, computed: u32 },
+    /// A cell is wider than the `u32` grids of the crate.
+    ValueTooLarge(u64),
+}
+
+impl fmt::Display for MsqError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Truncated => write!(f, "The .msq data is cut short."),
+            Self::BadMagic => write!(f, "This is not .msq data: the magic bytes are missing."),
+            Self::UnsupportedVersion(v) => write!(f, "Version {} of the .msq format is not supported (up to {}).", v, VERSION),
+            Self::InvalidCellWidth(w) => write!(f, "A cell width of {} bytes is invalid. Expected 1, 2, 4 or 8.", w),
+            Self::InvalidMethod => write!(f, "The method name must be UTF-8 and at most 255 bytes."),
+            Self::InvalidOrder(n) => write!(f, "Order {} does not match the data or does not fit in the header.", n),
+            Self::ChecksumMismatch { stored, computed } => {
+                write!(f, "The checksum does not match (stored {:08x}, computed {:08x}). The data is corrupted.", stored, computed)
+            }
+            Self::ValueTooLarge(v) => write!(f, "The value {} does not fit in 32 bits.", v),
+        }
+    }
+}
+
+impl std::error::Error for MsqError {}
+
+impl From<io::Error> for MsqError {
+    fn from(e: io::Error) -> Self {
+        if e.kind() == io::ErrorKind::UnexpectedEof { Self::Truncated } else { Self::Io(e) }
+    }
+}
+
+/// Writes a square row by row.
+pub struct MsqWriter<W: Write> {
+    out: W,
+    crc: Crc32,
+    n: usize,
+    cell_width: u8,
+    rows_left: usize,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> MsqWriter<W> {
+    /// Writes the header. Every row must follow, then [`MsqWriter::finish`].
+    pub fn new(out: W, header: &Header) -> Result<Self, MsqError> {
+        if !matches!(header.cell_width, 1 | 2 | 4 | 8) {
+            return Err(MsqError::InvalidCellWidth(header.cell_width));
+        }
+        let order = u32::try_from(header.n).map_err(|_| MsqError::InvalidOrder(header.n))?;
+        let method = header.metadata.method.as_deref().unwrap_or("");
+        let method_len = u8::try_from(method.len()).map_err(|_| MsqError::InvalidMethod)?;
+
+        let mut flags = 0;
+        if header.metadata.seed.is_some() {
+            flags |= FLAG_SEED;
+        }
+        if header.verified {
+            flags |= FLAG_VERIFIED;
+        }
+        let mut bytes = Vec::with_capacity(FIXED_HEADER + method.len());
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&flags.to_le_bytes());
+        bytes.extend_from_slice(&order.to_le_bytes());
+        bytes.extend_from_slice(&[header.cell_width, method_len, 0, 0]);
+        bytes.extend_from_slice(&header.metadata.seed.unwrap_or(0).to_le_bytes());
+        bytes.extend_from_slice(method.as_bytes());
+
+        let mut writer = Self {
+            out,
+            crc: Crc32::new(),
+            n: header.n,
+            cell_width: header.cell_width,
+            rows_left: header.n,
+            buffer: Vec::with_capacity(header.n * header.cell_width as usize),
+        };
+        writer.emit(&bytes)?;
+        Ok(writer)
+    }
+
+    /// Writes the next row of $n$ values.
+    pub fn write_row<T: Copy + Into<u64>>(&mut self, row: &[T]) -> Result<(), MsqError> {
+        if row.len() != self.n || self.rows_left == 0 {
+            return Err(MsqError::InvalidOrder(self.n));
+        }
+        let width = self.cell_width as usize;
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.clear();
+        for &v in row {
+            let v: u64 = v.into();
+            if width < 8 && v >> (8 * width) != 0 {
+                return Err(MsqError::InvalidCellWidth(self.cell_width));
+            }
+            buffer.extend_from_slice(&v.to_le_bytes()[..width]);
+        }
+        self.emit(&buffer)?;
+        self.buffer = buffer;
+        self.rows_left -= 1;
+        Ok(())
+    }
+
+    /// Writes the checksum after the last row and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, MsqError> {
+        if self.rows_left != 0 {
+            return Err(MsqError::InvalidOrder(self.n));
+        }
+        self.out.write_all(&self.crc.finish().to_le_bytes())?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn emit(&mut self, bytes: &[u8]) -> Result<(), MsqError> {
+        self.crc = self.crc.update(bytes);
+        self.out.write_all(bytes)?;
+        Ok(())
+    }
+}
+
+/// Reads a square row by row, checking the CRC after the last row.
+pub struct MsqReader<R: Read> {
+    input: R,
+    crc: Crc32,
+    header: Header,
+    rows_left: usize,
+    buffer: Vec<u8>,
+}
+
+impl<R: Read> MsqReader<R> {
+    /// Reads and checks the header.
+    pub fn new(mut input: R) -> Result<Self, MsqError> {
+        let mut fixed = [0u8; FIXED_HEADER];
+        input.read_exact(&mut fixed)?;
+        if fixed[..8] != MAGIC {
+            return Err(MsqError::BadMagic);
+        }
+        let version = u16::from_le_bytes([fixed[8], fixed[9]]);
+        if version == 0 || version > VERSION {
+            return Err(MsqError::UnsupportedVersion(version));
+        }
+        let flags = u16::from_le_bytes([fixed[10], fixed[11]]);
+        let n = u32::from_le_bytes(fixed[12..16].try_into().unwrap()) as usize;
+        let cell_width = fixed[16];
+        if !matches!(cell_width, 1 | 2 | 4 | 8) {
+            return Err(MsqError::InvalidCellWidth(cell_width));
+        }
+        let seed = u64::from_le_bytes(fixed[20..28].try_into().unwrap());
+        let mut method = vec![0; fixed[17] as usize];
+        input.read_exact(&mut method)?;
+        let method = String::from_utf8(method).map_err(|_| MsqError::InvalidMethod)?;
+
+        let header = Header {
+            n,
+            cell_width,
+            metadata: Metadata {
+                method: (!method.is_empty()).then(|| method.clone()),
+                seed: (flags & FLAG_SEED != 0).then_some(seed),
+            },
+            verified: flags & FLAG_VERIFIED != 0,
+        };
+        if header.grid_len().is_none() {
+            return Err(MsqError::InvalidOrder(n));
+        }
+        let crc = Crc32::new().update(&fixed).update(method.as_bytes());
+        // The row buffer grows as data arrives, so a corrupted order cannot demand memory
+        // the input does not back.
+        let mut reader = Self { input, crc, header, rows_left: n, buffer: Vec::new() };
+        // An empty square has no rows, so its checksum follows the header directly.
+        if n == 0 {
+            reader.check_crc()?;
+        }
+        Ok(reader)
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Reads the next row into `row`, which must hold $n$ values. After the last row the
+    /// checksum is read and checked.
+    pub fn read_row(&mut self, row: &mut [u64]) -> Result<(), MsqError> {
+        if row.len() != self.header.n {
+            return Err(MsqError::InvalidOrder(self.header.n));
+        }
+        for (value, cell) in row.iter_mut().zip(self.next_row()?) {
+            *value = cell;
+        }
+        Ok(())
+    }
+
+    /// Reads every row into a flat `u32` grid, which grows a row at a time.
+    pub fn read_grid(mut self) -> Result<(Header, Vec<u32>), MsqError> {
+        let mut grid = Vec::new();
+        for _ in 0..self.header.n {
+            for v in self.next_row()? {
+                grid.push(u32::try_from(v).map_err(|_| MsqError::ValueTooLarge(v))?);
+            }
+        }
+        Ok((self.header, grid))
+    }
+
+    /// Reads the next row into the buffer and returns its values.
+    fn next_row(&mut self) -> Result<impl Iterator<Item = u64> + '_, MsqError> {
+        if self.rows_left == 0 {
+            return Err(MsqError::InvalidOrder(self.header.n));
+        }
+        let width = self.header.cell_width as usize;
+        let len = self.header.n * width;
+        self.buffer.clear();
+        (&mut self.input).take(len as u64).read_to_end(&mut self.buffer)?;
+        if self.buffer.len() < len {
+            return Err(MsqError::Truncated);
+        }
+        self.crc = self.crc.update(&self.buffer);
+
+        self.rows_left -= 1;
+        if self.rows_left == 0 {
+            self.check_crc()?;
+        }
+        Ok(self.buffer.chunks(width).map(move |cell| {
+            let mut le = [0u8; 8];
+            le[..width].copy_from_slice(cell);
+            u64::from_le_bytes(le)
+        }))
+    }
+
+    /// Reads the trailing checksum and compares it with everything read so far.
+    fn check_crc(&mut self) -> Result<(), MsqError> {
+        let mut stored = [0u8; 4];
+        self.input.read_exact(&mut stored)?;
+        let (stored, computed) = (u32::from_le_bytes(stored), self.crc.finish());
+        if stored != computed {
+            return Err(MsqError::ChecksumMismatch { stored, computed });
+        }
+        Ok(())
+    }
+}
+
+/// Encodes a whole square, with the narrowest cell width that holds its values.
+pub fn to_bytes(grid: &[u32], n: usize, metadata: &Metadata, verified: bool) -> Result<Vec<u8>, MsqError> {
+    if grid.len() != n * n {
+        return Err(MsqError::InvalidOrder(n));
+    }
+    let max = grid.iter().copied().max().unwrap_or(0) as u64;
+    let header = Header { verified, ..Header::new(n, max, metadata.clone()) };
+    let mut writer = MsqWriter::new(Vec::with_capacity(FIXED_HEADER + 4 + grid.len() * 4), &header)?;
+    for row in grid.chunks(n.max(1)) {
+        writer.write_row(row)?;
+    }
+    writer.finish()
+}
+
+/// Decodes every square of concatenated `.msq` data.
+pub fn from_bytes(mut bytes: &[u8]) -> Result<Vec<(Header, Vec<u32>)>, MsqError> {
+    let mut squares = Vec::new();
+    while !bytes.is_empty() {
+        squares.push(read_square(&mut bytes)?);
+    }
+    Ok(squares)
+}
+
+/// Decodes the square at the start of `bytes` and advances the slice past it.
+///
+/// With the length known up front, an order whose cells cannot fit in the remaining bytes
+/// is rejected before anything is allocated for it.
+pub fn read_square(bytes: &mut &[u8]) -> Result<(Header, Vec<u32>), MsqError> {
+    // Reading through `&mut &[u8]` advances the slice.
+    let reader = MsqReader::new(bytes)?;
+    if reader.header.grid_len().is_none_or(|len| len > reader.input.len()) {
+        return Err(MsqError::InvalidOrder(reader.header.n));
+    }
+    reader.read_grid()
+}
+
+/// Whether `bytes` starts like a `.msq` file.
+pub fn is_msq(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog;
+
+    fn metadata() -> Metadata {
+        Metadata { method: Some("latin".to_string()), seed: Some(42) }
+    }
+
+    #[test]
+    fn round_trips_squares() {
+        let durer = catalog::DURER.grid;
+        let bytes = to_bytes(durer, 4, &metadata(), true).unwrap();
+        assert_eq!(bytes[..8], MAGIC);
+        assert_eq!(bytes.len(), FIXED_HEADER + 5 + 16 + 4);
+        let squares = from_bytes(&bytes).unwrap();
+        assert_eq!(squares.len(), 1);
+        let (header, grid) = &squares[0];
+        assert_eq!(grid, durer);
+        assert_eq!(*header, Header { n: 4, cell_width: 1, metadata: metadata(), verified: true });
+
+        // Wider cells, no metadata, and two files back to back.
+        let big: Vec<u32> = (1..=90_000).collect();
+        let mut both = to_bytes(&big, 300, &Metadata::default(), false).unwrap();
+        both.extend(to_bytes(durer, 4, &Metadata::default(), false).unwrap());
+        let squares = from_bytes(&both).unwrap();
+        assert_eq!((squares[0].0.cell_width, squares[0].0.metadata.clone()), (4, Metadata::default()));
+        assert_eq!((&squares[0].1, &squares[1].1[..]), (&big, durer));
+    }
+
+    #[test]
+    fn round_trips_empty_squares() {
+        // With no rows, the checksum still has to be read and checked.
+        let bytes = to_bytes(&[], 0, &metadata(), false).unwrap();
+        assert_eq!(bytes.len(), FIXED_HEADER + 5 + 4);
+        let mut both = bytes.clone();
+        both.extend(to_bytes(catalog::LO_SHU.grid, 3, &Metadata::default(), false).unwrap());
+        both.extend(&bytes);
+        let squares = from_bytes(&both).unwrap();
+        assert_eq!(squares.len(), 3);
+        assert_eq!((squares[0].0.n, squares[0].1.len(), &squares[1].1[..]), (0, 0, catalog::LO_SHU.grid));
+        assert_eq!(squares[2], squares[0]);
+
+        let mut flipped = bytes.clone();
+        *flipped.last_mut().unwrap() ^= 1;
+        assert!(matches!(from_bytes(&flipped), Err(MsqError::ChecksumMismatch { .. })));
+        assert!(matches!(from_bytes(&bytes[..bytes.len() - 1]), Err(MsqError::Truncated)));
+    }
+
+    #[test]
+    fn streams_rows_of_wide_values() {
+        let header = Header::new(2, u64::MAX, Metadata::default());
+        assert_eq!(header.cell_width, 8);
+        let mut writer = MsqWriter::new(Vec::new(), &header).unwrap();
+        writer.write_row(&[1u64, u64::MAX]).unwrap();
+        writer.write_row(&[3u64, 4]).unwrap();
+        let bytes = writer.finish().unwrap();
+
+        let mut reader = MsqReader::new(&bytes[..]).unwrap();
+        let mut row = [0u64; 2];
+        reader.read_row(&mut row).unwrap();
+        assert_eq!(row, [1, u64::MAX]);
+        reader.read_row(&mut row).unwrap();
+        assert_eq!(row, [3, 4]);
+        assert!(matches!(MsqReader::new(&bytes[..]).unwrap().read_grid(), Err(MsqError::ValueTooLarge(u64::MAX))));
+    }
+
+    #[test]
+    fn detects_corruption() {
+        let bytes = to_bytes(catalog::LO_SHU.grid, 3, &metadata(), true).unwrap();
+        let mut flipped = bytes.clone();
+        flipped[40] ^= 1;
+        assert!(matches!(from_bytes(&flipped), Err(MsqError::ChecksumMismatch { .. })));
+        assert!(matches!(from_bytes(&bytes[..bytes.len() - 1]), Err(MsqError::Truncated)));
+        assert!(matches!(from_bytes(&bytes[1..]), Err(MsqError::BadMagic)));
+
+        let mut future = bytes.clone();
+        future[8] = 2;
+        assert!(matches!(from_bytes(&future), Err(MsqError::UnsupportedVersion(2))));
+    }
+
+    #[test]
+    fn rejects_corrupted_orders() {
+        let bytes = to_bytes(catalog::LO_SHU.grid, 3, &metadata(), true).unwrap();
+        for order in [4, 1 << 16, u32::MAX] {
+            let mut corrupted = bytes.clone();
+            corrupted[12..16].copy_from_slice(&order.to_le_bytes());
+            assert!(matches!(from_bytes(&corrupted), Err(MsqError::InvalidOrder(n)) if n == order as usize));
+            // The streaming reader cannot see the length, but runs out of data before memory.
+            let reader = MsqReader::new(&corrupted[..]);
+            assert!(matches!(reader.and_then(MsqReader::read_grid), Err(MsqError::Truncated | MsqError::InvalidOrder(_))));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_writes() {
+        let header = Header::new(2, 255, Metadata::default());
+        let mut writer = MsqWriter::new(Vec::new(), &header).unwrap();
+        assert!(matches!(writer.write_row(&[1u32, 256]), Err(MsqError::InvalidCellWidth(1))));
+        assert!(matches!(writer.write_row(&[1u32]), Err(MsqError::InvalidOrder(2))));
+        writer.write_row(&[1u32, 2]).unwrap();
+        assert!(matches!(writer.finish(), Err(MsqError::InvalidOrder(2))));
+
+        let long = Metadata { method: Some("x".repeat(256)), seed: None };
+        assert!(matches!(to_bytes(&[1], 1, &long, false), Err(MsqError::InvalidMethod)));
+        assert!(matches!(to_bytes(&[1, 2], 1, &Metadata::default(), false), Err(MsqError::InvalidOrder(1))));
+    }
+}
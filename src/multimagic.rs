@@ -0,0 +1,333 @@
+//! Bimagic and trimagic squares: magic squares that stay magic when every entry is squared
+//! (bimagic) and also when it is cubed (trimagic).
+//!
+//! Both constructions work on orders $n = 2^m$, where the binary digits of row $r$ and column
+//! $c$ form a vector $(x, y)$ of $2m$ bits over the field $\mathbb{F}_2$.
+//!
+//! - **Affine geometry** (orders 16 and up): cell $(r, c)$ holds $1 + v$, where the bits of
+//!   $v$ are $A (x, y) \oplus b$ for an invertible $2m \times 2m$ matrix $A$. Along a row only
+//!   $y$ varies, so the bits of $v$ depend on the right half of $A$; along a column on the left
+//!   half, and along either diagonal ($x = y$ or $x = \bar y$) on the sum of both halves. A
+//!   $k$-th power $v^k$ is a combination of products of at most $k$ bits, so the line sums of
+//!   $k$-th powers take their average value when any $k$ rows of the relevant $2m \times m$
+//!   matrix are linearly independent. The matrix is found by a randomized search.
+//! - **Orthogonal Latin squares** (order 8, where no such matrix exists): cell $(r, c)$ holds
+//!   $1 + 8\sigma(a) + \tau(b)$, where $a$ and $b$ are linear Latin squares over
+//!   $\mathbb{F}_2^3$ that are orthogonal and Latin on the diagonals too, and $\sigma$, $\tau$
+//!   relabel their symbols as $0..8$. The square is bimagic when
+//!   $\sum \sigma(a)\tau(b) = 98$ along every line, and $\sigma$, $\tau$ are searched for.
+//!
+//! Bimagic squares are built for orders 8, 16, 32, ... and trimagic squares for orders 32,
+//! 64, ... Trimagic squares of other orders, such as 12, need different constructions and are
+//! not generated.
+
+use std::fmt;
+
+use crate::rng::Lcg;
+use crate::validator::Line;
+
+/// Largest supported order. Values stay below $2^{24}$, and cube sums well within `u128`.
+pub const MAX_ORDER: usize = 1 << 12;
+
+/// Sets of left halves tried by the affine search before giving up.
+const SEARCH_ATTEMPTS: usize = 1000;
+
+/// Steps spent on each set of left halves.
+const SEARCH_BUDGET: usize = 200_000;
+
+/// Pairs of orthogonal Latin squares tried for order 8 before giving up. About three in four
+/// admit labels that make the square bimagic.
+const LATIN_ATTEMPTS: usize = 50;
+
+/// Random linear forms drawn when looking for one Latin square of such a pair.
+const LATIN_DRAWS: usize = 10_000;
+
+/// Errors reported by [`generate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MultimagicError {
+    /// The construction needs a power of two, large enough for the requested power.
+    UnsupportedOrder { n: usize, power: u32 },
+    /// Only bimagic (2) and trimagic (3) squares are built.
+    UnsupportedPower(u32),
+    /// The search did not find a suitable matrix.
+    NotFound { n: usize, power: u32 },
+}
+
+impl fmt::Display for MultimagicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedOrder { n, power } => write!(
+                f,
+                "Order {} is not supported for power {}. Bimagic squares need a power of two from 8 to {}, trimagic squares from 32.",
+                n, power, MAX_ORDER
+            ),
+            Self::UnsupportedPower(power) => {
+                write!(f, "Power {} is not supported. Use 2 for bimagic or 3 for trimagic squares.", power)
+            }
+            Self::NotFound { n, power } => {
+                write!(f, "No order {} square magic up to power {} was found.", n, power)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MultimagicError {}
+
+/// Generates a square of order `n` that is magic for the first `power` powers of its entries:
+/// `power = 2` gives a bimagic square and `power = 3` a trimagic square.
+pub fn generate(n: usize, power: u32, rng: &mut Lcg) -> Result<Vec<u32>, MultimagicError> {
+    if !(2..=3).contains(&power) {
+        return Err(MultimagicError::UnsupportedPower(power));
+    }
+    let min_order = if power == 2 { 8 } else { 32 };
+    if !n.is_power_of_two() || !(min_order..=MAX_ORDER).contains(&n) {
+        return Err(MultimagicError::UnsupportedOrder { n, power });
+    }
+
+    if n == 8 {
+        return order_eight(rng).ok_or(MultimagicError::NotFound { n, power });
+    }
+
+    let m = n.trailing_zeros() as usize;
+    let rows = search(m, power, rng).ok_or(MultimagicError::NotFound { n, power })?;
+    let offset = rng.next_range(0, n * n);
+
+    let mut grid = vec![0; n * n];
+    for r in 0..n {
+        for c in 0..n {
+            grid[r * n + c] = (apply(&rows, (r << m) | c) ^ offset) as u32 + 1;
+        }
+    }
+    Ok(grid)
+}
+
+/// Finds the rows of $A$, as $2m$-bit masks (left half in the high bits).
+///
+/// Row $i$ splits into a left part $l_i$ and a right part $r_i$. The sets of $l_i$, of $r_i$
+/// and of $l_i \oplus r_i$ must each have any `power` members independent, and all rows
+/// together must be independent. The left parts are drawn at random, then the right parts are
+/// completed by a backtracking search.
+fn search(m: usize, power: u32, rng: &mut Lcg) -> Option<Vec<usize>> {
+    for _ in 0..SEARCH_ATTEMPTS {
+        let mut left = Vec::with_capacity(2 * m);
+        for _ in 0..SEARCH_BUDGET {
+            if left.len() == 2 * m {
+                break;
+            }
+            let candidate = rng.next_range(1, 1 << m);
+            if independent_with(candidate, &left, power) {
+                left.push(candidate);
+            }
+        }
+        if left.len() < 2 * m {
+            continue;
+        }
+
+        let mut right = Vec::with_capacity(2 * m);
+        let mut budget = SEARCH_BUDGET;
+        if complete(m, power, &left, &mut right, rng, &mut budget) {
+            return Some(left.iter().zip(&right).map(|(&l, &r)| l << m | r).collect());
+        }
+    }
+    None
+}
+
+/// Extends `right` to match `left`, trying the values in a random order at every step.
+fn complete(m: usize, power: u32, left: &[usize], right: &mut Vec<usize>, rng: &mut Lcg, budget: &mut usize) -> bool {
+    let i = right.len();
+    if i == left.len() {
+        return true;
+    }
+    let sums: Vec<usize> = left.iter().zip(right.iter()).map(|(&l, &r)| l ^ r).collect();
+    let rows: Vec<usize> = left.iter().zip(right.iter()).map(|(&l, &r)| l << m | r).collect();
+
+    let mut candidates: Vec<usize> = (1..1 << m).collect();
+    rng.shuffle(&mut candidates);
+    for candidate in candidates {
+        if *budget == 0 {
+            return false;
+        }
+        *budget -= 1;
+
+        let fits = independent_with(candidate, right, power)
+            && independent_with(left[i] ^ candidate, &sums, power)
+            && independent(&rows, left[i] << m | candidate);
+        if fits {
+            right.push(candidate);
+            if complete(m, power, left, right, rng, budget) {
+                return true;
+            }
+            right.pop();
+        }
+    }
+    false
+}
+
+/// Bimagic square of order 8 from two orthogonal Latin squares (see the module docs).
+///
+/// Along a line, $b = M a \oplus t$ for a matrix $M$ fixed by the direction of the line. In
+/// terms of the Walsh transforms of the centred labels, the line has the right
+/// $\sum \sigma(a)\tau(b)$ for every $t$ exactly when $\hat\sigma(M^T w)\,\hat\tau(w) = 0$ for all
+/// $w \ne 0$. Labelings are grouped by the support of their transform, and only pairs of groups
+/// meeting this for rows and columns are searched; the diagonals are checked directly.
+fn order_eight(rng: &mut Lcg) -> Option<Vec<u32>> {
+    let n = 8;
+    let lines = Line::all(n);
+    let mut labelings = permutations(n);
+    rng.shuffle(&mut labelings);
+    let mut groups: Vec<Vec<usize>> = vec![Vec::new(); 1 << n];
+    for (i, labeling) in labelings.iter().enumerate() {
+        groups[walsh_support(labeling)].push(i);
+    }
+    let mut supports: Vec<usize> = (0..groups.len()).filter(|&s| !groups[s].is_empty()).collect();
+
+    let symbols = |form: &[usize], cell: usize| apply(form, ((cell / n) << 3) | (cell % n));
+    let latin = |form: &[usize]| {
+        lines.iter().all(|line| line.cells(n).fold(0, |seen, cell| seen | 1 << symbols(form, cell)) == 0xff)
+    };
+
+    for _ in 0..LATIN_ATTEMPTS {
+        let Some(a) = draw_form(rng, latin) else { continue };
+        let orthogonal = |b: &[usize]| (0..n * n).fold(0u64, |seen, cell| seen | 1 << (symbols(&a, cell) * 8 + symbols(b, cell))) == u64::MAX;
+        let Some(b) = draw_form(rng, |b| latin(b) && orthogonal(b)) else { continue };
+
+        // pairing[line][a] is the b paired with symbol a along the line.
+        let mut pairing = vec![[0; 8]; lines.len()];
+        for (line, pairs) in lines.iter().zip(&mut pairing) {
+            for cell in line.cells(n) {
+                pairs[symbols(&a, cell)] = symbols(&b, cell);
+            }
+        }
+
+        // Columns of M for rows and for columns, and the products sum(σ(a)τ(b)) per line.
+        let matrix = |pairs: &[usize; 8]| [pairs[1] ^ pairs[0], pairs[2] ^ pairs[0], pairs[4] ^ pairs[0]];
+        let directions = [matrix(&pairing[0]), matrix(&pairing[n])];
+        let balanced = |sigma: &[usize], tau: &[usize]| {
+            pairing.iter().all(|pairs| (0..n).map(|a| sigma[a] * tau[pairs[a]]).sum::<usize>() == 98)
+        };
+
+        rng.shuffle(&mut supports);
+        for &s in &supports {
+            for &t in &supports {
+                let disjoint = (1..n).filter(|w| t >> w & 1 == 1).all(|w| {
+                    // Bit j of M^T w is the dot product of column j of M with w.
+                    directions.iter().all(|m| s >> apply(m, w) & 1 == 0)
+                });
+                if !disjoint {
+                    continue;
+                }
+                for &i in &groups[s] {
+                    for &j in &groups[t] {
+                        let (sigma, tau) = (&labelings[i], &labelings[j]);
+                        if balanced(sigma, tau) {
+                            let value = |cell| (8 * sigma[symbols(&a, cell)] + tau[symbols(&b, cell)] + 1) as u32;
+                            return Some((0..n * n).map(value).collect());
+                        }
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Draws random linear maps $\mathbb{F}_2^6 \to \mathbb{F}_2^3$ until one is accepted.
+fn draw_form(rng: &mut Lcg, accept: impl Fn(&[usize]) -> bool) -> Option<[usize; 3]> {
+    (0..LATIN_DRAWS).map(|_| [0; 3].map(|_| rng.next_range(1, 64))).find(|form| accept(form))
+}
+
+/// Bit mask of the $w \ne 0$ where the Walsh transform of the centred labeling is non-zero.
+fn walsh_support(labeling: &[usize]) -> usize {
+    let centred = |a: usize| 2 * labeling[a] as i64 - (labeling.len() as i64 - 1);
+    (1..labeling.len())
+        .filter(|&w| (0..labeling.len()).map(|a| if parity(w & a) == 1 { -centred(a) } else { centred(a) }).sum::<i64>() != 0)
+        .fold(0, |mask, w| mask | 1 << w)
+}
+
+/// Applies a linear map given by its rows (bit masks) to the vector `v`.
+fn apply(rows: &[usize], v: usize) -> usize {
+    rows.iter().enumerate().fold(0, |out, (bit, &row)| out | parity(row & v) << bit)
+}
+
+/// All orderings of $0..k$.
+fn permutations(k: usize) -> Vec<Vec<usize>> {
+    if k == 0 {
+        return vec![Vec::new()];
+    }
+    let mut out = Vec::new();
+    for smaller in permutations(k - 1) {
+        for position in 0..k {
+            let mut p = smaller.clone();
+            p.insert(position, k - 1);
+            out.push(p);
+        }
+    }
+    out
+}
+
+/// Whether adding `new` to `set` keeps any `power` (2 or 3) of its vectors independent.
+fn independent_with(new: usize, set: &[usize], power: u32) -> bool {
+    if new == 0 || set.contains(&new) {
+        return false;
+    }
+    power < 3 || !set.iter().any(|&a| set.contains(&(a ^ new)))
+}
+
+/// Whether `new` is outside the span of `rows`.
+fn independent(rows: &[usize], new: usize) -> bool {
+    // Reducing against a basis sorted by leading bit, highest first, clears every leading bit.
+    let mut basis: Vec<usize> = Vec::with_capacity(rows.len() + 1);
+    for &v in rows.iter().chain([&new]) {
+        let reduced = basis.iter().fold(v, |v, &b| v.min(v ^ b));
+        if reduced == 0 {
+            return false;
+        }
+        basis.push(reduced);
+        basis.sort_unstable_by(|a, b| b.cmp(a));
+    }
+    true
+}
+
+fn parity(v: usize) -> usize {
+    v.count_ones() as usize & 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validator::{classify, is_multimagic};
+
+    #[test]
+    fn bimagic_squares() {
+        let mut rng = Lcg::new_with_seed(1);
+        for n in [8, 16, 32] {
+            let grid = generate(n, 2, &mut rng).unwrap_or_else(|e| panic!("order {}: {}", n, e));
+            let properties = classify(&grid, n);
+            assert!(properties.magic && properties.bimagic, "order {}", n);
+        }
+    }
+
+    #[test]
+    fn trimagic_square() {
+        let mut rng = Lcg::new_with_seed(2);
+        let grid = generate(32, 3, &mut rng).unwrap();
+        assert!(classify(&grid, 32).trimagic);
+    }
+
+    #[test]
+    fn ordinary_squares_are_not_bimagic() {
+        let lo_shu = [2, 7, 6, 9, 5, 1, 4, 3, 8];
+        let properties = classify(&lo_shu, 3);
+        assert!(properties.magic && !properties.bimagic && !properties.trimagic);
+        assert!(!is_multimagic(&[1, 2, 3, 4], 2, 2));
+    }
+
+    #[test]
+    fn rejects_unsupported_orders() {
+        let mut rng = Lcg::new_with_seed(3);
+        assert_eq!(generate(4, 2, &mut rng), Err(MultimagicError::UnsupportedOrder { n: 4, power: 2 }));
+        assert_eq!(generate(12, 3, &mut rng), Err(MultimagicError::UnsupportedOrder { n: 12, power: 3 }));
+        assert_eq!(generate(16, 3, &mut rng), Err(MultimagicError::UnsupportedOrder { n: 16, power: 3 }));
+        assert_eq!(generate(8, 4, &mut rng), Err(MultimagicError::UnsupportedPower(4)));
+    }
+}
@@ -0,0 +1,151 @@
+//! Multiplicative magic squares: every row, column and main diagonal has the same product.
+//!
+//! They are built from additive squares used as exponents. If $M$ is a magic square with line
+//! sum $S$, the square $b^{M_{r,c}}$ has line product $b^S$; with two squares $M$, $N$ and
+//! coprime bases $a$, $b$, the square $a^{M_{r,c}} b^{N_{r,c}}$ has product $a^S b^T$. The
+//! entries are distinct when the exponents of $M$ are. Products grow quickly, so entries are
+//! `u128` and every multiplication is checked: base 2 reaches order 6, base 3 order 5.
+
+use std::fmt;
+
+use crate::generator;
+use crate::rng::Lcg;
+use crate::validator;
+
+/// Errors returned by the multiplicative square constructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MultiplicativeError {
+    /// No additive magic square of this order exists to use as exponents.
+    UnsupportedOrder(usize),
+    /// Bases must be at least 2.
+    InvalidBase(u128),
+    /// Two bases must be coprime for the entries to stay distinct.
+    SharedFactor { a: u128, b: u128 },
+    /// The exponent grid is not an $n \times n$ square with a common line sum.
+    NotMagic,
+    /// The line product does not fit in a `u128`.
+    Overflow { n: usize },
+}
+
+impl fmt::Display for MultiplicativeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedOrder(n) => write!(f, "Order {} is not supported. There is no magic square of order 2.", n),
+            Self::InvalidBase(base) => write!(f, "Base {} is too small. Bases must be at least 2.", base),
+            Self::SharedFactor { a, b } => write!(f, "Bases {} and {} share a factor. Use coprime bases.", a, b),
+            Self::NotMagic => write!(f, "The exponent grid is not a square with a common line sum."),
+            Self::Overflow { n } => write!(f, "The line product of order {} overflows 128 bits. Use a smaller order or base.", n),
+        }
+    }
+}
+
+impl std::error::Error for MultiplicativeError {}
+
+/// Generates a multiplicative magic square of order `n` whose entries are powers of `base`,
+/// using a square from the additive generators as exponents.
+pub fn generate(n: usize, base: u128, rng: &mut Lcg) -> Result<Vec<u128>, MultiplicativeError> {
+    let exponents = additive(n, rng)?;
+    from_additive(&exponents, n, base)
+}
+
+/// Generates a multiplicative magic square of order `n` whose entries are $a^x b^y$, with the
+/// exponents taken from two independently generated additive squares.
+pub fn generate_with_bases(n: usize, (a, b): (u128, u128), rng: &mut Lcg) -> Result<Vec<u128>, MultiplicativeError> {
+    let first = additive(n, rng)?;
+    let second = additive(n, rng)?;
+    combine(&first, &second, n, (a, b))
+}
+
+/// Raises `base` to each entry of an additive magic square. Any grid whose rows, columns and
+/// main diagonals share a sum works, though the entries are only distinct if the exponents are.
+pub fn from_additive(exponents: &[u32], n: usize, base: u128) -> Result<Vec<u128>, MultiplicativeError> {
+    if base < 2 {
+        return Err(MultiplicativeError::InvalidBase(base));
+    }
+    let sum = validator::common_line_sum(exponents, n).ok_or(MultiplicativeError::NotMagic)?;
+    checked_pow(base, sum).ok_or(MultiplicativeError::Overflow { n })?;
+
+    // Every entry divides the line product, so none of these can overflow.
+    Ok(exponents.iter().map(|&e| base.pow(e)).collect())
+}
+
+/// Combines two additive magic squares into $a^{M_{r,c}} b^{N_{r,c}}$ for coprime bases.
+pub fn combine(first: &[u32], second: &[u32], n: usize, (a, b): (u128, u128)) -> Result<Vec<u128>, MultiplicativeError> {
+    let left = from_additive(first, n, a)?;
+    let right = from_additive(second, n, b)?;
+    if gcd(a, b) != 1 {
+        return Err(MultiplicativeError::SharedFactor { a, b });
+    }
+    let product = left[..n].iter().chain(&right[..n]).try_fold(1u128, |acc, &v| acc.checked_mul(v));
+    if product.is_none() {
+        return Err(MultiplicativeError::Overflow { n });
+    }
+
+    Ok(left.iter().zip(&right).map(|(&x, &y)| x * y).collect())
+}
+
+/// An additive magic square of order `n` from the generators, to use as exponents.
+fn additive(n: usize, rng: &mut Lcg) -> Result<Vec<u32>, MultiplicativeError> {
+    if n == 0 || n == 2 {
+        return Err(MultiplicativeError::UnsupportedOrder(n));
+    }
+    Ok(generator::create(n, rng).generate(n))
+}
+
+fn checked_pow(base: u128, exponent: u64) -> Option<u128> {
+    (0..exponent).try_fold(1u128, |acc, _| acc.checked_mul(base))
+}
+
+fn gcd(a: u128, b: u128) -> u128 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog;
+    use crate::validator::{common_product, is_multiplicative_magic};
+
+    #[test]
+    fn powers_of_one_base() {
+        let mut rng = Lcg::new_with_seed(1);
+        for (n, base) in [(3, 2), (4, 2), (5, 3), (6, 2)] {
+            let grid = generate(n, base, &mut rng).unwrap_or_else(|e| panic!("order {}: {}", n, e));
+            assert!(is_multiplicative_magic(&grid, n), "order {}", n);
+            assert_eq!(common_product(&grid, n), Some(base.pow(validator::magic_constant(n) as u32)));
+        }
+    }
+
+    #[test]
+    fn powers_of_two_bases() {
+        let mut rng = Lcg::new_with_seed(2);
+        let grid = generate_with_bases(3, (2, 3), &mut rng).unwrap();
+        assert!(is_multiplicative_magic(&grid, 3));
+        assert_eq!(common_product(&grid, 3), Some(6u128.pow(15)));
+
+        let lo_shu = catalog::LO_SHU.grid;
+        let grid = combine(lo_shu, catalog::DURER.grid, 3, (2, 5));
+        assert_eq!(grid, Err(MultiplicativeError::NotMagic));
+        assert_eq!(combine(lo_shu, lo_shu, 3, (2, 4)), Err(MultiplicativeError::SharedFactor { a: 2, b: 4 }));
+    }
+
+    #[test]
+    fn reports_overflow() {
+        let mut rng = Lcg::new_with_seed(3);
+        assert_eq!(generate(7, 2, &mut rng), Err(MultiplicativeError::Overflow { n: 7 }));
+        assert_eq!(generate(5, 5, &mut rng), Err(MultiplicativeError::Overflow { n: 5 }));
+        assert_eq!(generate_with_bases(5, (2, 3), &mut rng), Err(MultiplicativeError::Overflow { n: 5 }));
+        assert_eq!(common_product(&[u128::MAX; 4], 2), None);
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        let mut rng = Lcg::new_with_seed(4);
+        assert_eq!(generate(2, 2, &mut rng), Err(MultiplicativeError::UnsupportedOrder(2)));
+        assert_eq!(generate(3, 1, &mut rng), Err(MultiplicativeError::InvalidBase(1)));
+
+        // Equal entries give equal products but do not make a multiplicative magic square.
+        assert_eq!(common_product(&[6; 9], 3), Some(216));
+        assert!(!is_multiplicative_magic(&[6; 9], 3));
+    }
+}
@@ -0,0 +1,158 @@
+//! Orbit explorer: every square reachable from a magic square by known magic-preserving moves.
+//!
+//! The group used here is generated by the 8 symmetries of the square, the symmetric row and
+//! column pair permutations of [`Transform::swap_pairs`] and [`Transform::swap_within_pair`],
+//! and the cyclic row and column shifts. Pair permutations do not always keep a square
+//! pandiagonal, so shifts are only applied to the squares of the orbit that still are.
+//!
+//! Squares are identified by their canonical hash, so the orbit is reported as a list of
+//! essentially different squares (each one standing for all of its rotations and reflections).
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::canonical;
+use crate::transform::Transform;
+use crate::validator;
+
+/// Default cap on the number of essentially different squares explored.
+pub const DEFAULT_LIMIT: usize = 100_000;
+
+/// The orbit of a square.
+#[derive(Debug, Clone)]
+pub struct Orbit {
+    /// The order of the squares.
+    pub n: usize,
+    /// One representative per class, in Frénicle standard form, in discovery order.
+    pub squares: Vec<Vec<u32>>,
+    /// Number of squares when rotations and reflections are counted separately.
+    pub raw_size: usize,
+    /// Whether the starting square is pandiagonal, so cyclic shifts were explored.
+    pub pandiagonal: bool,
+    /// `false` when exploration stopped at the limit before the orbit was exhausted.
+    pub complete: bool,
+}
+
+impl Orbit {
+    /// Number of essentially different squares in the orbit.
+    pub fn size(&self) -> usize {
+        self.squares.len()
+    }
+}
+
+/// Pair permutations generating every symmetric permutation of order `n`, apart from the
+/// symmetries of the square which are folded in by canonicalization.
+pub fn generators(n: usize) -> Vec<Transform> {
+    let pairs = n / 2;
+    let mut gens = Vec::new();
+    // Adjacent pair swaps and a single in-pair swap generate all symmetric permutations.
+    for i in 1..pairs {
+        gens.push(Transform::swap_pairs(n, i - 1, i));
+    }
+    if pairs > 0 {
+        gens.push(Transform::swap_within_pair(n, 0));
+    }
+    gens
+}
+
+/// Cyclic row and column shifts, which only preserve pandiagonal squares.
+pub fn shift_generators(n: usize) -> Vec<Transform> {
+    if n < 2 {
+        return Vec::new();
+    }
+    vec![Transform::cyclic_shift(n, 1, 0), Transform::cyclic_shift(n, 0, 1)]
+}
+
+/// Explores the orbit of `grid` breadth first, stopping after `limit` distinct squares.
+pub fn orbit(grid: &[u32], n: usize, limit: usize) -> Orbit {
+    let pandiagonal = validator::is_pandiagonal(grid, n);
+    let gens = generators(n);
+    let shifts = shift_generators(n);
+
+    let start = canonical::canonicalize(grid, n);
+    let mut seen = HashSet::new();
+    seen.insert(canonical::canonical_hash(&start, n));
+    let mut queue = VecDeque::from([0]);
+    let mut squares = vec![start];
+    let mut complete = true;
+
+    'explore: while let Some(idx) = queue.pop_front() {
+        let shifts: &[Transform] = if pandiagonal && validator::is_pandiagonal(&squares[idx], n) {
+            &shifts
+        } else {
+            &[]
+        };
+        for g in gens.iter().chain(shifts) {
+            let next = canonical::canonicalize(&g.apply(&squares[idx]), n);
+            if seen.insert(canonical::canonical_hash(&next, n)) {
+                if squares.len() == limit {
+                    complete = false;
+                    break 'explore;
+                }
+                queue.push_back(squares.len());
+                squares.push(next);
+            }
+        }
+    }
+
+    let raw_size = squares.iter().map(|sq| distinct_symmetries(sq, n)).sum();
+    Orbit { n, squares, raw_size, pandiagonal, complete }
+}
+
+/// Number of different grids among the 8 symmetries of `grid`.
+fn distinct_symmetries(grid: &[u32], n: usize) -> usize {
+    let variants: HashSet<Vec<u32>> = canonical::symmetries(grid, n).into_iter().collect();
+    variants.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator;
+    use crate::rng::Lcg;
+
+    #[test]
+    fn lo_shu_is_alone() {
+        let result = orbit(&[4, 9, 2, 3, 5, 7, 8, 1, 6], 3, DEFAULT_LIMIT);
+        assert_eq!(result.size(), 1);
+        assert_eq!(result.raw_size, 8);
+        assert!(result.complete);
+    }
+
+    #[test]
+    fn order_4_pair_permutations() {
+        // The symmetric permutations of order 4 form a group of 8 elements, half of which
+        // are already symmetries of the square.
+        let mut lcg = Lcg::new_with_seed(7);
+        let sq = generator::create(4, &mut lcg).generate(4);
+        let result = orbit(&sq, 4, DEFAULT_LIMIT);
+        assert!(result.complete);
+        assert!(result.squares.iter().all(|s| validator::check_magic_properties(s, 4)));
+        assert_eq!(result.raw_size, result.size() * 8);
+    }
+
+    #[test]
+    fn pandiagonal_squares_include_shifts() {
+        let sq = [
+            1, 15, 24, 8, 17,
+            23, 7, 16, 5, 14,
+            20, 4, 13, 22, 6,
+            12, 21, 10, 19, 3,
+            9, 18, 2, 11, 25,
+        ];
+        let result = orbit(&sq, 5, DEFAULT_LIMIT);
+        assert!(result.pandiagonal);
+        assert!(result.complete);
+        assert!(result.size() > orbit(&[4, 9, 2, 3, 5, 7, 8, 1, 6], 3, DEFAULT_LIMIT).size());
+        assert!(result.squares.iter().all(|s| validator::check_magic_properties(s, 5)));
+        assert!(result.squares.iter().any(|s| !validator::is_pandiagonal(s, 5)));
+    }
+
+    #[test]
+    fn respects_limit() {
+        let mut lcg = Lcg::new_with_seed(11);
+        let sq = generator::create(9, &mut lcg).generate(9);
+        let result = orbit(&sq, 9, 10);
+        assert_eq!(result.size(), 10);
+        assert!(!result.complete);
+    }
+}
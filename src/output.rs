@@ -0,0 +1,402 @@
+//! Writing squares for other tools and documents.
+//!
+//! Every format works on the flat row-major grid of the generators:
+//!
+//! - **Text**: right-aligned columns, as printed by the CLI.
+//! - **CSV** and **TSV**: one row per line.
+//! - **JSON**: an object with `n`, `method` and `seed` (when known), `magic_constant` and the
+//!   `grid` as an array of rows. Several squares make an array of such objects.
+//! - **NDJSON**: the same objects, one per line.
+//! - **Markdown**: a table with an empty header row, since squares have no column names.
+//! - **LaTeX**: a ruled `tabular`.
+//! - **SVG**: a standalone image with the default [`SvgOptions`]; see [`svg::render`] for
+//!   highlighted lines and magic lines.
+//! - **PPM** and **PNG**: heatmaps about [`heatmap::DEFAULT_SIDE`] pixels wide with the
+//!   default gradient; see [`heatmap::render`] for other gradients and scales.
+//! - **Binary**: per square, the order as a little-endian `u32`, the cell width in bytes
+//!   (1, 2 or 4, the smallest that holds the largest value), then the cells in little-endian
+//!   order. Read it back with [`read_binary`].
+//! - **MSQ**: the `.msq` format of [`msq`], with a header recording the method and seed, a
+//!   verified flag and a CRC.
+//!
+//! Text-based formats separate several squares with a blank line (for SVG, that makes one
+//! document per square); binary records and `.msq` files are simply concatenated. Images other than SVG hold
+//! a single square: see [`Format::is_image`].
+
+use std::fmt;
+use std::fmt::Write;
+use std::io;
+use std::str::FromStr;
+
+use crate::heatmap::{self, Gradient, ImageFormat};
+use crate::msq;
+use crate::svg::{self, SvgOptions};
+use crate::validator;
+
+/// The output formats of [`format_squares`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Csv,
+    Tsv,
+    Json,
+    Ndjson,
+    Markdown,
+    Latex,
+    Svg,
+    Ppm,
+    Png,
+    Binary,
+    Msq,
+}
+
+impl Format {
+    /// Every format, in the order they are listed to users.
+    pub const ALL: [Format; 12] = [
+        Format::Text,
+        Format::Csv,
+        Format::Tsv,
+        Format::Json,
+        Format::Ndjson,
+        Format::Markdown,
+        Format::Latex,
+        Format::Svg,
+        Format::Ppm,
+        Format::Png,
+        Format::Binary,
+        Format::Msq,
+    ];
+
+    /// The name accepted by [`Format::from_str`].
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Text => "text",
+            Format::Csv => "csv",
+            Format::Tsv => "tsv",
+            Format::Json => "json",
+            Format::Ndjson => "ndjson",
+            Format::Markdown => "markdown",
+            Format::Latex => "latex",
+            Format::Svg => "svg",
+            Format::Ppm => "ppm",
+            Format::Png => "png",
+            Format::Binary => "binary",
+            Format::Msq => "msq",
+        }
+    }
+
+    /// Whether the format is an image, which holds one square.
+    pub fn is_image(self) -> bool {
+        matches!(self, Format::Svg | Format::Ppm | Format::Png)
+    }
+}
+
+impl FromStr for Format {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "md" => Ok(Format::Markdown),
+            "tex" => Ok(Format::Latex),
+            _ => Format::ALL.into_iter().find(|f| f.name() == s).ok_or(()),
+        }
+    }
+}
+
+/// How a square was made, recorded by the JSON formats.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// The construction, e.g. `latin` or `franklin`.
+    pub method: Option<String>,
+    /// The seed that reproduces the square.
+    pub seed: Option<u64>,
+}
+
+/// Errors returned by [`read_binary`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryError {
+    /// The data ends inside the record starting at `offset`.
+    Truncated { offset: usize },
+    /// The record starting at `offset` has a cell width other than 1, 2 or 4.
+    InvalidWidth { offset: usize, width: u8 },
+}
+
+impl fmt::Display for BinaryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated { offset } => write!(f, "The square at byte {} is cut short.", offset),
+            Self::InvalidWidth { offset, width } => {
+                write!(f, "The square at byte {} has a cell width of {} bytes. Expected 1, 2 or 4.", offset, width)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BinaryError {}
+
+/// Formats a single square.
+///
+/// # Panics
+///
+/// For the image and `.msq` formats, if the grid does not hold $n^2$ values, and for `.msq`
+/// if the method name is longer than 255 bytes.
+pub fn format_square(grid: &[u32], n: usize, format: Format, metadata: &Metadata) -> Vec<u8> {
+    match format {
+        Format::Binary => binary(grid, n),
+        Format::Msq => {
+            let verified = grid.len() == n * n && validator::check_magic_properties(grid, n);
+            msq::to_bytes(grid, n, metadata, verified).expect("an n x n grid and a short method name")
+        }
+        Format::Json => json(grid, n, metadata, true).into_bytes(),
+        Format::Ndjson => (json(grid, n, metadata, false) + "\n").into_bytes(),
+        Format::Text => text(grid, n).into_bytes(),
+        Format::Csv => delimited(grid, n, ",").into_bytes(),
+        Format::Tsv => delimited(grid, n, "\t").into_bytes(),
+        Format::Markdown => markdown(grid, n).into_bytes(),
+        Format::Latex => latex(grid, n).into_bytes(),
+        Format::Svg => svg::render(grid, n, &SvgOptions::default()).expect("an n x n grid").into_bytes(),
+        Format::Ppm | Format::Png => {
+            let image = if format == Format::Ppm { ImageFormat::Ppm } else { ImageFormat::Png };
+            let scale = heatmap::scale_for(n, heatmap::DEFAULT_SIDE);
+            heatmap::render(grid, n, &Gradient::default(), scale, image).expect("an n x n grid within the size limit")
+        }
+    }
+}
+
+/// Formats several squares of order `n` made the same way. PPM and PNG images are
+/// concatenated, which only makes sense for a single square.
+pub fn format_squares(squares: &[Vec<u32>], n: usize, format: Format, metadata: &Metadata) -> Vec<u8> {
+    let mut writer = SquareWriter::new(Vec::new(), n, format, metadata.clone(), squares.len());
+    for grid in squares {
+        writer.write_square(grid).expect("writing to a Vec");
+    }
+    writer.finish().expect("writing to a Vec")
+}
+
+/// Writes squares one at a time in the layout of [`format_squares`], so that a batch never
+/// has to be held in memory.
+///
+/// Only JSON needs the number of squares up front: one square is a bare object, several are
+/// an array whose brackets are written before the first and after the last.
+pub struct SquareWriter<W: io::Write> {
+    out: W,
+    n: usize,
+    format: Format,
+    metadata: Metadata,
+    count: usize,
+    written: usize,
+}
+
+impl<W: io::Write> SquareWriter<W> {
+    /// A writer for `count` squares of order `n`.
+    pub fn new(out: W, n: usize, format: Format, metadata: Metadata, count: usize) -> Self {
+        Self { out, n, format, metadata, count, written: 0 }
+    }
+
+    /// Writes the next square.
+    pub fn write_square(&mut self, grid: &[u32]) -> io::Result<()> {
+        let array = self.format == Format::Json && self.count != 1;
+        let separator: &[u8] = match self.format {
+            _ if array && self.written == 0 => b"[\n  ",
+            _ if array => b",\n  ",
+            Format::Binary | Format::Msq | Format::Ndjson | Format::Ppm | Format::Png => b"",
+            _ if self.written == 0 => b"",
+            _ => b"\n",
+        };
+        self.out.write_all(separator)?;
+        if array {
+            self.out.write_all(json(grid, self.n, &self.metadata, false).as_bytes())?;
+        } else {
+            self.out.write_all(&format_square(grid, self.n, self.format, &self.metadata))?;
+        }
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Closes the JSON array, flushes and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.format == Format::Json && self.count != 1 {
+            let open: &[u8] = if self.written == 0 { b"[\n  " } else { b"" };
+            self.out.write_all(open)?;
+            self.out.write_all(b"\n]\n")?;
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Right-aligned columns, one space wider than the largest value.
+pub fn text(grid: &[u32], n: usize) -> String {
+    let width = grid.iter().max().map_or(0, |max| max.to_string().len()) + 1;
+    let mut out = String::new();
+    for row in grid.chunks(n.max(1)) {
+        for val in row {
+            let _ = write!(out, "{:width$}", val, width = width);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// One row per line, values joined by `separator`.
+fn delimited(grid: &[u32], n: usize, separator: &str) -> String {
+    grid.chunks(n.max(1)).map(|row| join(row, separator) + "\n").collect()
+}
+
+fn markdown(grid: &[u32], n: usize) -> String {
+    let mut out = format!("|{}\n|{}\n", "   |".repeat(n), "--:|".repeat(n));
+    for row in grid.chunks(n.max(1)) {
+        out += &format!("| {} |\n", join(row, " | "));
+    }
+    out
+}
+
+fn latex(grid: &[u32], n: usize) -> String {
+    let mut out = format!("\\begin{{tabular}}{{|{}}}\n\\hline\n", "r|".repeat(n));
+    for row in grid.chunks(n.max(1)) {
+        out += &format!("{} \\\\ \\hline\n", join(row, " & "));
+    }
+    out + "\\end{tabular}\n"
+}
+
+/// A JSON object, spread over one line per row when `pretty`.
+fn json(grid: &[u32], n: usize, metadata: &Metadata, pretty: bool) -> String {
+    let mut fields = vec![format!("\"n\": {}", n)];
+    if let Some(method) = &metadata.method {
+        fields.push(format!("\"method\": \"{}\"", method.replace('\\', "\\\\").replace('"', "\\\"")));
+    }
+    if let Some(seed) = metadata.seed {
+        fields.push(format!("\"seed\": {}", seed));
+    }
+    fields.push(format!("\"magic_constant\": {}", validator::magic_constant(n)));
+
+    let rows: Vec<String> = grid.chunks(n.max(1)).map(|row| format!("[{}]", join(row, ", "))).collect();
+    if pretty {
+        fields.push(format!("\"grid\": [\n    {}\n  ]", rows.join(",\n    ")));
+        format!("{{\n  {}\n}}\n", fields.join(",\n  "))
+    } else {
+        fields.push(format!("\"grid\": [{}]", rows.join(", ")));
+        format!("{{{}}}", fields.join(", "))
+    }
+}
+
+fn join(row: &[u32], separator: &str) -> String {
+    row.iter().map(u32::to_string).collect::<Vec<_>>().join(separator)
+}
+
+/// The compact binary record of a square.
+pub fn binary(grid: &[u32], n: usize) -> Vec<u8> {
+    let width: u8 = match grid.iter().max().copied().unwrap_or(0) {
+        0..=0xFF => 1,
+        0x100..=0xFFFF => 2,
+        _ => 4,
+    };
+    let mut out = Vec::with_capacity(5 + grid.len() * width as usize);
+    out.extend_from_slice(&(n as u32).to_le_bytes());
+    out.push(width);
+    for &v in grid {
+        out.extend_from_slice(&v.to_le_bytes()[..width as usize]);
+    }
+    out
+}
+
+/// Reads the squares of concatenated binary records, as `(n, grid)` pairs.
+pub fn read_binary(mut bytes: &[u8]) -> Result<Vec<(usize, Vec<u32>)>, BinaryError> {
+    let mut squares = Vec::new();
+    let mut offset = 0;
+    while !bytes.is_empty() {
+        let truncated = BinaryError::Truncated { offset };
+        let (header, rest) = bytes.split_at_checked(5).ok_or(truncated)?;
+        let n = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let width = header[4];
+        if !matches!(width, 1 | 2 | 4) {
+            return Err(BinaryError::InvalidWidth { offset, width });
+        }
+
+        let size = n.checked_mul(n).and_then(|cells| cells.checked_mul(width as usize)).ok_or(truncated)?;
+        let (cells, rest) = rest.split_at_checked(size).ok_or(truncated)?;
+        let grid = cells
+            .chunks(width as usize)
+            .map(|cell| {
+                let mut le = [0; 4];
+                le[..cell.len()].copy_from_slice(cell);
+                u32::from_le_bytes(le)
+            })
+            .collect();
+        squares.push((n, grid));
+        offset += 5 + size;
+        bytes = rest;
+    }
+    Ok(squares)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog;
+    use crate::input;
+
+    const LO_SHU: &[u32] = catalog::LO_SHU.grid;
+
+    #[test]
+    fn formats_text_tables() {
+        let none = Metadata::default();
+        let text = |format| String::from_utf8(format_square(LO_SHU, 3, format, &none)).unwrap();
+        assert_eq!(text(Format::Text), " 4 9 2\n 3 5 7\n 8 1 6\n");
+        assert_eq!(text(Format::Csv), "4,9,2\n3,5,7\n8,1,6\n");
+        assert_eq!(text(Format::Tsv), "4\t9\t2\n3\t5\t7\n8\t1\t6\n");
+        assert_eq!(text(Format::Markdown), "|   |   |   |\n|--:|--:|--:|\n| 4 | 9 | 2 |\n| 3 | 5 | 7 |\n| 8 | 1 | 6 |\n");
+        assert_eq!(
+            text(Format::Latex),
+            "\\begin{tabular}{|r|r|r|}\n\\hline\n4 & 9 & 2 \\\\ \\hline\n3 & 5 & 7 \\\\ \\hline\n8 & 1 & 6 \\\\ \\hline\n\\end{tabular}\n"
+        );
+
+        let both = String::from_utf8(format_squares(&[LO_SHU.to_vec(), LO_SHU.to_vec()], 3, Format::Csv, &none)).unwrap();
+        assert_eq!(input::parse(&both).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn formats_json_with_metadata() {
+        let metadata = Metadata { method: Some("latin".to_string()), seed: Some(42) };
+        let single = String::from_utf8(format_square(LO_SHU, 3, Format::Json, &metadata)).unwrap();
+        assert!(single.contains("\"method\": \"latin\",\n  \"seed\": 42,\n  \"magic_constant\": 15"), "{}", single);
+
+        let ndjson = String::from_utf8(format_square(LO_SHU, 3, Format::Ndjson, &Metadata::default())).unwrap();
+        assert_eq!(ndjson, "{\"n\": 3, \"magic_constant\": 15, \"grid\": [[4, 9, 2], [3, 5, 7], [8, 1, 6]]}\n");
+
+        // Every JSON shape reads back as the same squares.
+        let squares = [LO_SHU.to_vec(), LO_SHU.iter().rev().copied().collect()];
+        for format in [Format::Json, Format::Ndjson] {
+            for batch in [&squares[..1], &squares[..]] {
+                let text = String::from_utf8(format_squares(batch, 3, format, &metadata)).unwrap();
+                let read: Vec<Vec<u32>> = input::parse(&text).unwrap().into_iter().map(|s| s.grid).collect();
+                assert_eq!(read, batch, "{}", text);
+            }
+        }
+    }
+
+    #[test]
+    fn binary_round_trips() {
+        let big: Vec<u32> = (1..=400).collect();
+        let huge = vec![70_000; 4];
+        for (grid, n, width) in [(LO_SHU.to_vec(), 3, 1), (big, 20, 2), (huge, 2, 4)] {
+            let bytes = binary(&grid, n);
+            assert_eq!((bytes.len(), bytes[4]), (5 + n * n * width, width as u8));
+            assert_eq!(read_binary(&bytes), Ok(vec![(n, grid)]));
+        }
+
+        let two = format_squares(&[LO_SHU.to_vec(), LO_SHU.to_vec()], 3, Format::Binary, &Metadata::default());
+        assert_eq!(read_binary(&two).unwrap().len(), 2);
+        assert_eq!(read_binary(&two[..20]), Err(BinaryError::Truncated { offset: 14 }));
+        assert_eq!(read_binary(&[1, 0, 0, 0, 3, 0]), Err(BinaryError::InvalidWidth { offset: 0, width: 3 }));
+    }
+
+    #[test]
+    fn parses_format_names() {
+        for format in Format::ALL {
+            assert_eq!(format.name().parse(), Ok(format));
+        }
+        assert_eq!("md".parse(), Ok(Format::Markdown));
+        assert_eq!("xml".parse::<Format>(), Err(()));
+    }
+}
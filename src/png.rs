@@ -0,0 +1,338 @@
+//! A small, self-contained PNG encoder for 8-bit RGB images.
+//!
+//! Each row is filtered with whichever of the None, Sub, Up and Paeth filters gives the
+//! smallest sum of absolute differences, the usual heuristic. The filtered rows are
+//! compressed as a single zlib stream of fixed-Huffman deflate blocks, with LZ77 matches
+//! found through hash chains. This is far from the best compression, but the repetitive
+//! patterns of generated squares shrink well, and it needs no dependency.
+
+use crate::checksum::{adler32, Crc32};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+/// Encodes `width` x `height` RGB pixels (3 bytes each, row-major) as a PNG file.
+///
+/// # Panics
+///
+/// If `pixels` does not hold `3 * width * height` bytes.
+pub fn encode_rgb(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+    let stride = 3 * width as usize;
+    assert_eq!(pixels.len(), stride * height as usize, "expected {}x{} RGB pixels", width, height);
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, colour type 2 (RGB), deflate, adaptive filtering, no interlace.
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut png = SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib(&filter(pixels, stride)));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    out.extend_from_slice(&Crc32::new().update(kind).update(data).finish().to_be_bytes());
+}
+
+/// Prefixes each row with its filter type and filters it, 3 bytes per pixel.
+fn filter(pixels: &[u8], stride: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(pixels.len() + pixels.len() / stride.max(1));
+    let zero = vec![0; stride];
+    let mut candidate = vec![0; stride];
+    let mut best = vec![0; stride];
+
+    for (r, row) in pixels.chunks(stride.max(1)).enumerate() {
+        let above = if r == 0 { &zero[..] } else { &pixels[(r - 1) * stride..r * stride] };
+        let mut best_type = 0;
+        let mut best_cost = u64::MAX;
+        for filter_type in 0..=4u8 {
+            if filter_type == 3 {
+                continue; // Average rarely wins on these images.
+            }
+            for i in 0..stride {
+                let left = if i >= 3 { row[i - 3] } else { 0 };
+                let up_left = if i >= 3 { above[i - 3] } else { 0 };
+                let predicted = match filter_type {
+                    0 => 0,
+                    1 => left,
+                    2 => above[i],
+                    _ => paeth(left, above[i], up_left),
+                };
+                candidate[i] = row[i].wrapping_sub(predicted);
+            }
+            let cost = candidate.iter().map(|&b| (b as i8).unsigned_abs() as u64).sum();
+            if cost < best_cost {
+                best_cost = cost;
+                best_type = filter_type;
+                std::mem::swap(&mut best, &mut candidate);
+            }
+        }
+        out.push(best_type);
+        out.extend_from_slice(&best);
+    }
+    out
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Wraps compressed data in a zlib header and Adler-32 trailer.
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x9C];
+    out.extend_from_slice(&deflate(data));
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+const WINDOW: usize = 1 << 15;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// Candidates tried in each hash chain before settling for the best match so far.
+const MAX_CHAIN: usize = 32;
+const HASH_BITS: u32 = 15;
+
+/// Base lengths of the length codes 257..=285, and their extra bits.
+const LENGTH_BASE: [u16; 29] =
+    [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+/// Base distances of the distance codes 0..=29, and their extra bits.
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097,
+    6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+/// Compresses `data` as one final deflate block with the fixed Huffman codes.
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut bits = BitWriter::default();
+    bits.write(1, 1); // BFINAL
+    bits.write(1, 2); // BTYPE = fixed Huffman
+
+    let hash = |i: usize| {
+        let v = (data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32;
+        (v.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+    };
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; WINDOW];
+    let insert = |i: usize, head: &mut Vec<usize>, prev: &mut Vec<usize>| {
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(i);
+            prev[i % WINDOW] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let (mut best_len, mut best_dist) = (0, 0);
+        if i + MIN_MATCH <= data.len() {
+            let max_len = MAX_MATCH.min(data.len() - i);
+            let mut candidate = head[hash(i)];
+            let mut tries = 0;
+            while candidate != usize::MAX && i - candidate <= WINDOW && tries < MAX_CHAIN {
+                let len = data[candidate..].iter().zip(&data[i..i + max_len]).take_while(|(a, b)| a == b).count();
+                if len > best_len {
+                    (best_len, best_dist) = (len, i - candidate);
+                    if len == max_len {
+                        break;
+                    }
+                }
+                let next = prev[candidate % WINDOW];
+                // Entries older than the window have been overwritten by newer positions.
+                if next == usize::MAX || next >= candidate {
+                    break;
+                }
+                candidate = next;
+                tries += 1;
+            }
+        }
+
+        if best_len >= MIN_MATCH {
+            bits.length(best_len);
+            bits.distance(best_dist);
+            for j in i..i + best_len {
+                insert(j, &mut head, &mut prev);
+            }
+            i += best_len;
+        } else {
+            bits.literal(data[i] as u16);
+            insert(i, &mut head, &mut prev);
+            i += 1;
+        }
+    }
+    bits.literal(256); // end of block
+    bits.finish()
+}
+
+/// Writes bits least-significant first, as deflate packs them.
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, bits: u32) {
+        self.buffer |= (value as u64) << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, which deflate stores most-significant bit first.
+    fn code(&mut self, code: u32, bits: u32) {
+        self.write(code.reverse_bits() >> (32 - bits), bits);
+    }
+
+    /// A literal byte or the end-of-block symbol, with the fixed literal/length code.
+    fn literal(&mut self, symbol: u16) {
+        let symbol = symbol as u32;
+        match symbol {
+            0..=143 => self.code(0x30 + symbol, 8),
+            144..=255 => self.code(0x190 + symbol - 144, 9),
+            256..=279 => self.code(symbol - 256, 7),
+            _ => self.code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    fn length(&mut self, len: usize) {
+        let index = LENGTH_BASE.iter().rposition(|&base| base as usize <= len).expect("length is at least 3");
+        self.literal(257 + index as u16);
+        self.write((len - LENGTH_BASE[index] as usize) as u32, LENGTH_EXTRA[index] as u32);
+    }
+
+    fn distance(&mut self, dist: usize) {
+        let index = DIST_BASE.iter().rposition(|&base| base as usize <= dist).expect("distance is at least 1");
+        self.code(index as u32, 5);
+        self.write((dist - DIST_BASE[index] as usize) as u32, DIST_EXTRA[index] as u32);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.buffer as u8);
+        }
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A minimal inflater for fixed-Huffman blocks, enough to check the encoder's output.
+    fn inflate_fixed(data: &[u8]) -> Vec<u8> {
+        let mut pos = 0;
+        let mut bit = |n: u32| -> u32 {
+            let mut v = 0;
+            for k in 0..n {
+                v |= ((data[pos / 8] >> (pos % 8)) as u32 & 1) << k;
+                pos += 1;
+            }
+            v
+        };
+        assert_eq!((bit(1), bit(2)), (1, 1), "one final fixed block");
+
+        let mut out: Vec<u8> = Vec::new();
+        loop {
+            // Read the literal/length code MSB-first, growing from 7 to 9 bits.
+            let mut code = 0;
+            let mut symbol = None;
+            for len in 1..=9 {
+                code = code << 1 | bit(1);
+                symbol = match (len, code) {
+                    (7, 0..=0x17) => Some(code + 256),
+                    (8, 0x30..=0xBF) => Some(code - 0x30),
+                    (8, 0xC0..=0xC7) => Some(code - 0xC0 + 280),
+                    (9, 0x190..=0x1FF) => Some(code - 0x190 + 144),
+                    _ => None,
+                };
+                if symbol.is_some() {
+                    break;
+                }
+            }
+            match symbol.expect("valid code") {
+                s @ 0..=255 => out.push(s as u8),
+                256 => return out,
+                s => {
+                    let i = (s - 257) as usize;
+                    let len = LENGTH_BASE[i] as usize + bit(LENGTH_EXTRA[i] as u32) as usize;
+                    let d = (0..5).fold(0, |acc, _| acc << 1 | bit(1)) as usize;
+                    let dist = DIST_BASE[d] as usize + bit(DIST_EXTRA[d] as u32) as usize;
+                    for _ in 0..len {
+                        out.push(out[out.len() - dist]);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn deflate_round_trips() {
+        let repetitive: Vec<u8> = (0..5000u32).map(|i| (i % 7 * 31) as u8).collect();
+        let noisy: Vec<u8> = (0..5000u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect();
+        for data in [&b""[..], b"a", b"abcabcabcabcabc", &repetitive, &noisy, &[0; 70_000]] {
+            assert_eq!(inflate_fixed(&deflate(data)), data);
+        }
+        assert!(deflate(&repetitive).len() < 200);
+    }
+
+    #[test]
+    fn writes_png_chunks() {
+        let pixels: Vec<u8> = (0..4 * 3 * 3).map(|i| (i * 7) as u8).collect();
+        let png = encode_rgb(4, 3, &pixels);
+        assert_eq!(png[..8], SIGNATURE);
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(png[16..29], [0, 0, 0, 4, 0, 0, 0, 3, 8, 2, 0, 0, 0]);
+        assert_eq!(png[29..33], crate::checksum::crc32(&png[12..29]).to_be_bytes());
+        assert_eq!(png[png.len() - 12..], [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]);
+
+        // The image data inflates back to the filtered rows.
+        let idat_len = u32::from_be_bytes(png[33..37].try_into().unwrap()) as usize;
+        let zlib = &png[41..41 + idat_len];
+        let rows = inflate_fixed(&zlib[2..zlib.len() - 4]);
+        assert_eq!(rows, filter(&pixels, 12));
+        assert_eq!(zlib[zlib.len() - 4..], adler32(&rows).to_be_bytes());
+    }
+
+    #[test]
+    fn filters_undo_to_the_pixels() {
+        let pixels: Vec<u8> = (0..5 * 4 * 3).map(|i| (i * i % 251) as u8).collect();
+        let stride = 15;
+        let filtered = filter(&pixels, stride);
+        let mut decoded: Vec<u8> = Vec::new();
+        for (r, row) in filtered.chunks(stride + 1).enumerate() {
+            for i in 0..stride {
+                let left = if i >= 3 { decoded[r * stride + i - 3] } else { 0 };
+                let up = if r > 0 { decoded[(r - 1) * stride + i] } else { 0 };
+                let up_left = if r > 0 && i >= 3 { decoded[(r - 1) * stride + i - 3] } else { 0 };
+                let predicted = match row[0] {
+                    0 => 0,
+                    1 => left,
+                    2 => up,
+                    4 => paeth(left, up, up_left),
+                    other => panic!("unexpected filter {}", other),
+                };
+                decoded.push(row[1 + i].wrapping_add(predicted));
+            }
+        }
+        assert_eq!(decoded, pixels);
+    }
+}
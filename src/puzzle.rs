@@ -0,0 +1,171 @@
+//! Puzzle generator: magic squares with some cells removed and a unique completion.
+//!
+//! A square is generated from a seed, then cells are removed in a random order. A removal is
+//! kept only while the [completion solver](crate::solver) still finds exactly one solution and
+//! the deductions it takes stay within the requested difficulty:
+//! - [`Difficulty::Easy`]: every cell is the last empty cell of some line.
+//! - [`Difficulty::Medium`]: some cells need an elimination (all other values contradict).
+//! - [`Difficulty::Hard`]: the solver has to guess and backtrack.
+
+use std::cmp::Reverse;
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+use crate::generator;
+use crate::rng::Lcg;
+use crate::solver::{self, Deductions};
+
+/// Largest order for which puzzles are generated. Proving uniqueness gets expensive quickly.
+pub const MAX_ORDER: usize = 6;
+
+/// Number of squares tried before settling for the closest difficulty.
+const ATTEMPTS: u64 = 8;
+
+/// How hard a puzzle is, from the deductions needed to solve it.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Difficulty {
+    Easy = 0,
+    Medium = 1,
+    Hard = 2,
+}
+
+impl Difficulty {
+    /// Rates a solve by its hardest kind of deduction.
+    pub fn of(deductions: &Deductions) -> Self {
+        if deductions.guesses > 0 {
+            Difficulty::Hard
+        } else if deductions.eliminations > 0 {
+            Difficulty::Medium
+        } else {
+            Difficulty::Easy
+        }
+    }
+}
+
+/// A puzzle and its unique solution.
+#[derive(Debug, Clone)]
+pub struct Puzzle {
+    /// The order of the square.
+    pub n: usize,
+    /// The flat grid shown to the player, `None` for cells to fill in.
+    pub clues: Vec<Option<u32>>,
+    /// The only completion of the clues.
+    pub solution: Vec<u32>,
+    /// The rated difficulty, which can be below the requested one (see [`make_puzzle`]).
+    pub difficulty: Difficulty,
+    /// The deductions the rating is based on.
+    pub deductions: Deductions,
+}
+
+/// Errors reported by [`make_puzzle`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PuzzleError {
+    /// The order is below 3 or above [`MAX_ORDER`].
+    UnsupportedOrder(usize),
+}
+
+impl fmt::Display for PuzzleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedOrder(n) => {
+                write!(f, "Order {} is not supported. Puzzles work for orders 3 to {}.", n, MAX_ORDER)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PuzzleError {}
+
+/// Generates a puzzle of order `n`. The same seed always gives the same puzzle.
+///
+/// Some squares cannot reach the requested difficulty (every order-3 puzzle is at most
+/// medium, for instance). A few squares are tried, and if none reaches it the hardest puzzle
+/// found is returned, with its actual rating.
+pub fn make_puzzle(n: usize, difficulty: Difficulty, seed: u64) -> Result<Puzzle, PuzzleError> {
+    if !(3..=MAX_ORDER).contains(&n) {
+        return Err(PuzzleError::UnsupportedOrder(n));
+    }
+
+    let mut best: Option<Puzzle> = None;
+    for attempt in 0..ATTEMPTS {
+        let mut lcg = Lcg::new_with_seed(seed.wrapping_add(attempt));
+        let puzzle = carve(n, difficulty, &mut lcg);
+        if puzzle.difficulty == difficulty {
+            return Ok(puzzle);
+        }
+        // Prefer the hardest puzzle, then the one with the fewest clues.
+        let rank = |p: &Puzzle| (p.difficulty, Reverse(p.clue_count()));
+        let better = best.as_ref().is_none_or(|b| rank(&puzzle) > rank(b));
+        if better {
+            best = Some(puzzle);
+        }
+    }
+    Ok(best.expect("at least one attempt"))
+}
+
+impl Puzzle {
+    /// Number of cells given to the player.
+    pub fn clue_count(&self) -> usize {
+        self.clues.iter().filter(|c| c.is_some()).count()
+    }
+}
+
+/// Removes as many cells as possible from a fresh square without exceeding `target`.
+fn carve(n: usize, target: Difficulty, lcg: &mut Lcg) -> Puzzle {
+    let solution = generator::create(n, lcg).generate(n);
+    let mut clues: Vec<Option<u32>> = solution.iter().copied().map(Some).collect();
+
+    let mut order: Vec<usize> = (0..n * n).collect();
+    lcg.shuffle(&mut order);
+
+    for cell in order {
+        let removed = clues[cell].take();
+        let keep = solver::count_solutions(&clues, n, 2) == Ok(1)
+            && solver::deduce(&clues, n).is_ok_and(|d| Difficulty::of(&d) <= target);
+        if !keep {
+            clues[cell] = removed;
+        }
+    }
+
+    let deductions = solver::deduce(&clues, n).expect("the clues come from a magic square");
+    Puzzle { n, clues, solution, difficulty: Difficulty::of(&deductions), deductions }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn puzzles_have_a_unique_solution() {
+        for n in 3..=5 {
+            let puzzle = make_puzzle(n, Difficulty::Medium, 42).unwrap();
+            assert_eq!(solver::count_solutions(&puzzle.clues, n, 2), Ok(1));
+            assert_eq!(solver::solve(&puzzle.clues, n), Ok(puzzle.solution.clone()));
+            assert!(puzzle.difficulty <= Difficulty::Medium);
+            assert!(puzzle.clue_count() < n * n);
+        }
+    }
+
+    #[test]
+    fn seeds_are_reproducible() {
+        let a = make_puzzle(4, Difficulty::Hard, 7).unwrap();
+        let b = make_puzzle(4, Difficulty::Hard, 7).unwrap();
+        assert_eq!(a.clues, b.clues);
+        assert_eq!(a.difficulty, Difficulty::Hard);
+    }
+
+    #[test]
+    fn easy_puzzles_need_only_line_fills() {
+        let puzzle = make_puzzle(4, Difficulty::Easy, 3).unwrap();
+        assert_eq!(puzzle.difficulty, Difficulty::Easy);
+        assert_eq!(puzzle.deductions.line_fills, 16 - puzzle.clue_count());
+    }
+
+    #[test]
+    fn rejects_unsupported_orders() {
+        assert_eq!(make_puzzle(2, Difficulty::Easy, 0).unwrap_err(), PuzzleError::UnsupportedOrder(2));
+        assert!(make_puzzle(MAX_ORDER + 1, Difficulty::Easy, 0).is_err());
+    }
+}
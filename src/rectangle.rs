@@ -0,0 +1,428 @@
+//! Magic rectangles and semi-magic squares.
+//!
+//! A magic rectangle with `rows` rows and `cols` columns holds $1..N$ ($N$ = `rows * cols`)
+//! once each, every row summing to `cols` $(N+1)/2$ and every column to `rows` $(N+1)/2$.
+//! There are no diagonal conditions, so a semi-magic square is a square magic rectangle.
+//! Magic rectangles exist exactly when both sides have the same parity, apart from the
+//! degenerate shapes ($2 \times 2$, and a single row or column longer than one cell).
+//!
+//! Grids use the same flat row-major layout as the square generators: cell `(r, c)` is at
+//! index `r * cols + c`.
+//!
+//! - **Even sides**: rows are paired, and each pair holds complementary values $x$ and $N+1-x$
+//!   in the same column, so every column sum is right by construction. The values are split
+//!   into one group per row pair so that each row of the pair also sums correctly.
+//! - **Odd sides**: squares come from the regular generators. Other shapes start from a
+//!   three-row rectangle and grow by one border at a time, two rows and two columns, each
+//!   border taking the values furthest from the middle so far, opposite sides cancelling out.
+
+use std::fmt;
+
+use crate::generator;
+use crate::rng::Lcg;
+
+/// Errors reported by the rectangle generators.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RectangleError {
+    /// No magic rectangle of this shape exists.
+    Impossible { rows: usize, cols: usize },
+}
+
+impl fmt::Display for RectangleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Impossible { rows, cols } => write!(
+                f,
+                "No {}x{} magic rectangle exists. Both sides must be even or both odd, and 2x2 is impossible.",
+                rows, cols
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RectangleError {}
+
+/// Returns whether a magic rectangle of this shape exists.
+pub fn exists(rows: usize, cols: usize) -> bool {
+    if rows == 1 || cols == 1 {
+        return rows == cols;
+    }
+    rows > 0 && cols > 0 && rows % 2 == cols % 2 && (rows, cols) != (2, 2)
+}
+
+/// Generates a `rows` x `cols` magic rectangle.
+pub fn generate(rows: usize, cols: usize, rng: &mut Lcg) -> Result<Vec<u32>, RectangleError> {
+    if !exists(rows, cols) {
+        return Err(RectangleError::Impossible { rows, cols });
+    }
+    if rows == cols {
+        return Ok(generator::create(rows, rng).generate(rows));
+    }
+    if rows.is_multiple_of(2) {
+        Ok(even_rectangle(rows, cols, rng))
+    } else {
+        Ok(odd_rectangle(rows, cols, rng))
+    }
+}
+
+/// Generates a semi-magic square of order `n`: rows and columns sum to the magic constant,
+/// the diagonals need not.
+///
+/// A magic square has its rows and its columns shuffled independently, which keeps every row
+/// and column sum but scatters the diagonals.
+pub fn generate_semi_magic(n: usize, rng: &mut Lcg) -> Result<Vec<u32>, RectangleError> {
+    if !exists(n, n) {
+        return Err(RectangleError::Impossible { rows: n, cols: n });
+    }
+    let square = generator::create(n, rng).generate(n);
+
+    let mut row_order: Vec<usize> = (0..n).collect();
+    let mut col_order: Vec<usize> = (0..n).collect();
+    rng.shuffle(&mut row_order);
+    rng.shuffle(&mut col_order);
+
+    let mut grid = Vec::with_capacity(n * n);
+    for &r in &row_order {
+        grid.extend(col_order.iter().map(|&c| square[r * n + c]));
+    }
+    Ok(grid)
+}
+
+/// Builds an even-sided rectangle from complementary row pairs.
+///
+/// A cell pair holding $x$ on top and $N+1-x$ below is described by the odd number
+/// $d = 2x - N - 1$. A row pair is a group of `cols` signed $d$ values: the top row gets
+/// $x = (N+1+d)/2$, the bottom row the complement, and the top row sums correctly exactly when
+/// the signed values cancel out. The odd numbers $1, 3, ..., N-1$ are split into such groups.
+fn even_rectangle(rows: usize, cols: usize, rng: &mut Lcg) -> Vec<u32> {
+    // Groups of 2 cannot cancel out, so use the other orientation for two columns.
+    if cols == 2 {
+        return transpose(&even_rectangle(cols, rows, rng), cols, rows);
+    }
+
+    let total = (rows * cols) as u32;
+    let mut groups = cancelling_groups(rows / 2, cols);
+    rng.shuffle(&mut groups);
+
+    let mut row_order: Vec<usize> = (0..rows).collect();
+    rng.shuffle(&mut row_order);
+
+    let mut grid = vec![0; rows * cols];
+    for (pair, group) in groups.iter_mut().enumerate() {
+        rng.shuffle(group);
+        let flip = rng.next_range(0, 2) == 1;
+        let (top, bottom) = (row_order[2 * pair], row_order[2 * pair + 1]);
+        for (c, &(t, plus)) in group.iter().enumerate() {
+            let d = 2 * t as u32 + 1;
+            let (high, low) = ((total + 1 + d) / 2, (total + 1 - d) / 2);
+            let (up, down) = if plus != flip { (high, low) } else { (low, high) };
+            grid[top * cols + c] = up;
+            grid[bottom * cols + c] = down;
+        }
+    }
+    grid
+}
+
+/// Splits the odd numbers $2t+1$, $t < $ `count * size`, into `count` groups of `size` signed
+/// values that sum to zero. Each entry is `(t, positive)`. `size` must be even and at least 4.
+fn cancelling_groups(count: usize, size: usize) -> Vec<Vec<(usize, bool)>> {
+    let mut groups = vec![Vec::with_capacity(size); count];
+    let mut next = 0;
+
+    // Sizes of the form 4k + 2 start each group with six values. 1 + 3 + 5 + 9 = 7 + 11 only
+    // works at the very start; further groups go in pairs sharing twelve consecutive values.
+    if size % 4 == 2 {
+        let mut start = 0;
+        if count % 2 == 1 {
+            let first = [(0, false), (1, false), (2, false), (3, true), (4, false), (5, true)];
+            extend(&mut groups[0], next, &first);
+            next += 6;
+            start = 1;
+        }
+        for g in (start..count).step_by(2) {
+            let a = [(0, true), (1, false), (2, false), (4, true), (5, true), (6, false)];
+            let b = [(3, true), (7, false), (8, false), (9, false), (10, true), (11, true)];
+            extend(&mut groups[g], next, &a);
+            extend(&mut groups[g + 1], next, &b);
+            next += 12;
+        }
+    }
+
+    // Fill up with runs of four consecutive values: t - (t+1) - (t+2) + (t+3) = 0.
+    let quad = [(0, true), (1, false), (2, false), (3, true)];
+    for group in &mut groups {
+        while group.len() < size {
+            extend(group, next, &quad);
+            next += 4;
+        }
+    }
+    groups
+}
+
+fn extend(group: &mut Vec<(usize, bool)>, base: usize, offsets: &[(usize, bool)]) {
+    group.extend(offsets.iter().map(|&(o, plus)| (base + o, plus)));
+}
+
+/// Builds an odd-sided, non-square rectangle.
+///
+/// The construction works with centred values $-H..=H$, $H = (N-1)/2$, so that every row and
+/// column must sum to zero; $H+1$ is added on the way into the grid. A three-row core with
+/// `cols - rows + 3` columns is wrapped in borders, each adding two rows and two columns
+/// around the previous rectangle without touching its values. Rows and columns are shuffled
+/// at the end, which keeps every line sum.
+fn odd_rectangle(rows: usize, cols: usize, rng: &mut Lcg) -> Vec<u32> {
+    if rows > cols {
+        return transpose(&odd_rectangle(cols, rows, rng), cols, rows);
+    }
+
+    let mut row_order: Vec<usize> = (0..rows).collect();
+    let mut col_order: Vec<usize> = (0..cols).collect();
+    rng.shuffle(&mut row_order);
+    rng.shuffle(&mut col_order);
+
+    let shift = (rows * cols / 2) as i64 + 1;
+    let mut grid = vec![0; rows * cols];
+    let mut put = |r: usize, c: usize, y: i64| grid[row_order[r] * cols + col_order[c]] = (y + shift) as u32;
+
+    let width = cols - rows + 3;
+    let mut margin = (rows - 3) / 2;
+    for (i, y) in core(width).into_iter().enumerate() {
+        put(margin + i / width, margin + i % width, y);
+    }
+
+    let (mut inner_rows, mut inner_cols) = (3, width);
+    while inner_rows < rows {
+        let ([corner, other], top, left) = border(inner_cols, inner_rows);
+        margin -= 1;
+        let (bottom, right) = (margin + inner_rows + 1, margin + inner_cols + 1);
+        put(margin, margin, corner);
+        put(margin, right, other);
+        put(bottom, margin, -other);
+        put(bottom, right, -corner);
+        for (j, &y) in top.iter().enumerate() {
+            put(margin, margin + 1 + j, y);
+            put(bottom, margin + 1 + j, -y);
+        }
+        for (i, &y) in left.iter().enumerate() {
+            put(margin + 1 + i, margin, y);
+            put(margin + 1 + i, right, -y);
+        }
+        inner_rows += 2;
+        inner_cols += 2;
+    }
+    grid
+}
+
+/// Centred $3 \times 9$ rectangle; the general core below has no solution for this width.
+const CORE_NINE: [i64; 27] = [
+    -9, 1, -1, -6, 12, -12, 7, 0, 8, //
+    -2, -10, -3, 13, -8, 10, 6, 5, -11, //
+    11, 9, 4, -7, -4, 2, -13, -5, 3,
+];
+
+/// Centred $3 \times n$ rectangle for odd $n \geq 5$, as a flat row-major grid.
+///
+/// With $n = 2k+1$ the first row is $-k..=k$. Column $j$ then holds $P_j$ and $-(P_j + j)$
+/// below it in some order, where both $P_j$ and $P_j + j$ run over $k+1..=3k+1$; every column
+/// sums to zero. The second row sums to zero when the columns whose positive value goes on
+/// top have $P_j + (P_j + j)$ adding up to $n^2$, which is a subset sum over two arithmetic
+/// progressions with step 3. Checked for every width up to 7001.
+fn core(n: usize) -> Vec<i64> {
+    if n == 9 {
+        return CORE_NINE.to_vec();
+    }
+    let k = (n / 2) as i64;
+    let positive = |j: i64| if j >= 0 { k + 1 + j } else { 3 * k + 2 + j };
+
+    // Columns j = t >= 0 weigh 2k+2+3t, columns j = -(k-u) weigh 3k+4+3u.
+    let target = (n * n) as i64;
+    let starts: Vec<i64> = (0..=k).collect();
+    let ends: Vec<i64> = (0..k).collect();
+    let mut up = vec![false; n];
+    'outer: for count_a in 0..=k {
+        for count_b in 0..=k {
+            let rest = target - count_a * (2 * k + 2) - count_b * (3 * k + 4);
+            if rest < 0 || rest % 3 != 0 {
+                continue;
+            }
+            let (max_a, max_b) = (count_a * (2 * k + 1 - count_a) / 2, count_b * (2 * k - count_b) / 2);
+            let sum = rest / 3;
+            let sum_a = (sum - max_b).max(count_a * (count_a - 1) / 2);
+            if sum_a > max_a || sum - sum_a < count_b * (count_b - 1) / 2 {
+                continue;
+            }
+            let (Some(a), Some(b)) =
+                (pick(&starts, count_a as usize, sum_a), pick(&ends, count_b as usize, sum - sum_a))
+            else {
+                continue;
+            };
+            for t in a {
+                up[k as usize + t] = true;
+            }
+            for u in b {
+                up[u] = true;
+            }
+            break 'outer;
+        }
+    }
+
+    let mut grid = vec![0; 3 * n];
+    for c in 0..n {
+        let j = c as i64 - k;
+        let (plus, minus) = (positive(j), -(positive(j) + j));
+        let (second, third) = if up[c] { (plus, minus) } else { (minus, plus) };
+        grid[c] = j;
+        grid[n + c] = second;
+        grid[2 * n + c] = third;
+    }
+    grid
+}
+
+/// Border around a centred `q` x `p` rectangle, $p > q \geq 3$ both odd.
+///
+/// Returns the top-left and top-right corners, the `p` cells between them and the `q` cells
+/// below the top-left corner. The bottom row and right column are their negations, so the
+/// magnitudes $L..L+w$ with $L = (pq+1)/2$, $w = p+q+2$ each appear twice, and the opposite
+/// lines cancel. Writing the magnitudes as $L + o$, the corners take offsets 0 and $v$ (1 or
+/// 2, by parity) and the top row and left column reduce to two signed subset sums over the
+/// remaining offsets: a negative group of `(p+q)/2` offsets that outweighs the positive one
+/// by $2L$, and a left column whose negatives outweigh its positives by $L + v$.
+///
+/// The greedy picks have been checked for every $p < 700$, and up to $p = 7001$ with $q$ near
+/// 3, near $p$ and around $p/2$.
+fn border(p: usize, q: usize) -> ([i64; 2], Vec<i64>, Vec<i64>) {
+    let base = (p * q).div_ceil(2) as i64;
+    let half = (p + q) / 2;
+    let second = if half.is_multiple_of(2) { 1 } else { 2 };
+    let offsets: Vec<i64> = (1..2 * half as i64 + 2).filter(|&o| o != second).collect();
+    let (low, high) = (q / 2, q.div_ceil(2));
+
+    // The top `high` offsets are negative, the bottom `low` positive, the middle is picked.
+    let total: i64 = offsets.iter().sum();
+    let split = offsets.len() - high;
+    let mut negative = vec![false; offsets.len()];
+    negative[split..].fill(true);
+    let wanted = (total + 2 * base) / 2 - offsets[split..].iter().sum::<i64>();
+    let middle = pick(&offsets[low..split], half - high, wanted).expect("negative offsets");
+    for i in middle {
+        negative[low + i] = true;
+    }
+    let select = |sign: bool| offsets.iter().zip(&negative).filter(move |&(_, &n)| n == sign).map(|(&o, _)| o);
+    let (neg, pos): (Vec<i64>, Vec<i64>) = (select(true).collect(), select(false).collect());
+
+    // The left negatives come from the top run of consecutive negative offsets, the left
+    // positives from anywhere; lower the negative sum until the positive side can match it.
+    let run = neg.len() - neg.windows(2).rev().take_while(|w| w[1] == w[0] + 1).count() - 1;
+    let run = &neg[run..];
+    let gap = base + second;
+    let most = run[run.len() - high..].iter().sum::<i64>().min(gap + pos[pos.len() - low..].iter().sum::<i64>());
+    let least = run[..high].iter().sum::<i64>().max(gap + pos[..low].iter().sum::<i64>());
+    let (sum, left_pos) =
+        (least..=most).rev().find_map(|sum| Some((sum, pick(&pos, low, sum - gap)?))).expect("left column");
+    let left_neg = pick(run, high, sum).expect("left negatives");
+
+    let mut in_left_neg = vec![false; neg.len()];
+    for i in left_neg {
+        in_left_neg[neg.len() - run.len() + i] = true;
+    }
+    let mut in_left_pos = vec![false; pos.len()];
+    for &i in &left_pos {
+        in_left_pos[i] = true;
+    }
+
+    let mut top = Vec::with_capacity(p);
+    let mut left = Vec::with_capacity(q);
+    for (&o, &l) in pos.iter().zip(&in_left_pos) {
+        if l { left.push(base + o) } else { top.push(base + o) }
+    }
+    for (&o, &l) in neg.iter().zip(&in_left_neg) {
+        if l { left.push(-(base + o)) } else { top.push(-(base + o)) }
+    }
+    ([base, -(base + second)], top, left)
+}
+
+/// Indices of `k` entries of the ascending `values` that add up to `target`, if the greedy
+/// finds them: start from the `k` smallest and slide each, highest first, as far up as the
+/// remaining difference allows. This always succeeds when `values` are consecutive.
+fn pick(values: &[i64], k: usize, target: i64) -> Option<Vec<usize>> {
+    if k > values.len() {
+        return None;
+    }
+    let mut chosen: Vec<usize> = (0..k).collect();
+    let mut need = target - values[..k].iter().sum::<i64>();
+    let mut limit = values.len();
+    for slot in chosen.iter_mut().rev() {
+        if need <= 0 {
+            break;
+        }
+        let from = *slot;
+        let to = from + values[from..limit].partition_point(|&v| v - values[from] <= need) - 1;
+        need -= values[to] - values[from];
+        *slot = to;
+        limit = to;
+    }
+    (need == 0).then_some(chosen)
+}
+
+fn transpose(grid: &[u32], rows: usize, cols: usize) -> Vec<u32> {
+    (0..cols * rows).map(|i| grid[(i % rows) * cols + i / rows]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validator::{check_magic_rectangle, check_semi_magic_properties};
+
+    #[test]
+    fn even_rectangles() {
+        let mut rng = Lcg::new_with_seed(1);
+        for (rows, cols) in [(2, 4), (4, 2), (2, 6), (4, 6), (6, 4), (6, 10), (10, 6), (4, 8), (8, 14), (12, 10)] {
+            let grid = generate(rows, cols, &mut rng).unwrap();
+            assert!(check_magic_rectangle(&grid, rows, cols), "{}x{}", rows, cols);
+        }
+    }
+
+    #[test]
+    fn odd_rectangles() {
+        let mut rng = Lcg::new_with_seed(2);
+        // 3x9 and 5x11 grow from the fixed 3x9 core.
+        for (rows, cols) in [(3, 5), (5, 3), (3, 7), (3, 9), (5, 7), (5, 11), (9, 15), (15, 21), (7, 7)] {
+            let grid = generate(rows, cols, &mut rng).unwrap();
+            assert!(check_magic_rectangle(&grid, rows, cols), "{}x{}", rows, cols);
+        }
+    }
+
+    #[test]
+    fn rejects_impossible_shapes() {
+        let mut rng = Lcg::new_with_seed(3);
+        for (rows, cols) in [(2, 2), (3, 4), (1, 3), (4, 1), (0, 0)] {
+            assert_eq!(generate(rows, cols, &mut rng), Err(RectangleError::Impossible { rows, cols }));
+        }
+        assert_eq!(generate(1, 1, &mut rng), Ok(vec![1]));
+        assert!(generate_semi_magic(2, &mut rng).is_err());
+    }
+
+    #[test]
+    fn semi_magic_squares() {
+        let mut rng = Lcg::new_with_seed(4);
+        for n in [1, 3, 4, 5, 6, 8] {
+            let grid = generate_semi_magic(n, &mut rng).unwrap();
+            assert!(check_semi_magic_properties(&grid, n), "order {}", n);
+        }
+
+        // Lo Shu with its first two rows swapped keeps its rows and columns only.
+        let grid = [3, 5, 7, 4, 9, 2, 8, 1, 6];
+        assert!(check_semi_magic_properties(&grid, 3));
+        assert!(!crate::validator::check_magic_properties(&grid, 3));
+        assert!(!check_semi_magic_properties(&[5; 9], 3));
+    }
+
+    #[test]
+    fn large_odd_rectangles() {
+        let mut rng = Lcg::new_with_seed(5);
+        for (rows, cols) in [(41, 43), (43, 41), (3, 1001), (101, 103)] {
+            let grid = generate(rows, cols, &mut rng).unwrap();
+            assert!(check_magic_rectangle(&grid, rows, cols), "{}x{}", rows, cols);
+        }
+    }
+}
@@ -0,0 +1,367 @@
+//! Magic squares over a restricted set of values, such as primes.
+//!
+//! Given a pool of candidate values and a target constant, the search looks for an
+//! $n \times n$ grid of distinct pool values whose rows, columns and main diagonals all sum
+//! to the target. It works like the completion [solver](crate::solver):
+//! - A line with a single empty cell forces that cell to the missing amount, which must be an
+//!   unused pool value.
+//! - A line whose remaining amount cannot be reached with the smallest or the largest unused
+//!   values, or a line with two empty cells and no pair of unused values adding up to the
+//!   remaining amount, is a dead end.
+//! - Otherwise the search branches on a cell of the line with the fewest empty cells, trying
+//!   the pool values in a random order.
+//!
+//! Only one of the 8 rotations and reflections of each square is searched; the square found
+//! is returned in a random orientation.
+//!
+//! [`smallest`] tries every feasible constant in increasing order, which finds for instance the
+//! 3x3 prime square with the smallest sum, 177.
+
+use std::cmp::Reverse;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::rng::Lcg;
+use crate::transform::Transform;
+use crate::validator::{Line, LineTally};
+
+/// Branching steps spent on one target constant before giving up.
+const SEARCH_BUDGET: usize = 2_000_000;
+
+/// Errors returned by the restricted-set search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestrictedError {
+    /// Orders below 3 have no magic square of distinct values.
+    UnsupportedOrder(usize),
+    /// The pool holds fewer distinct values than the square has cells.
+    PoolTooSmall { needed: usize, available: usize },
+    /// The whole search space was explored: no square has this constant.
+    NotFound { target: u64 },
+    /// The search budget ran out before a square was found.
+    GaveUp { target: u64 },
+    /// No constant in the feasible range admits a square.
+    NoSquare,
+}
+
+impl fmt::Display for RestrictedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedOrder(n) => write!(f, "Order {} is not supported. Use an order of at least 3.", n),
+            Self::PoolTooSmall { needed, available } => {
+                write!(f, "The square needs {} distinct values but the pool has {}.", needed, available)
+            }
+            Self::NotFound { target } => write!(f, "No square from this pool has the constant {}.", target),
+            Self::GaveUp { target } => write!(f, "The search for a square with the constant {} gave up.", target),
+            Self::NoSquare => write!(f, "No square can be built from this pool."),
+        }
+    }
+}
+
+impl std::error::Error for RestrictedError {}
+
+/// Finds an order-`n` magic square of distinct values from `pool` whose lines sum to `target`.
+pub fn search(n: usize, pool: &[u32], target: u64, rng: &mut Lcg) -> Result<Vec<u32>, RestrictedError> {
+    let pool = prepare(n, pool)?;
+    Search::new(n, &pool, target, rng).run(rng)
+}
+
+/// Finds the order-`n` magic square from `pool` with the smallest constant. Constants on which
+/// the search gives up are skipped, so the result is the smallest only if none was skipped.
+pub fn smallest(n: usize, pool: &[u32], rng: &mut Lcg) -> Result<Vec<u32>, RestrictedError> {
+    let pool = prepare(n, pool)?;
+    let cells = n * n;
+    let low: u64 = pool.iter().take(cells).map(|&v| v as u64).sum();
+    let high: u64 = pool.iter().rev().take(cells).map(|&v| v as u64).sum();
+
+    for target in low.div_ceil(n as u64)..=high / n as u64 {
+        if let Ok(grid) = Search::new(n, &pool, target, rng).run(rng) {
+            return Ok(grid);
+        }
+    }
+    Err(RestrictedError::NoSquare)
+}
+
+/// The primes up to and including `limit`, by the sieve of Eratosthenes.
+pub fn primes_up_to(limit: u32) -> Vec<u32> {
+    let limit = limit as usize;
+    let mut composite = vec![false; limit + 1];
+    let mut primes = Vec::new();
+    for i in 2..=limit {
+        if composite[i] {
+            continue;
+        }
+        primes.push(i as u32);
+        for multiple in (i * i..=limit).step_by(i) {
+            composite[multiple] = true;
+        }
+    }
+    primes
+}
+
+/// Checks the order and returns the pool sorted without duplicates.
+fn prepare(n: usize, pool: &[u32]) -> Result<Vec<u32>, RestrictedError> {
+    if n < 3 {
+        return Err(RestrictedError::UnsupportedOrder(n));
+    }
+    let mut pool = pool.to_vec();
+    pool.sort_unstable();
+    pool.dedup();
+    if pool.len() < n * n {
+        return Err(RestrictedError::PoolTooSmall { needed: n * n, available: pool.len() });
+    }
+    Ok(pool)
+}
+
+/// Search state: the grid with `None` for empty cells, plus running line tallies.
+struct Search<'a> {
+    n: usize,
+    target: u64,
+    /// Sorted, distinct candidate values.
+    pool: &'a [u32],
+    /// `used[i]` is set once `pool[i]` is placed.
+    used: Vec<bool>,
+    /// Pool indices in the order branches try them.
+    order: Vec<usize>,
+    grid: Vec<Option<u32>>,
+    tally: LineTally,
+    /// Cells in placement order, so placements can be undone.
+    trail: Vec<usize>,
+    /// Branching steps left.
+    budget: usize,
+}
+
+impl<'a> Search<'a> {
+    fn new(n: usize, pool: &'a [u32], target: u64, rng: &mut Lcg) -> Self {
+        let mut order: Vec<usize> = (0..pool.len()).collect();
+        rng.shuffle(&mut order);
+        Self {
+            n,
+            target,
+            pool,
+            used: vec![false; pool.len()],
+            order,
+            grid: vec![None; n * n],
+            tally: LineTally::with_target(n, target),
+            trail: Vec::new(),
+            budget: SEARCH_BUDGET,
+        }
+    }
+
+    fn run(mut self, rng: &mut Lcg) -> Result<Vec<u32>, RestrictedError> {
+        match self.search() {
+            Some(grid) => {
+                let symmetries = Transform::dihedral(self.n);
+                Ok(symmetries[rng.next_range(0, symmetries.len())].apply(&grid))
+            }
+            None if self.budget == 0 => Err(RestrictedError::GaveUp { target: self.target }),
+            None => Err(RestrictedError::NotFound { target: self.target }),
+        }
+    }
+
+    fn place(&mut self, cell: usize, index: usize) {
+        self.grid[cell] = Some(self.pool[index]);
+        self.used[index] = true;
+        self.tally.add(cell, self.pool[index]);
+        self.trail.push(cell);
+    }
+
+    /// Removes every placement made after the trail had length `mark`.
+    fn undo(&mut self, mark: usize) {
+        while self.trail.len() > mark {
+            let cell = self.trail.pop().unwrap();
+            let value = self.grid[cell].take().unwrap();
+            let index = self.pool.binary_search(&value).unwrap();
+            self.used[index] = false;
+            self.tally.remove(cell, value);
+        }
+    }
+
+    /// Places forced values until nothing changes. Returns `false` on a dead end.
+    fn propagate(&mut self) -> bool {
+        loop {
+            if !self.canonical_corners() {
+                return false;
+            }
+            let mut changed = false;
+
+            for idx in 0..2 * self.n + 2 {
+                let k = self.tally.empty(idx);
+                let Some(remaining) = self.target.checked_sub(self.tally.sum(idx)) else {
+                    return false;
+                };
+                if k == 0 {
+                    if remaining != 0 {
+                        return false;
+                    }
+                    continue;
+                }
+                let (low, high) = self.extremes(k);
+                if low.is_none_or(|low| remaining < low) || high.is_none_or(|high| remaining > high) {
+                    return false;
+                }
+
+                if k == 2 && !self.has_pair(remaining) {
+                    return false;
+                }
+                if k == 1 {
+                    let Some(index) = self.free_index(remaining) else {
+                        return false;
+                    };
+                    let line = Line::from_index(self.n, idx);
+                    let cell = line.cells(self.n).find(|&c| self.grid[c].is_none()).unwrap();
+                    self.place(cell, index);
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                return true;
+            }
+        }
+    }
+
+    /// Whether the filled corners are compatible with the smallest corner being top left and
+    /// the top-right corner being below the bottom-left one. Exactly one of the 8 symmetric
+    /// copies of a square satisfies this, so the others are never searched.
+    fn canonical_corners(&self) -> bool {
+        let n = self.n;
+        let [top_left, top_right, bottom_left, bottom_right] = [0, n - 1, n * n - n, n * n - 1].map(|c| self.grid[c]);
+        let below = |a: Option<u32>, b: Option<u32>| a.zip(b).is_none_or(|(a, b)| a < b);
+        below(top_left, top_right)
+            && below(top_left, bottom_left)
+            && below(top_left, bottom_right)
+            && below(top_right, bottom_left)
+    }
+
+    /// Sums of the `k` smallest and the `k` largest unused values, if there are `k` of them.
+    fn extremes(&self, k: usize) -> (Option<u64>, Option<u64>) {
+        let free = |i: &usize| !self.used[*i];
+        let sum = |indices: &mut dyn Iterator<Item = usize>| {
+            let (count, total) = indices.filter(free).take(k).fold((0, 0), |(count, total), i| (count + 1, total + self.pool[i] as u64));
+            (count == k).then_some(total)
+        };
+        (sum(&mut (0..self.pool.len())), sum(&mut (0..self.pool.len()).rev()))
+    }
+
+    /// Position in the pool of `value`, if it is an unused pool value.
+    fn free_index(&self, value: u64) -> Option<usize> {
+        let index = self.pool.binary_search(&u32::try_from(value).ok()?).ok()?;
+        (!self.used[index]).then_some(index)
+    }
+
+    /// Whether two different unused values add up to `total`.
+    fn has_pair(&self, total: u64) -> bool {
+        (0..self.pool.len())
+            .take_while(|&i| 2 * (self.pool[i] as u64) < total)
+            .any(|i| !self.used[i] && self.free_index(total - self.pool[i] as u64).is_some())
+    }
+
+    /// Picks the empty cell on the most filled line, preferring cells on both diagonals and
+    /// then corners, with the range of values it can take given the other empty cells of its
+    /// lines. Returns `None` once the grid is full.
+    fn pick_cell(&self) -> Option<(usize, RangeInclusive<u64>)> {
+        let cell = (0..self.grid.len()).filter(|&c| self.grid[c].is_none()).min_by_key(|&c| {
+            let ks = Line::through(self.n, c).map(|l| self.tally.empty(l.index(self.n)));
+            (ks.clone().min(), Reverse(ks.count()))
+        })?;
+
+        let (mut min, mut max) = (0, u64::MAX);
+        for line in Line::through(self.n, cell) {
+            let idx = line.index(self.n);
+            let remaining = self.target - self.tally.sum(idx);
+            let (low, high) = self.extremes(self.tally.empty(idx) - 1);
+            max = max.min(remaining.saturating_sub(low.unwrap_or(0)));
+            min = min.max(remaining.saturating_sub(high.unwrap_or(0)));
+        }
+        Some((cell, min..=max))
+    }
+
+    /// Depth-first search for the first complete square.
+    fn search(&mut self) -> Option<Vec<u32>> {
+        let mark = self.trail.len();
+        let found = if !self.propagate() {
+            None
+        } else if let Some((cell, range)) = self.pick_cell() {
+            self.branch(cell, range)
+        } else {
+            Some(self.grid.iter().map(|v| v.unwrap()).collect())
+        };
+        self.undo(mark);
+        found
+    }
+
+    fn branch(&mut self, cell: usize, range: RangeInclusive<u64>) -> Option<Vec<u32>> {
+        for i in 0..self.order.len() {
+            let index = self.order[i];
+            if self.used[index] || !range.contains(&(self.pool[index] as u64)) {
+                continue;
+            }
+            if self.budget == 0 {
+                return None;
+            }
+            self.budget -= 1;
+
+            let mark = self.trail.len();
+            self.place(cell, index);
+            let found = self.search();
+            self.undo(mark);
+            if found.is_some() {
+                return found;
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validator::{check_magic_properties_from, common_line_sum, has_distinct_values_from};
+
+    #[test]
+    fn smallest_prime_square_of_order_3() {
+        let primes = primes_up_to(113);
+        let mut rng = Lcg::new_with_seed(1);
+        let grid = smallest(3, &primes, &mut rng).unwrap();
+        assert!(check_magic_properties_from(&grid, 3, &primes));
+        assert_eq!(common_line_sum(&grid, 3), Some(177));
+    }
+
+    #[test]
+    fn consecutive_primes_of_order_4() {
+        // The first run of sixteen consecutive primes that fills a magic square goes from 31 to
+        // 101: earlier runs either sum to a number that is not a multiple of 4 or have no square.
+        let primes = primes_up_to(101);
+        let run = &primes[primes.len() - 16..];
+        let target = run.iter().map(|&p| p as u64).sum::<u64>() / 4;
+        let mut rng = Lcg::new_with_seed(2);
+        let grid = search(4, run, target, &mut rng).unwrap();
+        assert!(check_magic_properties_from(&grid, 4, run));
+
+        let earlier = &primes[5..21];
+        assert_eq!((earlier[0], earlier[15]), (13, 73));
+        assert_eq!(search(4, earlier, 171, &mut rng), Err(RestrictedError::NotFound { target: 171 }));
+    }
+
+    #[test]
+    fn any_value_set() {
+        let evens: Vec<u32> = (1..=9).map(|v| 2 * v).collect();
+        let mut rng = Lcg::new_with_seed(3);
+        let grid = search(3, &evens, 30, &mut rng).unwrap();
+        assert!(check_magic_properties_from(&grid, 3, &evens));
+        assert_eq!(search(3, &evens, 31, &mut rng), Err(RestrictedError::NotFound { target: 31 }));
+        assert!(!has_distinct_values_from(&[2, 2, 4], &evens));
+        assert!(!has_distinct_values_from(&[2, 3], &evens));
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        let mut rng = Lcg::new_with_seed(4);
+        assert_eq!(search(2, &[1, 2, 3, 4], 5, &mut rng), Err(RestrictedError::UnsupportedOrder(2)));
+        assert_eq!(
+            search(3, &[1, 1, 2, 3, 4, 5, 6, 7, 8], 15, &mut rng),
+            Err(RestrictedError::PoolTooSmall { needed: 9, available: 8 })
+        );
+        assert_eq!(primes_up_to(30), [2, 3, 5, 7, 11, 13, 17, 19, 23, 29]);
+    }
+}
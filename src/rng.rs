@@ -69,3 +69,9 @@ impl Lcg {
         }
     }
 }
+
+impl Default for Lcg {
+    fn default() -> Self {
+        Self::new()
+    }
+}
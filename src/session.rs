@@ -0,0 +1,327 @@
+//! Interactive puzzle session for the fill-in game of the web app.
+//!
+//! A [`PuzzleSession`] holds a puzzle, the player's entries and a [`LineTally`] of the grid.
+//! Each entry updates only the lines through its cell, so checking the board after a keystroke
+//! does not revalidate the whole square.
+
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+use crate::puzzle::{self, Difficulty, Puzzle};
+use crate::validator::{Line, LineStatus, LineTally};
+
+/// Errors reported when editing a session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionError {
+    /// The cell lies outside the square.
+    OutOfBounds { row: usize, col: usize },
+    /// The cell is a clue and cannot be changed.
+    FixedCell { row: usize, col: usize },
+    /// The value lies outside $1..n^2$.
+    ValueOutOfRange(u32),
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfBounds { row, col } => write!(f, "Cell ({}, {}) is outside the square.", row, col),
+            Self::FixedCell { row, col } => write!(f, "Cell ({}, {}) is a clue and cannot be changed.", row, col),
+            Self::ValueOutOfRange(value) => write!(f, "Value {} is outside the allowed range.", value),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+/// A suggested move returned by [`PuzzleSession::hint`].
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hint {
+    row: usize,
+    col: usize,
+    value: u32,
+    correction: bool,
+}
+
+#[wasm_bindgen]
+impl Hint {
+    /// Row of the cell to fill (0-based).
+    #[wasm_bindgen(getter)]
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    /// Column of the cell to fill (0-based).
+    #[wasm_bindgen(getter)]
+    pub fn col(&self) -> usize {
+        self.col
+    }
+
+    /// The value that belongs in the cell.
+    #[wasm_bindgen(getter)]
+    pub fn value(&self) -> u32 {
+        self.value
+    }
+
+    /// Whether the hint replaces a wrong entry rather than filling an empty cell.
+    #[wasm_bindgen(getter)]
+    pub fn correction(&self) -> bool {
+        self.correction
+    }
+}
+
+/// A puzzle being solved: clues, the player's entries and the running line sums.
+#[wasm_bindgen]
+pub struct PuzzleSession {
+    n: usize,
+    /// Current grid, clues included, `0` for empty cells.
+    cells: Vec<u32>,
+    fixed: Vec<bool>,
+    solution: Vec<u32>,
+    tally: LineTally,
+    /// `counts[v]` is the number of cells holding `v`, to spot repeated values.
+    counts: Vec<u32>,
+    repeated: usize,
+    difficulty: Difficulty,
+}
+
+#[wasm_bindgen]
+impl PuzzleSession {
+    /// Starts a session on a new puzzle (see `generate_puzzle`).
+    #[wasm_bindgen(constructor)]
+    pub fn new(n: usize, difficulty: Difficulty, seed: u32) -> Result<PuzzleSession, JsError> {
+        let puzzle = puzzle::make_puzzle(n, difficulty, seed as u64).map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(Self::from_puzzle(puzzle))
+    }
+
+    /// Enters `value` at (`row`, `col`), replacing any previous entry.
+    #[wasm_bindgen]
+    pub fn set_cell(&mut self, row: usize, col: usize, value: u32) -> Result<(), JsError> {
+        self.fill(row, col, value).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Empties the cell at (`row`, `col`).
+    #[wasm_bindgen]
+    pub fn clear_cell(&mut self, row: usize, col: usize) -> Result<(), JsError> {
+        self.clear(row, col).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// State of every line as `LineStatus` codes: rows, then columns, then the main diagonal
+    /// and the anti-diagonal. `0` incomplete, `1` correct, `2` wrong, `3` over the constant.
+    #[wasm_bindgen]
+    pub fn line_status(&self) -> Vec<u8> {
+        self.tally.statuses().into_iter().map(|s| s as u8).collect()
+    }
+
+    /// Suggests a move: the correction of a wrong entry if there is one, otherwise the value
+    /// of an empty cell on the most filled line. Returns nothing once the square is solved.
+    #[wasm_bindgen]
+    pub fn hint(&self) -> Option<Hint> {
+        let n = self.n;
+        let hint = |cell: usize, correction| Hint { row: cell / n, col: cell % n, value: self.solution[cell], correction };
+
+        if let Some(cell) = (0..n * n).find(|&c| self.cells[c] != 0 && self.cells[c] != self.solution[c]) {
+            return Some(hint(cell, true));
+        }
+        (0..n * n)
+            .filter(|&c| self.cells[c] == 0)
+            .min_by_key(|&c| Line::through(n, c).map(|l| self.tally.empty(l.index(n))).min())
+            .map(|cell| hint(cell, false))
+    }
+
+    /// Whether every cell is filled, with distinct values and every line correct.
+    #[wasm_bindgen]
+    pub fn is_solved(&self) -> bool {
+        self.repeated == 0
+            && !self.cells.contains(&0)
+            && self.tally.statuses().iter().all(|&s| s == LineStatus::Correct)
+    }
+
+    /// Whether the cell at (`row`, `col`) is a clue.
+    #[wasm_bindgen]
+    pub fn is_clue(&self, row: usize, col: usize) -> bool {
+        row < self.n && col < self.n && self.fixed[row * self.n + col]
+    }
+
+    /// Returns a raw pointer to the current grid (`0` for empty cells).
+    #[wasm_bindgen]
+    pub fn get_grid_ptr(&self) -> *const u32 {
+        self.cells.as_ptr()
+    }
+
+    /// Returns the number of elements in the grid.
+    #[wasm_bindgen]
+    pub fn get_grid_len(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Returns the order (n) of the square.
+    #[wasm_bindgen(getter)]
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    /// Returns the rated difficulty of the puzzle.
+    #[wasm_bindgen(getter)]
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+}
+
+impl PuzzleSession {
+    /// Starts a session on an existing puzzle.
+    pub fn from_puzzle(puzzle: Puzzle) -> Self {
+        let n = puzzle.n;
+        let mut session = Self {
+            n,
+            cells: vec![0; n * n],
+            fixed: puzzle.clues.iter().map(Option::is_some).collect(),
+            solution: puzzle.solution,
+            tally: LineTally::new(n),
+            counts: vec![0; n * n + 1],
+            repeated: 0,
+            difficulty: puzzle.difficulty,
+        };
+        for (cell, clue) in puzzle.clues.iter().enumerate() {
+            if let Some(value) = *clue {
+                session.put(cell, value);
+            }
+        }
+        session
+    }
+
+    /// Enters `value` at (`row`, `col`), replacing any previous entry.
+    pub fn fill(&mut self, row: usize, col: usize, value: u32) -> Result<(), SessionError> {
+        let cell = self.editable(row, col)?;
+        if value == 0 || value as usize > self.n * self.n {
+            return Err(SessionError::ValueOutOfRange(value));
+        }
+        self.take(cell);
+        self.put(cell, value);
+        Ok(())
+    }
+
+    /// Empties the cell at (`row`, `col`).
+    pub fn clear(&mut self, row: usize, col: usize) -> Result<(), SessionError> {
+        let cell = self.editable(row, col)?;
+        self.take(cell);
+        Ok(())
+    }
+
+    /// Returns a copy of the current grid (`0` for empty cells) for Rust-side callers.
+    pub fn grid(&self) -> Vec<u32> {
+        self.cells.clone()
+    }
+
+    fn editable(&self, row: usize, col: usize) -> Result<usize, SessionError> {
+        if row >= self.n || col >= self.n {
+            return Err(SessionError::OutOfBounds { row, col });
+        }
+        if self.fixed[row * self.n + col] {
+            return Err(SessionError::FixedCell { row, col });
+        }
+        Ok(row * self.n + col)
+    }
+
+    fn put(&mut self, cell: usize, value: u32) {
+        self.cells[cell] = value;
+        self.tally.add(cell, value);
+        self.counts[value as usize] += 1;
+        if self.counts[value as usize] > 1 {
+            self.repeated += 1;
+        }
+    }
+
+    fn take(&mut self, cell: usize) {
+        let value = self.cells[cell];
+        if value == 0 {
+            return;
+        }
+        self.cells[cell] = 0;
+        self.tally.remove(cell, value);
+        if self.counts[value as usize] > 1 {
+            self.repeated -= 1;
+        }
+        self.counts[value as usize] -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::Deductions;
+
+    /// Lo Shu with only the top row given.
+    fn session() -> PuzzleSession {
+        let solution = vec![4, 9, 2, 3, 5, 7, 8, 1, 6];
+        let clues = solution.iter().enumerate().map(|(i, &v)| (i < 3).then_some(v)).collect();
+        PuzzleSession::from_puzzle(Puzzle {
+            n: 3,
+            clues,
+            solution,
+            difficulty: Difficulty::Medium,
+            deductions: Deductions::default(),
+        })
+    }
+
+    #[test]
+    fn tracks_line_status() {
+        let mut s = session();
+        assert_eq!(s.line_status(), vec![1, 0, 0, 0, 0, 0, 0, 0]);
+
+        s.fill(1, 0, 3).unwrap();
+        s.fill(2, 0, 7).unwrap();
+        // Column 0 is 4 + 3 + 7 = 14, then the anti-diagonal reaches 2 + 9 + 7 = 18.
+        assert_eq!(s.line_status()[3], LineStatus::Wrong as u8);
+        s.fill(1, 1, 9).unwrap();
+        assert_eq!(s.line_status()[7], LineStatus::Over as u8);
+
+        s.fill(1, 1, 5).unwrap();
+        s.fill(2, 0, 8).unwrap();
+        assert_eq!(s.line_status()[3], LineStatus::Correct as u8);
+        assert_eq!(s.line_status()[7], LineStatus::Correct as u8);
+        s.clear(1, 0).unwrap();
+        assert_eq!(s.line_status()[3], LineStatus::Incomplete as u8);
+    }
+
+    #[test]
+    fn hints_lead_to_the_solution() {
+        let mut s = session();
+        s.fill(1, 1, 7).unwrap();
+        let hint = s.hint().unwrap();
+        assert_eq!((hint.row(), hint.col(), hint.value(), hint.correction()), (1, 1, 5, true));
+
+        while let Some(hint) = s.hint() {
+            s.fill(hint.row(), hint.col(), hint.value()).unwrap();
+        }
+        assert!(s.is_solved());
+        assert_eq!(s.grid(), vec![4, 9, 2, 3, 5, 7, 8, 1, 6]);
+    }
+
+    #[test]
+    fn rejects_invalid_edits() {
+        let mut s = session();
+        assert_eq!(s.fill(0, 0, 1), Err(SessionError::FixedCell { row: 0, col: 0 }));
+        assert_eq!(s.fill(3, 0, 1), Err(SessionError::OutOfBounds { row: 3, col: 0 }));
+        assert_eq!(s.fill(1, 0, 10), Err(SessionError::ValueOutOfRange(10)));
+        assert_eq!(s.clear(0, 1), Err(SessionError::FixedCell { row: 0, col: 1 }));
+    }
+
+    #[test]
+    fn repeated_values_are_not_solved() {
+        let mut s = PuzzleSession::from_puzzle(Puzzle {
+            n: 3,
+            clues: vec![None; 9],
+            solution: vec![4, 9, 2, 3, 5, 7, 8, 1, 6],
+            difficulty: Difficulty::Hard,
+            deductions: Deductions::default(),
+        });
+        // A grid of 5s has every line summing to 15.
+        for cell in 0..9 {
+            s.fill(cell / 3, cell % 3, 5).unwrap();
+        }
+        assert!(s.line_status().iter().all(|&code| code == LineStatus::Correct as u8));
+        assert!(!s.is_solved());
+    }
+}
@@ -0,0 +1,401 @@
+//! Completion solver for partially filled magic squares.
+//!
+//! The input is a flat $n \times n$ grid (the same row-major layout as the validator) where
+//! `None` marks an empty cell. The solver fills the empty cells with the unused values
+//! $1..n^2$ so that every row, column and main diagonal sums to the magic constant.
+//!
+//! The search alternates constraint propagation and backtracking:
+//! - A line with a single empty cell forces that cell to the missing amount.
+//! - A line whose remaining amount cannot be reached with the smallest or the largest unused
+//!   values, or a line with two empty cells and no pair of unused values adding up to the
+//!   remaining amount, is a dead end.
+//! - Otherwise the solver branches on a cell of the line with the fewest empty cells.
+//!
+//! Contradictions found before any guess are reported with the line that caused them.
+
+use std::fmt;
+
+use crate::validator::{Line, LineTally};
+
+/// Errors reported by the solver.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolveError {
+    /// The grid does not hold $n^2$ cells.
+    WrongLength { expected: usize, found: usize },
+    /// A given value lies outside $1..n^2$.
+    OutOfRange { cell: usize, value: u32 },
+    /// A value is given more than once.
+    Duplicate { value: u32 },
+    /// The givens already make this line impossible to complete.
+    Infeasible { line: Line },
+    /// The givens are consistent line by line but no completion exists.
+    NoSolution,
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WrongLength { expected, found } => {
+                write!(f, "Expected {} cells but the grid has {}.", expected, found)
+            }
+            Self::OutOfRange { cell, value } => {
+                write!(f, "Value {} in cell {} is outside the allowed range.", value, cell)
+            }
+            Self::Duplicate { value } => write!(f, "Value {} appears more than once.", value),
+            Self::Infeasible { line } => write!(f, "The {} cannot sum to the magic constant.", line),
+            Self::NoSolution => write!(f, "The grid has no completion."),
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+/// Returns one completion of `partial`.
+pub fn solve(partial: &[Option<u32>], n: usize) -> Result<Vec<u32>, SolveError> {
+    let mut found = None;
+    Solver::new(partial, n)?.search(&mut |grid| {
+        found = Some(grid.to_vec());
+        false
+    });
+    found.ok_or(SolveError::NoSolution)
+}
+
+/// Returns every completion of `partial`.
+///
+/// An empty result is not an error: it means the givens admit no completion.
+pub fn solve_all(partial: &[Option<u32>], n: usize) -> Result<Vec<Vec<u32>>, SolveError> {
+    let mut found = Vec::new();
+    Solver::new(partial, n)?.search(&mut |grid| {
+        found.push(grid.to_vec());
+        true
+    });
+    Ok(found)
+}
+
+/// Counts the completions of `partial`, stopping once `limit` have been found.
+///
+/// A limit of 2 is enough to tell whether the completion is unique.
+pub fn count_solutions(partial: &[Option<u32>], n: usize, limit: usize) -> Result<usize, SolveError> {
+    let mut count = 0;
+    if limit == 0 {
+        return Ok(0);
+    }
+    Solver::new(partial, n)?.search(&mut |_| {
+        count += 1;
+        count < limit
+    });
+    Ok(count)
+}
+
+/// How a grid was solved, from the simplest deductions to guesses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Deductions {
+    /// Cells filled because they were the last empty cell of a line.
+    pub line_fills: usize,
+    /// Cells filled because every other value led to a contradiction after propagation.
+    pub eliminations: usize,
+    /// Trial placements made by backtracking once no deduction applied.
+    pub guesses: usize,
+}
+
+/// Solves `partial` the way a person would, counting the deductions it takes.
+///
+/// Line fills are tried first, then single-cell eliminations, and backtracking only when
+/// neither applies. Fails with [`SolveError::NoSolution`] when there is no completion.
+pub fn deduce(partial: &[Option<u32>], n: usize) -> Result<Deductions, SolveError> {
+    let mut solver = Solver::new(partial, n)?;
+    let mut deductions = Deductions { line_fills: solver.trail.len(), ..Deductions::default() };
+    solver.trail.clear();
+
+    while solver.grid.contains(&0) {
+        let Some((cell, value)) = solver.find_elimination() else {
+            let mut solved = false;
+            solver.search(&mut |_| {
+                solved = true;
+                false
+            });
+            deductions.guesses = solver.guesses;
+            return if solved { Ok(deductions) } else { Err(SolveError::NoSolution) };
+        };
+        deductions.eliminations += 1;
+        solver.place(cell, value);
+        let mark = solver.trail.len();
+        solver.propagate().map_err(|_| SolveError::NoSolution)?;
+        deductions.line_fills += solver.trail.len() - mark;
+    }
+    Ok(deductions)
+}
+
+/// Search state: the grid with `0` for empty cells, plus running line tallies.
+struct Solver {
+    n: usize,
+    target: u64,
+    grid: Vec<u32>,
+    /// `used[v]` is set once value `v` is placed.
+    used: Vec<bool>,
+    tally: LineTally,
+    /// Cells in placement order, so placements can be undone.
+    trail: Vec<usize>,
+    /// Number of trial placements made by [`Solver::branch`].
+    guesses: usize,
+}
+
+impl Solver {
+    /// Loads the givens and runs a first round of propagation.
+    fn new(partial: &[Option<u32>], n: usize) -> Result<Self, SolveError> {
+        let cells = n * n;
+        if partial.len() != cells {
+            return Err(SolveError::WrongLength { expected: cells, found: partial.len() });
+        }
+
+        let tally = LineTally::new(n);
+        let mut solver = Self {
+            n,
+            target: tally.target(),
+            grid: vec![0; cells],
+            used: vec![false; cells + 1],
+            tally,
+            trail: Vec::new(),
+            guesses: 0,
+        };
+
+        for (cell, given) in partial.iter().enumerate() {
+            if let Some(value) = *given {
+                if value == 0 || value as usize > cells {
+                    return Err(SolveError::OutOfRange { cell, value });
+                }
+                if solver.used[value as usize] {
+                    return Err(SolveError::Duplicate { value });
+                }
+                solver.place(cell, value);
+            }
+        }
+        // Keep the givens off the trail so that backtracking never removes them.
+        // Cells forced by the first propagation stay on it and are never undone either.
+        solver.trail.clear();
+
+        solver.propagate().map_err(|line| SolveError::Infeasible { line })?;
+        Ok(solver)
+    }
+
+    fn place(&mut self, cell: usize, value: u32) {
+        self.grid[cell] = value;
+        self.used[value as usize] = true;
+        self.tally.add(cell, value);
+        self.trail.push(cell);
+    }
+
+    /// Removes every placement made after the trail had length `mark`.
+    fn undo(&mut self, mark: usize) {
+        while self.trail.len() > mark {
+            let cell = self.trail.pop().unwrap();
+            let value = self.grid[cell];
+            self.grid[cell] = 0;
+            self.used[value as usize] = false;
+            self.tally.remove(cell, value);
+        }
+    }
+
+    /// Places forced values until nothing changes. Returns the first line found impossible.
+    fn propagate(&mut self) -> Result<(), Line> {
+        loop {
+            let mut free: Vec<u64> = (1..self.used.len()).filter(|&v| !self.used[v]).map(|v| v as u64).collect();
+            let mut changed = false;
+
+            for idx in 0..2 * self.n + 2 {
+                let line = Line::from_index(self.n, idx);
+                let k = self.tally.empty(idx);
+                if self.tally.sum(idx) > self.target {
+                    return Err(line);
+                }
+                let remaining = self.target - self.tally.sum(idx);
+
+                if k == 0 {
+                    if remaining != 0 {
+                        return Err(line);
+                    }
+                    continue;
+                }
+                let low: u64 = free.iter().take(k).sum();
+                let high: u64 = free.iter().rev().take(k).sum();
+                if free.len() < k || remaining < low || remaining > high {
+                    return Err(line);
+                }
+
+                if k == 2 && !self.has_pair(&free, remaining) {
+                    return Err(line);
+                }
+                if k == 1 {
+                    let value = remaining as usize;
+                    if value >= self.used.len() || self.used[value] {
+                        return Err(line);
+                    }
+                    let cell = line.cells(self.n).find(|&c| self.grid[c] == 0).unwrap();
+                    self.place(cell, value as u32);
+                    if let Ok(pos) = free.binary_search(&(value as u64)) {
+                        free.remove(pos);
+                    }
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Whether two different unused values add up to `total`.
+    fn has_pair(&self, free: &[u64], total: u64) -> bool {
+        free.iter()
+            .take_while(|&&a| 2 * a < total)
+            .any(|&a| ((total - a) as usize) < self.used.len() && !self.used[(total - a) as usize])
+    }
+
+    /// Picks the empty cell on the most filled lines, with an upper bound on the values it can
+    /// take. Returns `None` once the grid is full.
+    fn pick_cell(&self) -> Option<(usize, u32)> {
+        let cell = (0..self.grid.len()).filter(|&c| self.grid[c] == 0).min_by_key(|&c| {
+            let ks = Line::through(self.n, c).map(|l| self.tally.empty(l.index(self.n)));
+            (ks.clone().min(), ks.sum::<usize>())
+        })?;
+
+        // The other empty cells of each line need at least 1 + 2 + ... + (k - 1).
+        let bound = Line::through(self.n, cell)
+            .map(|l| {
+                let k = self.tally.empty(l.index(self.n)) as u64;
+                (self.target - self.tally.sum(l.index(self.n))).saturating_sub(k * (k - 1) / 2)
+            })
+            .min()
+            .unwrap_or(0);
+        Some((cell, bound.min(self.used.len() as u64 - 1) as u32))
+    }
+
+    /// Finds an empty cell where exactly one unused value survives propagation.
+    fn find_elimination(&mut self) -> Option<(usize, u32)> {
+        for cell in 0..self.grid.len() {
+            if self.grid[cell] != 0 {
+                continue;
+            }
+            let mut survivor = None;
+            for value in 1..self.used.len() as u32 {
+                if self.used[value as usize] {
+                    continue;
+                }
+                let mark = self.trail.len();
+                self.place(cell, value);
+                let consistent = self.propagate().is_ok();
+                self.undo(mark);
+                if consistent {
+                    if survivor.is_some() {
+                        survivor = None;
+                        break;
+                    }
+                    survivor = Some(value);
+                }
+            }
+            if let Some(value) = survivor {
+                return Some((cell, value));
+            }
+        }
+        None
+    }
+
+    /// Depth-first search. `visit` receives each completion and returns whether to go on.
+    /// Returns `false` once `visit` asked to stop.
+    fn search(&mut self, visit: &mut dyn FnMut(&[u32]) -> bool) -> bool {
+        let mark = self.trail.len();
+        let keep_going = match self.propagate() {
+            Err(_) => true,
+            Ok(()) => match self.pick_cell() {
+                None => visit(&self.grid),
+                Some((cell, bound)) => self.branch(cell, bound, visit),
+            },
+        };
+        self.undo(mark);
+        keep_going
+    }
+
+    fn branch(&mut self, cell: usize, bound: u32, visit: &mut dyn FnMut(&[u32]) -> bool) -> bool {
+        for value in 1..=bound {
+            if self.used[value as usize] {
+                continue;
+            }
+            let mark = self.trail.len();
+            self.guesses += 1;
+            self.place(cell, value);
+            let keep_going = self.search(visit);
+            self.undo(mark);
+            if !keep_going {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validator;
+
+    fn givens(grid: &[u32]) -> Vec<Option<u32>> {
+        grid.iter().map(|&v| if v == 0 { None } else { Some(v) }).collect()
+    }
+
+    #[test]
+    fn completes_lo_shu() {
+        let partial = givens(&[4, 9, 0, 0, 0, 0, 0, 0, 0]);
+        let solution = solve(&partial, 3).unwrap();
+        assert_eq!(solution, vec![4, 9, 2, 3, 5, 7, 8, 1, 6]);
+        assert_eq!(count_solutions(&partial, 3, 10), Ok(1));
+    }
+
+    #[test]
+    fn counts_every_square_of_small_orders() {
+        assert_eq!(solve_all(&[None; 9], 3).unwrap().len(), 8);
+        let all = solve_all(&[None; 16], 4).unwrap();
+        assert_eq!(all.len(), 7040);
+        assert!(all.iter().all(|sq| validator::check_magic_properties(sq, 4)));
+        assert_eq!(count_solutions(&[None; 16], 4, 100), Ok(100));
+    }
+
+    #[test]
+    fn reports_the_conflicting_line() {
+        // 9 + 8 + 7 > 15 in the first row.
+        let partial = givens(&[9, 8, 7, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(solve(&partial, 3), Err(SolveError::Infeasible { line: Line::Row(0) }));
+        // The centre of an order-3 square is always 5.
+        let partial = givens(&[0, 0, 0, 0, 1, 0, 0, 0, 0]);
+        assert_eq!(solve(&partial, 3), Err(SolveError::NoSolution));
+        assert_eq!(solve_all(&partial, 3), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn grades_deductions() {
+        // Two clues leave only line fills.
+        let easy = deduce(&givens(&[4, 9, 0, 0, 5, 0, 0, 0, 0]), 3).unwrap();
+        assert_eq!(easy, Deductions { line_fills: 6, eliminations: 0, guesses: 0 });
+        // Corner and edge clues alone need eliminations before any line can be closed.
+        let harder = deduce(&givens(&[4, 9, 0, 0, 0, 0, 0, 0, 0]), 3).unwrap();
+        assert!(harder.eliminations > 0);
+        assert_eq!(harder.guesses, 0);
+        assert!(deduce(&[None; 16], 4).unwrap().guesses > 0);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert_eq!(
+            solve(&[None; 8], 3),
+            Err(SolveError::WrongLength { expected: 9, found: 8 })
+        );
+        assert_eq!(
+            solve(&givens(&[10, 0, 0, 0, 0, 0, 0, 0, 0]), 3),
+            Err(SolveError::OutOfRange { cell: 0, value: 10 })
+        );
+        assert_eq!(
+            solve(&givens(&[2, 2, 0, 0, 0, 0, 0, 0, 0]), 3),
+            Err(SolveError::Duplicate { value: 2 })
+        );
+    }
+}
@@ -0,0 +1,489 @@
+//! Generating and checking squares a row at a time, for orders too large to hold in memory.
+//!
+//! [`RowSource`] computes any cell of a square from a closed formula, so rows can be written
+//! in order to a file or pipe with $O(n)$ memory. The constructions are the same as the
+//! generators': the Siamese method for odd orders, Conway's LUX method for singly even
+//! orders and the truth-grid method for doubly even orders. A seed picks one of the 8
+//! symmetries of the square and whether to take its complement $n^2 + 1 - x$. Values are
+//! `u64`, as $n^2$ no longer fits in a `u32` beyond order 65535.
+//!
+//! [`StreamValidator`] checks rows as they arrive, keeping the column and diagonal sums and a
+//! bitmap of the values seen: $n^2$ bits, or 1.25 GB at order 100,000, instead of the 80 GB
+//! of the grid itself.
+
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+
+use crate::msq::{self, Header, MsqError, MsqReader, MsqWriter};
+use crate::output::{Format, Metadata};
+use crate::rng::Lcg;
+use crate::transform::Symmetry;
+use crate::validator::{self, Line};
+
+/// The largest order streamed: the magic constant $n(n^2 + 1)/2$ must fit in a `u64`.
+pub const MAX_ORDER: usize = 1 << 20;
+
+/// Errors returned while streaming squares.
+#[derive(Debug)]
+pub enum StreamError {
+    /// Reading or writing failed.
+    Io(io::Error),
+    /// The `.msq` input is invalid.
+    Msq(MsqError),
+    /// No magic square of this order exists or it is larger than [`MAX_ORDER`].
+    InvalidOrder(usize),
+    /// The format cannot be written a row at a time.
+    UnsupportedFormat(Format),
+    /// A token on a line of text input is not a number.
+    InvalidNumber { line: usize, token: String },
+    /// A row of text input does not have $n$ values.
+    RowLength { line: usize, n: usize, values: usize },
+    /// The input ends after `rows` of the $n$ rows.
+    MissingRows { n: usize, rows: usize },
+    /// Text input goes on after the last row.
+    TrailingData { line: usize },
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Msq(e) => write!(f, "{}", e),
+            Self::InvalidOrder(n) => write!(f, "Order {} cannot be streamed. Expected 1 or 3 to {}.", n, MAX_ORDER),
+            Self::UnsupportedFormat(format) => {
+                write!(f, "The {} format cannot be streamed. Use text, csv, tsv or msq.", format.name())
+            }
+            Self::InvalidNumber { line, token } => write!(f, "Line {}: '{}' is not a number.", line, token),
+            Self::RowLength { line, n, values } => write!(f, "Line {}: expected {} values, found {}.", line, n, values),
+            Self::MissingRows { n, rows } => write!(f, "The input ends after {} of {} rows.", rows, n),
+            Self::TrailingData { line } => {
+                write!(f, "Line {}: the input goes on after the last row. Streaming reads one square.", line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StreamError {}
+
+impl From<io::Error> for StreamError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<MsqError> for StreamError {
+    fn from(e: MsqError) -> Self {
+        Self::Msq(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Construction {
+    Siamese,
+    Lux,
+    TruthGrid,
+}
+
+/// The cells of a magic square, computed on demand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RowSource {
+    n: usize,
+    construction: Construction,
+    symmetry: Symmetry,
+    complement: bool,
+}
+
+impl RowSource {
+    /// A square of order `n`, varied by `seed`.
+    pub fn new(n: usize, seed: u64) -> Result<Self, StreamError> {
+        if n == 0 || n == 2 || n > MAX_ORDER {
+            return Err(StreamError::InvalidOrder(n));
+        }
+        let construction = match n % 4 {
+            0 => Construction::TruthGrid,
+            2 => Construction::Lux,
+            _ => Construction::Siamese,
+        };
+        let mut rng = Lcg::new_with_seed(seed);
+        let symmetry = Symmetry::ALL[rng.next_range(0, 8)];
+        let complement = rng.next_u32() & 1 == 1;
+        Ok(Self { n, construction, symmetry, complement })
+    }
+
+    /// The order of the square.
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    /// The name of the construction: `siamese`, `lux` or `truth-grid`.
+    pub fn method(&self) -> &'static str {
+        match self.construction {
+            Construction::Siamese => "siamese",
+            Construction::Lux => "lux",
+            Construction::TruthGrid => "truth-grid",
+        }
+    }
+
+    /// The value in row `r` and column `c`.
+    pub fn cell(&self, r: usize, c: usize) -> u64 {
+        let (r, c) = self.symmetry.source(self.n, r, c);
+        let value = match self.construction {
+            Construction::Siamese => siamese(self.n as u64, r as u64, c as u64),
+            Construction::Lux => lux(self.n as u64, r as u64, c as u64),
+            Construction::TruthGrid => truth_grid(self.n as u64, r as u64, c as u64),
+        };
+        if self.complement { square(self.n) + 1 - value } else { value }
+    }
+
+    /// Fills `row` with the $n$ values of row `r`.
+    pub fn fill_row(&self, r: usize, row: &mut [u64]) {
+        for (c, value) in row.iter_mut().enumerate() {
+            *value = self.cell(r, c);
+        }
+    }
+}
+
+/// De la Loubère's Siamese square: 1 in the middle of the top row, then up and to the right,
+/// dropping down a row when the cell is taken.
+fn siamese(n: u64, r: u64, c: u64) -> u64 {
+    n * ((r + c + n.div_ceil(2)) % n) + (r + 2 * c + 1) % n + 1
+}
+
+/// Conway's LUX square: each cell of a Siamese square of order $2m + 1$ becomes a 2x2 block
+/// filled in the pattern of its letter, with $m + 1$ rows of L, one of U and $m - 1$ of X,
+/// and the middle U swapped with the L above it.
+fn lux(n: u64, r: u64, c: u64) -> u64 {
+    let m = (n - 2) / 4;
+    let (br, bc) = (r / 2, c / 2);
+    let letter = match (br, bc == m) {
+        (b, true) if b == m => b'U',
+        (b, true) if b == m + 1 => b'L',
+        (b, _) if b <= m => b'L',
+        (b, _) if b == m + 1 => b'U',
+        _ => b'X',
+    };
+    let block = match letter {
+        b'L' => [[4, 1], [2, 3]],
+        b'U' => [[1, 4], [2, 3]],
+        _ => [[1, 4], [3, 2]],
+    };
+    4 * (siamese(2 * m + 1, br, bc) - 1) + block[(r % 2) as usize][(c % 2) as usize]
+}
+
+/// The truth-grid square: count up from 1 in reading order, replacing the cells on the
+/// diagonals of each 4x4 block by their complement.
+fn truth_grid(n: u64, r: u64, c: u64) -> u64 {
+    let k = r * n + c;
+    if r % 4 == c % 4 || r % 4 + c % 4 == 3 { n * n - k } else { k + 1 }
+}
+
+/// $n^2$, computed in `u64` so that it cannot overflow a 32-bit `usize`.
+fn square(n: usize) -> u64 {
+    n as u64 * n as u64
+}
+
+/// Writes the square row by row as text, CSV, TSV or `.msq`, in the same layout as
+/// [`crate::output::format_square`].
+pub fn write<W: Write>(source: &RowSource, format: Format, metadata: &Metadata, out: W) -> Result<(), StreamError> {
+    let n = source.n;
+    let mut row = vec![0u64; n];
+    // Standard output is line-buffered, which would flush at every newline byte of `.msq` data.
+    let mut out = io::BufWriter::with_capacity(1 << 16, out);
+    if format == Format::Msq {
+        let header = Header::new(n, square(n), metadata.clone());
+        let mut writer = MsqWriter::new(&mut out, &header)?;
+        for r in 0..n {
+            source.fill_row(r, &mut row);
+            writer.write_row(&row)?;
+        }
+        writer.finish()?;
+        return Ok(());
+    }
+
+    let width = square(n).to_string().len() + 1;
+    let separator = match format {
+        Format::Text => "",
+        Format::Csv => ",",
+        Format::Tsv => "\t",
+        _ => return Err(StreamError::UnsupportedFormat(format)),
+    };
+    for r in 0..n {
+        source.fill_row(r, &mut row);
+        for (c, value) in row.iter().enumerate() {
+            match format {
+                Format::Text => write!(out, "{:width$}", value, width = width)?,
+                _ if c == 0 => write!(out, "{}", value)?,
+                _ => write!(out, "{}{}", separator, value)?,
+            }
+        }
+        out.write_all(b"\n")?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// What [`StreamValidator`] found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamReport {
+    /// The order of the square.
+    pub n: usize,
+    /// How many values are outside $1..n^2$.
+    pub out_of_range: u64,
+    /// How many values repeat an earlier one.
+    pub repeated: u64,
+    /// The lines that do not sum to the magic constant, with their sums.
+    pub wrong_lines: Vec<(Line, u64)>,
+}
+
+impl StreamReport {
+    /// Whether the square is a magic square.
+    pub fn is_magic(&self) -> bool {
+        self.out_of_range == 0 && self.repeated == 0 && self.wrong_lines.is_empty()
+    }
+}
+
+/// Checks a square fed to it one row at a time.
+///
+/// With $n$ rows of $n$ values, no value out of range and none repeated, every value of
+/// $1..n^2$ appears exactly once, so the bitmap needs no final scan.
+#[derive(Debug, Clone)]
+pub struct StreamValidator {
+    n: usize,
+    target: u64,
+    rows: usize,
+    columns: Vec<u64>,
+    diagonal: u64,
+    anti_diagonal: u64,
+    seen: Vec<u64>,
+    report: StreamReport,
+}
+
+impl StreamValidator {
+    /// A validator for a square of order `n`.
+    pub fn new(n: usize) -> Self {
+        Self {
+            n,
+            target: validator::magic_constant(n),
+            rows: 0,
+            columns: vec![0; n],
+            diagonal: 0,
+            anti_diagonal: 0,
+            seen: vec![0; square(n).div_ceil(64) as usize],
+            report: StreamReport { n, out_of_range: 0, repeated: 0, wrong_lines: Vec::new() },
+        }
+    }
+
+    /// Adds the next row.
+    ///
+    /// # Panics
+    ///
+    /// If the row does not hold $n$ values or all $n$ rows were already added.
+    pub fn push_row(&mut self, row: &[u64]) {
+        assert_eq!(row.len(), self.n, "a row of an order {} square has {} values", self.n, self.n);
+        assert!(self.rows < self.n, "all {} rows were already added", self.n);
+        let r = self.rows;
+        let mut sum = 0u64;
+        for (column, &v) in self.columns.iter_mut().zip(row) {
+            sum = sum.saturating_add(v);
+            *column = column.saturating_add(v);
+            if v == 0 || v > square(self.n) {
+                self.report.out_of_range += 1;
+                continue;
+            }
+            let (word, bit) = (((v - 1) / 64) as usize, (v - 1) % 64);
+            if self.seen[word] & (1 << bit) != 0 {
+                self.report.repeated += 1;
+            }
+            self.seen[word] |= 1 << bit;
+        }
+        self.diagonal = self.diagonal.saturating_add(row[r]);
+        self.anti_diagonal = self.anti_diagonal.saturating_add(row[self.n - 1 - r]);
+        if sum != self.target {
+            self.report.wrong_lines.push((Line::Row(r), sum));
+        }
+        self.rows += 1;
+    }
+
+    /// Rows added so far.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Checks the columns and diagonals once every row is in.
+    ///
+    /// # Panics
+    ///
+    /// If fewer than $n$ rows were added.
+    pub fn finish(mut self) -> StreamReport {
+        assert_eq!(self.rows, self.n, "an order {} square has {} rows", self.n, self.n);
+        for (c, &sum) in self.columns.iter().enumerate() {
+            if sum != self.target {
+                self.report.wrong_lines.push((Line::Column(c), sum));
+            }
+        }
+        for (line, sum) in [(Line::Diagonal, self.diagonal), (Line::AntiDiagonal, self.anti_diagonal)] {
+            if sum != self.target {
+                self.report.wrong_lines.push((line, sum));
+            }
+        }
+        self.report
+    }
+}
+
+/// Checks a single square read row by row from `.msq` data or from text with one row per
+/// line (values separated by spaces, commas or tabs), told apart by the first byte.
+pub fn validate<R: BufRead>(mut input: R) -> Result<StreamReport, StreamError> {
+    if input.fill_buf()?.first() == Some(&msq::MAGIC[0]) {
+        validate_msq(input)
+    } else {
+        validate_text(input)
+    }
+}
+
+/// Checks a single `.msq` square, including its checksum.
+pub fn validate_msq<R: Read>(input: R) -> Result<StreamReport, StreamError> {
+    let mut reader = MsqReader::new(input)?;
+    let n = reader.header().n;
+    if n == 0 || n > MAX_ORDER {
+        return Err(StreamError::InvalidOrder(n));
+    }
+    let mut validator = StreamValidator::new(n);
+    let mut row = vec![0u64; n];
+    for _ in 0..n {
+        reader.read_row(&mut row)?;
+        validator.push_row(&row);
+    }
+    Ok(validator.finish())
+}
+
+/// Checks a single square of text, one row per line. The order is the number of values on
+/// the first non-blank line; blank lines may only come before and after the square.
+pub fn validate_text<R: BufRead>(input: R) -> Result<StreamReport, StreamError> {
+    let mut validator: Option<StreamValidator> = None;
+    let mut row = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        let number = i + 1;
+        row.clear();
+        for token in line.split(|ch: char| ch.is_whitespace() || ch == ',').filter(|t| !t.is_empty()) {
+            let value = token.parse().map_err(|_| StreamError::InvalidNumber { line: number, token: token.to_string() })?;
+            row.push(value);
+        }
+
+        let validator = match &mut validator {
+            Some(v) if v.rows() == v.n => {
+                if row.is_empty() {
+                    continue;
+                }
+                return Err(StreamError::TrailingData { line: number });
+            }
+            Some(v) => v,
+            None if row.is_empty() => continue,
+            None if row.len() > MAX_ORDER => return Err(StreamError::InvalidOrder(row.len())),
+            None => validator.insert(StreamValidator::new(row.len())),
+        };
+        if row.len() != validator.n {
+            if row.is_empty() {
+                return Err(StreamError::MissingRows { n: validator.n, rows: validator.rows() });
+            }
+            return Err(StreamError::RowLength { line: number, n: validator.n, values: row.len() });
+        }
+        validator.push_row(&row);
+    }
+
+    match validator {
+        Some(v) if v.rows() == v.n => Ok(v.finish()),
+        Some(v) => Err(StreamError::MissingRows { n: v.n, rows: v.rows() }),
+        None => Err(StreamError::MissingRows { n: 0, rows: 0 }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output;
+
+    fn grid(source: &RowSource) -> Vec<u32> {
+        let n = source.n();
+        (0..n * n).map(|i| source.cell(i / n, i % n) as u32).collect()
+    }
+
+    #[test]
+    fn computes_magic_squares_by_formula() {
+        for n in [1, 3, 4, 5, 6, 8, 10, 12, 14, 17, 18, 20, 31, 50, 64] {
+            for seed in 0..4 {
+                let source = RowSource::new(n, seed).unwrap();
+                assert!(validator::check_magic_properties(&grid(&source), n), "order {} seed {}", n, seed);
+            }
+        }
+        // The unvaried Siamese square of order 3 is the Lo Shu turned upside down.
+        let lo_shu = RowSource { n: 3, construction: Construction::Siamese, symmetry: Symmetry::Identity, complement: false };
+        assert_eq!(grid(&lo_shu), [8, 1, 6, 3, 5, 7, 4, 9, 2]);
+        assert_eq!(RowSource::new(6, 0).unwrap().method(), "lux");
+
+        // Beyond order 65535 the values no longer fit in a u32.
+        for n in [70_001, 70_002, 70_004] {
+            let source = RowSource::new(n, 1).unwrap();
+            let mut row = vec![0; n];
+            for r in [0, n / 2, n - 1] {
+                source.fill_row(r, &mut row);
+                assert_eq!(row.iter().sum::<u64>(), validator::magic_constant(n), "order {} row {}", n, r);
+            }
+            let column: u64 = (0..n).map(|r| source.cell(r, 7)).sum();
+            assert_eq!(column, validator::magic_constant(n));
+        }
+        assert!(RowSource::new(2, 0).is_err() && RowSource::new(MAX_ORDER + 1, 0).is_err());
+    }
+
+    #[test]
+    fn writes_the_same_bytes_as_the_whole_grid() {
+        let source = RowSource::new(10, 3).unwrap();
+        let meta = Metadata { method: Some(source.method().to_string()), seed: Some(3) };
+        for format in [Format::Text, Format::Csv, Format::Tsv, Format::Msq] {
+            let mut streamed = Vec::new();
+            write(&source, format, &meta, &mut streamed).unwrap();
+            let mut whole = output::format_square(&grid(&source), 10, format, &meta);
+            if format == Format::Msq {
+                // Only the verified flag and the checksum differ.
+                whole[10] &= !2;
+                let end = whole.len() - 4;
+                assert_eq!(streamed[..end], whole[..end]);
+            } else {
+                assert_eq!(streamed, whole);
+            }
+        }
+        assert!(matches!(write(&source, Format::Json, &meta, Vec::new()), Err(StreamError::UnsupportedFormat(Format::Json))));
+    }
+
+    #[test]
+    fn validates_streamed_input() {
+        let source = RowSource::new(12, 9).unwrap();
+        for format in [Format::Text, Format::Csv, Format::Msq] {
+            let mut bytes = Vec::new();
+            write(&source, format, &Metadata::default(), &mut bytes).unwrap();
+            let report = validate(&bytes[..]).unwrap();
+            assert!(report.is_magic(), "{:?}", format);
+        }
+
+        // Swapping 4 and 2 in the top row breaks two columns and both diagonals but keeps 1..9.
+        let report = validate(&b"\n2 9 4\n3 5 7\n8 1 6\n\n"[..]).unwrap();
+        assert_eq!((report.out_of_range, report.repeated), (0, 0));
+        let expected = [(Line::Column(0), 13), (Line::Column(2), 17), (Line::Diagonal, 13), (Line::AntiDiagonal, 17)];
+        assert_eq!(report.wrong_lines, expected);
+        let report = validate(&b"4,9,2\n3,5,7\n8,9,0\n"[..]).unwrap();
+        assert_eq!((report.out_of_range, report.repeated), (1, 1));
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert!(matches!(validate(&b"1 2\n3\n"[..]), Err(StreamError::RowLength { line: 2, n: 2, values: 1 })));
+        assert!(matches!(validate(&b"1 2\n3 4\n\n5\n"[..]), Err(StreamError::TrailingData { line: 4 })));
+        assert!(matches!(validate(&b"1 2\n"[..]), Err(StreamError::MissingRows { n: 2, rows: 1 })));
+        assert!(matches!(validate(&b"1 x\n"[..]), Err(StreamError::InvalidNumber { line: 1, .. })));
+        let mut msq = Vec::new();
+        write(&RowSource::new(5, 0).unwrap(), Format::Msq, &Metadata::default(), &mut msq).unwrap();
+        assert!(matches!(validate(&msq[..msq.len() - 2]), Err(StreamError::Msq(MsqError::Truncated))));
+        msq[12..16].copy_from_slice(&(MAX_ORDER as u32 + 1).to_le_bytes());
+        assert!(matches!(validate(&msq[..]), Err(StreamError::InvalidOrder(n)) if n == MAX_ORDER + 1));
+    }
+}
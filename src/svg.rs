@@ -0,0 +1,228 @@
+//! SVG rendering of squares.
+//!
+//! The drawing follows the web app's canvas: a dark grid with light numbers, sized so that the
+//! largest value $n^2$ fits its cell. A row, column or diagonal can be shaded, and the
+//! square's magic line can be drawn over it: the path through the centres of the cells
+//! holding $1, 2, ..., n^2$ in turn, as in Claude Bragdon's architectural ornaments. Its
+//! start is marked with a filled dot and its end with an open one.
+
+use std::fmt;
+use std::fmt::Write;
+
+use crate::validator::Line;
+
+/// Background of the square, `--card-bg` in the web app.
+const BACKGROUND: &str = "#1e293b";
+/// Grid lines, `--border-color`.
+const BORDER: &str = "#334155";
+/// Numbers, `--text-primary`.
+const TEXT: &str = "#f8fafc";
+/// Shading of the highlighted line, `--accent-color`.
+const ACCENT: &str = "#38bdf8";
+/// The magic line, chosen to stand out from the accent.
+const MAGIC_LINE: &str = "#f472b6";
+
+/// What [`render`] draws.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SvgOptions {
+    /// Side of a cell in pixels.
+    pub cell_size: u32,
+    /// Whether the numbers are written in the cells.
+    pub numbers: bool,
+    /// A row, column or diagonal to shade.
+    pub highlight: Option<Line>,
+    /// Whether to draw the magic line through $1, 2, ..., n^2$.
+    pub magic_line: bool,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self { cell_size: 40, numbers: true, highlight: None, magic_line: false }
+    }
+}
+
+/// Errors returned by [`render`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SvgError {
+    /// The grid does not hold $n^2$ values.
+    WrongLength { n: usize, len: usize },
+    /// The highlighted row or column is outside the square.
+    LineOutOfRange { line: Line, n: usize },
+    /// Cells must be at least one pixel wide.
+    InvalidCellSize,
+}
+
+impl fmt::Display for SvgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WrongLength { n, len } => write!(f, "An order {} square has {} cells, not {}.", n, n * n, len),
+            Self::LineOutOfRange { line, n } => write!(f, "There is no {} in a square of order {}.", line, n),
+            Self::InvalidCellSize => write!(f, "The cell size must be at least 1 pixel."),
+        }
+    }
+}
+
+impl std::error::Error for SvgError {}
+
+/// Renders a flat $n \times n$ grid as a standalone SVG document.
+pub fn render(grid: &[u32], n: usize, options: &SvgOptions) -> Result<String, SvgError> {
+    if grid.len() != n * n {
+        return Err(SvgError::WrongLength { n, len: grid.len() });
+    }
+    if options.cell_size == 0 {
+        return Err(SvgError::InvalidCellSize);
+    }
+    if let Some(line @ (Line::Row(i) | Line::Column(i))) = options.highlight
+        && i >= n
+    {
+        return Err(SvgError::LineOutOfRange { line, n });
+    }
+
+    let cell = options.cell_size as f64;
+    let size = n as f64 * cell;
+    let centre = |i: usize| ((i % n) as f64 * cell + cell / 2.0, (i / n) as f64 * cell + cell / 2.0);
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{s}\" height=\"{s}\" viewBox=\"0 0 {s} {s}\">\n",
+        s = num(size)
+    );
+    let _ = writeln!(svg, "<rect width=\"{s}\" height=\"{s}\" fill=\"{}\"/>", BACKGROUND, s = num(size));
+
+    if let Some(line) = options.highlight.filter(|_| n > 0) {
+        let _ = writeln!(svg, "<g fill=\"{}\" fill-opacity=\"0.3\">", ACCENT);
+        for i in line.cells(n) {
+            let (x, y) = centre(i);
+            let _ = writeln!(
+                svg,
+                "<rect x=\"{}\" y=\"{}\" width=\"{c}\" height=\"{c}\"/>",
+                num(x - cell / 2.0),
+                num(y - cell / 2.0),
+                c = num(cell)
+            );
+        }
+        svg += "</g>\n";
+    }
+
+    // One path for the whole grid: n + 1 horizontal and n + 1 vertical lines.
+    let mut grid_path = String::new();
+    for k in 0..=n {
+        let at = num(k as f64 * cell);
+        let _ = write!(grid_path, "M0 {at}H{s}M{at} 0V{s}", s = num(size));
+    }
+    let _ = writeln!(svg, "<path d=\"{}\" stroke=\"{}\" stroke-width=\"1\" fill=\"none\"/>", grid_path, BORDER);
+
+    if options.numbers && n > 0 {
+        let _ = writeln!(
+            svg,
+            "<g font-family=\"Inter, sans-serif\" font-weight=\"700\" font-size=\"{}\" fill=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\">",
+            num(font_size(n, cell)),
+            TEXT
+        );
+        for (i, value) in grid.iter().enumerate() {
+            let (x, y) = centre(i);
+            let _ = writeln!(svg, "<text x=\"{}\" y=\"{}\">{}</text>", num(x), num(y), value);
+        }
+        svg += "</g>\n";
+    }
+
+    if options.magic_line && n > 0 {
+        let mut order: Vec<usize> = (0..grid.len()).collect();
+        order.sort_by_key(|&i| grid[i]);
+        let width = num((cell / 12.0).max(1.0));
+        let mut path = String::new();
+        for (k, &i) in order.iter().enumerate() {
+            let (x, y) = centre(i);
+            let _ = write!(path, "{}{} {}", if k == 0 { "M" } else { "L" }, num(x), num(y));
+        }
+        let _ = writeln!(
+            svg,
+            "<path d=\"{}\" stroke=\"{}\" stroke-width=\"{}\" stroke-linejoin=\"round\" fill=\"none\"/>",
+            path, MAGIC_LINE, width
+        );
+        let radius = num(cell / 8.0);
+        let (x, y) = centre(order[0]);
+        let _ = writeln!(svg, "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\"/>", num(x), num(y), radius, MAGIC_LINE);
+        let (x, y) = centre(order[order.len() - 1]);
+        let _ = writeln!(
+            svg,
+            "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\" stroke=\"{}\" stroke-width=\"{}\"/>",
+            num(x),
+            num(y),
+            radius,
+            BACKGROUND,
+            MAGIC_LINE,
+            width
+        );
+    }
+
+    svg += "</svg>\n";
+    Ok(svg)
+}
+
+/// Font size that fits the digits of $n^2$ in a cell: the web app's 18px in a 40px cell,
+/// shrunk for wider numbers (bold digits are about 0.6em wide).
+fn font_size(n: usize, cell: f64) -> f64 {
+    let digits = (n * n).to_string().len() as f64;
+    (cell * 0.45).min(cell * 0.8 / (0.6 * digits))
+}
+
+/// A coordinate rounded to a tenth of a pixel, without trailing zeros.
+fn num(x: f64) -> String {
+    let rounded = (x * 10.0).round() / 10.0;
+    format!("{}", rounded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog;
+
+    const LO_SHU: &[u32] = catalog::LO_SHU.grid;
+
+    #[test]
+    fn renders_cells_and_numbers() {
+        let svg = render(LO_SHU, 3, &SvgOptions::default()).unwrap();
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"120\" height=\"120\""));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<text ").count(), 9);
+        assert!(svg.contains("<text x=\"20\" y=\"20\">4</text>"));
+        assert!(svg.contains("font-size=\"18\""));
+        assert!(!svg.contains("<circle"));
+
+        let plain = render(LO_SHU, 3, &SvgOptions { numbers: false, cell_size: 10, ..SvgOptions::default() }).unwrap();
+        assert!(!plain.contains("<text") && plain.contains("width=\"30\""));
+    }
+
+    #[test]
+    fn shrinks_numbers_for_large_orders() {
+        assert_eq!(font_size(3, 40.0), 18.0);
+        assert!(font_size(100, 40.0) < font_size(31, 40.0));
+        assert!(font_size(1000, 40.0) * 0.6 * 7.0 <= 40.0 * 0.8);
+    }
+
+    #[test]
+    fn draws_highlight_and_magic_line() {
+        let options = SvgOptions { highlight: Some(Line::AntiDiagonal), magic_line: true, ..SvgOptions::default() };
+        let svg = render(LO_SHU, 3, &options).unwrap();
+        // The anti-diagonal holds 2, 5 and 8.
+        assert!(svg.contains("<rect x=\"80\" y=\"0\" width=\"40\" height=\"40\"/>"));
+        assert!(svg.contains("<rect x=\"0\" y=\"80\" width=\"40\" height=\"40\"/>"));
+        // Lo Shu's magic line starts at 1 (bottom middle), then 2 (top right), 3 (middle left).
+        assert!(svg.contains("d=\"M60 100L100 20L20 60"));
+        assert_eq!(svg.matches("<circle").count(), 2);
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        let options = SvgOptions { highlight: Some(Line::Row(3)), ..SvgOptions::default() };
+        assert_eq!(render(LO_SHU, 3, &options), Err(SvgError::LineOutOfRange { line: Line::Row(3), n: 3 }));
+        assert_eq!(render(LO_SHU, 4, &SvgOptions::default()), Err(SvgError::WrongLength { n: 4, len: 9 }));
+        let options = SvgOptions { cell_size: 0, ..SvgOptions::default() };
+        assert_eq!(render(LO_SHU, 3, &options), Err(SvgError::InvalidCellSize));
+
+        assert_eq!("row:2".parse(), Ok(Line::Row(1)));
+        assert_eq!("Column 3".parse(), Ok(Line::Column(2)));
+        assert_eq!("anti-diagonal".parse(), Ok(Line::AntiDiagonal));
+        assert_eq!("row 0".parse::<Line>(), Err(()));
+    }
+}
@@ -0,0 +1,291 @@
+//! Composable, invertible transforms on flat $n \times n$ grids.
+//!
+//! A [`Transform`] moves cells around (a permutation of positions) and optionally replaces
+//! every value $x$ by its complement $n^2 + 1 - x$. Every transform built here maps magic
+//! squares to magic squares, except for the cyclic shifts which only preserve pandiagonal
+//! squares (squares whose broken diagonals also sum to the magic constant).
+//!
+//! Transforms compose with [`Transform::then`] and undo with [`Transform::inverse`].
+//!
+//! [`Symmetry`] names the 8 rotations and reflections as maps on coordinates, for callers
+//! that visit cells one at a time and cannot afford a table of $n^2$ positions.
+
+/// One of the 8 symmetries of the square: 4 rotations and 4 reflections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symmetry {
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipHorizontal,
+    FlipVertical,
+    Transpose,
+    AntiTranspose,
+}
+
+impl Symmetry {
+    /// Every symmetry, the identity first.
+    pub const ALL: [Symmetry; 8] = [
+        Self::Identity,
+        Self::Rotate90,
+        Self::Rotate180,
+        Self::Rotate270,
+        Self::FlipHorizontal,
+        Self::FlipVertical,
+        Self::Transpose,
+        Self::AntiTranspose,
+    ];
+
+    /// The input cell that ends up at row `r` and column `c` of an order-`n` grid.
+    pub fn source(self, n: usize, r: usize, c: usize) -> (usize, usize) {
+        let last = n - 1;
+        match self {
+            Self::Identity => (r, c),
+            Self::Rotate90 => (last - c, r),
+            Self::Rotate180 => (last - r, last - c),
+            Self::Rotate270 => (c, last - r),
+            Self::FlipHorizontal => (r, last - c),
+            Self::FlipVertical => (last - r, c),
+            Self::Transpose => (c, r),
+            Self::AntiTranspose => (last - c, last - r),
+        }
+    }
+
+    /// The same symmetry as a [`Transform`], to compose with others.
+    pub fn transform(self, n: usize) -> Transform {
+        Transform::from_fn(n, |r, c| self.source(n, r, c))
+    }
+}
+
+/// A rearrangement of the cells of an order-$n$ grid, optionally followed by the complement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transform {
+    n: usize,
+    /// `source[i]` is the input cell that ends up at output cell `i`.
+    source: Vec<usize>,
+    /// Whether values are replaced by $n^2 + 1 - x$.
+    complement: bool,
+}
+
+impl Transform {
+    /// Builds a positional transform where output cell `(r, c)` takes the input cell `f(r, c)`.
+    fn from_fn(n: usize, f: impl Fn(usize, usize) -> (usize, usize)) -> Self {
+        let mut source = Vec::with_capacity(n * n);
+        for r in 0..n {
+            for c in 0..n {
+                let (sr, sc) = f(r, c);
+                source.push(sr * n + sc);
+            }
+        }
+        Self { n, source, complement: false }
+    }
+
+    /// The transform that leaves the grid unchanged.
+    pub fn identity(n: usize) -> Self {
+        Self { n, source: (0..n * n).collect(), complement: false }
+    }
+
+    /// Rotates a quarter turn clockwise.
+    pub fn rotate90(n: usize) -> Self {
+        Symmetry::Rotate90.transform(n)
+    }
+
+    /// Rotates a half turn.
+    pub fn rotate180(n: usize) -> Self {
+        Symmetry::Rotate180.transform(n)
+    }
+
+    /// Rotates a quarter turn counter-clockwise.
+    pub fn rotate270(n: usize) -> Self {
+        Symmetry::Rotate270.transform(n)
+    }
+
+    /// Mirrors left to right (reverses the order of the columns).
+    pub fn flip_horizontal(n: usize) -> Self {
+        Symmetry::FlipHorizontal.transform(n)
+    }
+
+    /// Mirrors top to bottom (reverses the order of the rows).
+    pub fn flip_vertical(n: usize) -> Self {
+        Symmetry::FlipVertical.transform(n)
+    }
+
+    /// Mirrors about the main diagonal.
+    pub fn transpose(n: usize) -> Self {
+        Symmetry::Transpose.transform(n)
+    }
+
+    /// Mirrors about the anti-diagonal.
+    pub fn anti_transpose(n: usize) -> Self {
+        Symmetry::AntiTranspose.transform(n)
+    }
+
+    /// Replaces every value $x$ by $n^2 + 1 - x$, leaving positions unchanged.
+    pub fn complement(n: usize) -> Self {
+        Self { complement: true, ..Self::identity(n) }
+    }
+
+    /// Exchanges the symmetric pairs `i` and `j`: rows and columns `i` and `j` are swapped,
+    /// and so are rows and columns `n-1-i` and `n-1-j`. Both indices must be below `n / 2`.
+    pub fn swap_pairs(n: usize, i: usize, j: usize) -> Self {
+        assert!(i < n / 2 && j < n / 2, "pair indices must be below n / 2");
+        let mut perm: Vec<usize> = (0..n).collect();
+        perm.swap(i, j);
+        perm.swap(n - 1 - i, n - 1 - j);
+        Self::from_fn(n, |r, c| (perm[r], perm[c]))
+    }
+
+    /// Swaps row `i` with row `n-1-i` and column `i` with column `n-1-i`.
+    pub fn swap_within_pair(n: usize, i: usize) -> Self {
+        assert!(i < n / 2, "pair index must be below n / 2");
+        let mut perm: Vec<usize> = (0..n).collect();
+        perm.swap(i, n - 1 - i);
+        Self::from_fn(n, |r, c| (perm[r], perm[c]))
+    }
+
+    /// Applies the same permutation to rows and columns: output `(r, c)` takes input
+    /// `(perm[r], perm[c])`.
+    ///
+    /// Returns `None` unless the permutation commutes with the reflection $k \mapsto n-1-k$,
+    /// which is exactly when it keeps both diagonals on the diagonals.
+    pub fn symmetric_permutation(perm: &[usize]) -> Option<Self> {
+        let n = perm.len();
+        let mut seen = vec![false; n];
+        for (k, &p) in perm.iter().enumerate() {
+            if p >= n || seen[p] || perm[n - 1 - k] != n - 1 - p {
+                return None;
+            }
+            seen[p] = true;
+        }
+        Some(Self::from_fn(n, |r, c| (perm[r], perm[c])))
+    }
+
+    /// Shifts rows down by `dr` and columns right by `dc`, wrapping around the edges.
+    /// Only pandiagonal squares stay magic under this transform.
+    pub fn cyclic_shift(n: usize, dr: usize, dc: usize) -> Self {
+        let (dr, dc) = (dr % n.max(1), dc % n.max(1));
+        Self::from_fn(n, |r, c| ((r + n - dr) % n, (c + n - dc) % n))
+    }
+
+    /// The 8 symmetries of the square: the 4 rotations, each followed by a transpose.
+    /// The first element is the identity.
+    pub fn dihedral(n: usize) -> Vec<Self> {
+        let rotations = [Self::identity(n), Self::rotate90(n), Self::rotate180(n), Self::rotate270(n)];
+        rotations
+            .into_iter()
+            .flat_map(|rot| {
+                let mirrored = rot.then(&Self::transpose(n));
+                [rot, mirrored]
+            })
+            .collect()
+    }
+
+    /// The order of the grids this transform applies to.
+    pub fn order(&self) -> usize {
+        self.n
+    }
+
+    /// Returns the transform that applies `self` first and then `next`.
+    pub fn then(&self, next: &Transform) -> Transform {
+        assert_eq!(self.n, next.n, "cannot compose transforms of different orders");
+        Transform {
+            n: self.n,
+            source: next.source.iter().map(|&i| self.source[i]).collect(),
+            complement: self.complement != next.complement,
+        }
+    }
+
+    /// Returns the transform that undoes `self`.
+    pub fn inverse(&self) -> Transform {
+        let mut source = vec![0; self.source.len()];
+        for (dst, &src) in self.source.iter().enumerate() {
+            source[src] = dst;
+        }
+        Transform { n: self.n, source, complement: self.complement }
+    }
+
+    /// Applies the transform to a flat grid of the same order.
+    pub fn apply(&self, grid: &[u32]) -> Vec<u32> {
+        assert_eq!(grid.len(), self.source.len(), "grid does not match the transform order");
+        let top = (self.n * self.n) as u32 + 1;
+        self.source
+            .iter()
+            .map(|&src| if self.complement { top - grid[src] } else { grid[src] })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validator::check_magic_properties;
+
+    /// An order-5 pandiagonal square.
+    const PANDIAGONAL: [u32; 25] = [
+        1, 15, 24, 8, 17,
+        23, 7, 16, 5, 14,
+        20, 4, 13, 22, 6,
+        12, 21, 10, 19, 3,
+        9, 18, 2, 11, 25,
+    ];
+
+    #[test]
+    fn group_identities() {
+        let n = 5;
+        let r90 = Transform::rotate90(n);
+        assert_eq!(r90.then(&r90), Transform::rotate180(n));
+        assert_eq!(r90.then(&r90).then(&r90), Transform::rotate270(n));
+        assert_eq!(r90.inverse(), Transform::rotate270(n));
+        assert_eq!(
+            Transform::transpose(n).then(&Transform::rotate180(n)),
+            Transform::anti_transpose(n)
+        );
+        assert_eq!(
+            Transform::complement(n).then(&Transform::complement(n)),
+            Transform::identity(n)
+        );
+        let mixed = Transform::swap_pairs(n, 0, 1).then(&Transform::cyclic_shift(n, 2, 3));
+        assert_eq!(mixed.then(&mixed.inverse()), Transform::identity(n));
+    }
+
+    #[test]
+    fn transforms_preserve_magic() {
+        let n = 5;
+        let mut transforms = Transform::dihedral(n);
+        transforms.push(Transform::complement(n));
+        transforms.push(Transform::swap_pairs(n, 0, 1));
+        transforms.push(Transform::swap_within_pair(n, 1));
+        transforms.push(Transform::cyclic_shift(n, 1, 0));
+        transforms.push(Transform::cyclic_shift(n, 3, 2));
+        for t in &transforms {
+            assert!(check_magic_properties(&t.apply(&PANDIAGONAL), n), "{:?}", t);
+        }
+    }
+
+    #[test]
+    fn symmetries_match_the_named_transforms() {
+        let n = 5;
+        let named = [
+            Transform::identity(n),
+            Transform::rotate90(n),
+            Transform::rotate180(n),
+            Transform::rotate270(n),
+            Transform::flip_horizontal(n),
+            Transform::flip_vertical(n),
+            Transform::transpose(n),
+            Transform::anti_transpose(n),
+        ];
+        for (symmetry, transform) in Symmetry::ALL.into_iter().zip(&named) {
+            assert_eq!(symmetry.transform(n), *transform, "{:?}", symmetry);
+        }
+        // A quarter turn clockwise moves the bottom-left corner to the top-left.
+        assert_eq!(Symmetry::Rotate90.source(n, 0, 0), (4, 0));
+    }
+
+    #[test]
+    fn rejects_asymmetric_permutations() {
+        assert!(Transform::symmetric_permutation(&[4, 3, 2, 1, 0]).is_some());
+        assert!(Transform::symmetric_permutation(&[1, 0, 2, 3, 4]).is_none());
+        assert!(Transform::symmetric_permutation(&[0, 0, 2, 4, 4]).is_none());
+    }
+}
@@ -1,10 +1,5 @@
-/// Verifies that a given sequence of vectors forms a valid magic square.
-///
-/// A magic square of order $n$ must satisfy:
-/// 1. The sum of every row is the magic constant $M = n(n^2+1)/2$.
-/// 2. The sum of every column is $M$.
-/// 3. The sum of both main diagonals is $M$.
-/// 4. All numbers from $1$ to $n^2$ appear exactly once.
+use std::collections::HashSet;
+
 /// Verifies that a given sequence of numbers forms a valid magic square.
 /// The input is a flat vector representing an $n \times n$ grid.
 ///
@@ -14,42 +9,745 @@
 /// 3. The sum of both main diagonals is $M$.
 /// 4. All numbers from $1$ to $n^2$ appear exactly once.
 pub fn check_magic_properties(grid: &[u32], n: usize) -> bool {
+    // Check the sums first: they fail fast on most non-magic grids.
+    check_magic_sums(grid, n) && is_permutation(grid)
+}
+
+/// Whether every row, column and both diagonals of a flat $n \times n$ grid sum to the magic
+/// constant $n(n^2+1)/2$, without checking which values the grid holds.
+///
+/// Everything is summed in a single row-major pass: each row is added to running column
+/// sums rather than striding down every column, which would touch a new cache line per cell
+/// once a row outgrows the cache.
+pub fn check_magic_sums(grid: &[u32], n: usize) -> bool {
     if n == 0 || grid.len() != n * n { return false; }
-    
-    // Calculate the Magic Constant: M = n * (n^2 + 1) / 2
-    let magic_constant = (n as u32 * ((n * n) as u32 + 1)) / 2;
 
-    // Check Rows
-    for r in 0..n {
-        let start = r * n;
-        let end = start + n;
-        let sum: u32 = grid[start..end].iter().sum();
-        if sum != magic_constant { return false; }
+    // Sums are taken in u64 so that arbitrary input values cannot overflow them.
+    let target = magic_constant(n);
+    LineSums::of_rows(grid, n, 0..n, target).is_some_and(|sums| sums.is_magic(target))
+}
+
+/// [`check_magic_properties`] with the rows split into bands between `threads` threads (all
+/// cores for 0). Each band sums its rows and its share of the columns and diagonals, and the
+/// partial sums are added up at the end.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn check_magic_properties_parallel(grid: &[u32], n: usize, threads: usize) -> bool {
+    check_magic_sums_parallel(grid, n, threads) && is_permutation_parallel(grid, threads)
+}
+
+/// [`check_magic_sums`] with the rows split into bands between `threads` threads (all cores
+/// for 0).
+#[cfg(not(target_arch = "wasm32"))]
+pub fn check_magic_sums_parallel(grid: &[u32], n: usize, threads: usize) -> bool {
+    let threads = thread_count(threads).min(n.max(1));
+    if threads == 1 || grid.len() < PARALLEL_CELLS {
+        return check_magic_sums(grid, n);
+    }
+    if grid.len() != n * n { return false; }
+
+    let target = magic_constant(n);
+    let bands: Vec<Option<LineSums>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|t| {
+                let rows = t * n / threads..(t + 1) * n / threads;
+                scope.spawn(move || LineSums::of_rows(grid, n, rows, target))
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().expect("validation worker panicked")).collect()
+    });
+    let Some(sums) = bands.into_iter().reduce(|a, b| Some(a?.merge(&b?))).flatten() else { return false; };
+    sums.is_magic(target)
+}
+
+/// Below this many cells, threads cost more than they save.
+#[cfg(not(target_arch = "wasm32"))]
+const PARALLEL_CELLS: usize = 1 << 20;
+
+/// `threads`, or the number of cores for 0.
+#[cfg(not(target_arch = "wasm32"))]
+fn thread_count(threads: usize) -> usize {
+    if threads > 0 { threads } else { std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4) }
+}
+
+/// Cells summed side by side by [`add_row`]. Eight `u64` accumulators fill two AVX2 or four
+/// SSE2/NEON registers, and independent lanes let the compiler vectorize the loop.
+const LANES: usize = 8;
+
+/// Column and diagonal sums of a band of rows.
+struct LineSums {
+    columns: Vec<u64>,
+    diagonal: u64,
+    anti_diagonal: u64,
+}
+
+impl LineSums {
+    /// Sums the rows `rows` of the grid, or returns `None` as soon as one of them does not
+    /// sum to `target`.
+    fn of_rows(grid: &[u32], n: usize, rows: std::ops::Range<usize>, target: u64) -> Option<Self> {
+        let mut sums = Self { columns: vec![0; n], diagonal: 0, anti_diagonal: 0 };
+        for r in rows {
+            let row = &grid[r * n..(r + 1) * n];
+            if add_row(&mut sums.columns, row) != target { return None; }
+            sums.diagonal += row[r] as u64;
+            sums.anti_diagonal += row[n - 1 - r] as u64;
+        }
+        Some(sums)
+    }
+
+    /// The sums of two bands together.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn merge(mut self, other: &Self) -> Self {
+        for (a, b) in self.columns.iter_mut().zip(&other.columns) {
+            *a += b;
+        }
+        self.diagonal += other.diagonal;
+        self.anti_diagonal += other.anti_diagonal;
+        self
+    }
+
+    /// Whether the columns and diagonals all sum to `target`.
+    fn is_magic(&self, target: u64) -> bool {
+        self.diagonal == target && self.anti_diagonal == target && self.columns.iter().all(|&sum| sum == target)
+    }
+}
+
+/// Adds a row to the running column sums and returns the sum of the row.
+fn add_row(columns: &mut [u64], row: &[u32]) -> u64 {
+    let mut lanes = [0u64; LANES];
+    let mut column_chunks = columns.chunks_exact_mut(LANES);
+    let mut cell_chunks = row.chunks_exact(LANES);
+    for (sums, cells) in (&mut column_chunks).zip(&mut cell_chunks) {
+        for ((lane, sum), &v) in lanes.iter_mut().zip(sums).zip(cells) {
+            *lane += v as u64;
+            *sum += v as u64;
+        }
+    }
+    let mut total: u64 = lanes.iter().sum();
+    for (sum, &v) in column_chunks.into_remainder().iter_mut().zip(cell_chunks.remainder()) {
+        *sum += v as u64;
+        total += v as u64;
+    }
+    total
+}
+
+/// Verifies that a magic square is also pandiagonal: every broken diagonal, in both
+/// directions and wrapping around the edges, sums to the magic constant.
+pub fn is_pandiagonal(grid: &[u32], n: usize) -> bool {
+    if !check_magic_properties(grid, n) { return false; }
+
+    let magic_constant = magic_constant(n);
+    for k in 0..n {
+        let down: u64 = (0..n).map(|i| grid[i * n + (i + k) % n] as u64).sum();
+        let up: u64 = (0..n).map(|i| grid[i * n + (n + k - i) % n] as u64).sum();
+        if down != magic_constant || up != magic_constant { return false; }
+    }
+    true
+}
+
+/// Verifies that a magic square stays magic when its entries are raised to each power
+/// $2..=$ `power`: for every such $k$, each row, column and main diagonal has the same sum of
+/// $k$-th powers, $\frac{1}{n}\sum_{v=1}^{n^2} v^k$. Sums are taken in `u128`.
+pub fn is_multimagic(grid: &[u32], n: usize, power: u32) -> bool {
+    if !check_magic_properties(grid, n) { return false; }
+
+    let cells = (n * n) as u128;
+    (2..=power).all(|k| {
+        let total: u128 = (1..=cells).map(|v| v.pow(k)).sum();
+        let target = total / n as u128;
+        Line::all(n).into_iter().all(|line| line.cells(n).map(|i| (grid[i] as u128).pow(k)).sum::<u128>() == target)
+    })
+}
+
+/// Verifies that a grid is a Franklin square: a semi-magic square of order $n \equiv 0 \pmod 4$
+/// whose bent diagonals, half rows and half columns, and $2 \times 2$ blocks all have their
+/// share of the magic constant. The main diagonals need not be magic.
+pub fn is_franklin(grid: &[u32], n: usize) -> bool {
+    check_semi_magic_properties(grid, n) && has_bent_diagonals(grid, n) && has_half_line_sums(grid, n) && has_block_sums(grid, n)
+}
+
+/// Verifies that every bent diagonal sums to the magic constant. A bent diagonal crosses the
+/// square in a V: across the columns it visits rows $k, k+1, ..., k + n/2 - 1$ and back to $k$
+/// (wrapping around the edges). The V may point down, up, right or left, from any starting
+/// row or column $k$, giving $4n$ bent diagonals.
+pub fn has_bent_diagonals(grid: &[u32], n: usize) -> bool {
+    if !n.is_multiple_of(2) || grid.len() != n * n { return false; }
+
+    let target = magic_constant(n);
+    for k in 0..n {
+        let (mut down, mut up, mut right, mut left) = (0, 0, 0, 0);
+        for j in 0..n {
+            let depth = j.min(n - 1 - j);
+            let (ahead, behind) = ((k + depth) % n, (k + n - depth) % n);
+            down += grid[ahead * n + j] as u64;
+            up += grid[behind * n + j] as u64;
+            right += grid[j * n + ahead] as u64;
+            left += grid[j * n + behind] as u64;
+        }
+        if [down, up, right, left].iter().any(|&sum| sum != target) { return false; }
     }
+    true
+}
+
+/// Verifies that the left and right half of every row, and the top and bottom half of every
+/// column, sum to half the magic constant. This needs $n \equiv 0 \pmod 4$.
+pub fn has_half_line_sums(grid: &[u32], n: usize) -> bool {
+    if !n.is_multiple_of(4) || grid.len() != n * n { return false; }
 
-    // Check Columns
-    for c in 0..n {
-        // Stride iteration for columns
-        let sum: u32 = (0..n).map(|r| grid[r * n + c]).sum();
-        if sum != magic_constant { return false; }
+    let target = magic_constant(n) / 2;
+    let half = n / 2;
+    (0..n).all(|line| {
+        [0, half].iter().all(|&start| {
+            let row: u64 = (start..start + half).map(|c| grid[line * n + c] as u64).sum();
+            let col: u64 = (start..start + half).map(|r| grid[r * n + line] as u64).sum();
+            row == target && col == target
+        })
+    })
+}
+
+/// Verifies that every $2 \times 2$ block, including those wrapping around the edges, sums to
+/// $2(n^2+1)$, the magic constant scaled to four cells.
+pub fn has_block_sums(grid: &[u32], n: usize) -> bool {
+    if n < 2 || grid.len() != n * n { return false; }
+
+    let target = 2 * (n as u64 * n as u64 + 1);
+    (0..n).all(|r| {
+        (0..n).all(|c| {
+            let (down, right) = ((r + 1) % n, (c + 1) % n);
+            [r * n + c, r * n + right, down * n + c, down * n + right].iter().map(|&i| grid[i] as u64).sum::<u64>() == target
+        })
+    })
+}
+
+/// The properties of a grid found by [`classify`]. Each one implies the ones before it, apart
+/// from `pandiagonal` and `bimagic`, which are independent of each other, and `franklin`,
+/// which only implies `semi_magic`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Properties {
+    /// $1..n^2$ once each, rows and columns summing to the magic constant.
+    pub semi_magic: bool,
+    /// Semi-magic, and both main diagonals sum to the magic constant.
+    pub magic: bool,
+    /// Magic, and every broken diagonal sums to the magic constant.
+    pub pandiagonal: bool,
+    /// Magic, and still magic with every entry squared.
+    pub bimagic: bool,
+    /// Bimagic, and still magic with every entry cubed.
+    pub trimagic: bool,
+    /// Semi-magic, with magic bent diagonals, half lines and $2 \times 2$ blocks.
+    pub franklin: bool,
+}
+
+/// Classifies a flat $n \times n$ grid by the magic properties it satisfies.
+pub fn classify(grid: &[u32], n: usize) -> Properties {
+    let magic = check_magic_properties(grid, n);
+    let bimagic = magic && is_multimagic(grid, n, 2);
+    Properties {
+        semi_magic: check_semi_magic_properties(grid, n),
+        magic,
+        pandiagonal: magic && is_pandiagonal(grid, n),
+        bimagic,
+        trimagic: bimagic && is_multimagic(grid, n, 3),
+        franklin: is_franklin(grid, n),
     }
+}
+
+/// Magic constant $M = n(n^2+1)/2$ of a normal magic square of order $n$.
+pub fn magic_constant(n: usize) -> u64 {
+    let n = n as u64;
+    n * (n * n + 1) / 2
+}
+
+/// What keeps a grid from being a magic square, as found by [`diagnose`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Diagnosis {
+    /// Values outside $1..n^2$, in grid order.
+    pub out_of_range: Vec<u32>,
+    /// Values of $1..n^2$ that appear more than once, in increasing order.
+    pub repeated: Vec<u32>,
+    /// Lines that do not sum to the magic constant, with their sums, in the order of
+    /// [`Line::all`].
+    pub wrong_lines: Vec<(Line, u64)>,
+}
+
+impl Diagnosis {
+    /// Whether nothing is wrong, i.e. the grid is a magic square.
+    pub fn is_magic(&self) -> bool {
+        self.out_of_range.is_empty() && self.repeated.is_empty() && self.wrong_lines.is_empty()
+    }
+}
+
+/// Lists every value and line of a flat $n \times n$ grid that breaks the conditions of
+/// [`check_magic_properties`]. The grid must hold $n^2$ values.
+pub fn diagnose(grid: &[u32], n: usize) -> Diagnosis {
+    assert_eq!(grid.len(), n * n, "an order-{} grid holds {} values", n, n * n);
+    let mut diagnosis = Diagnosis::default();
+    let mut seen = vec![0u32; grid.len() + 1];
+    for &v in grid {
+        match seen.get_mut(v as usize) {
+            Some(count) if v > 0 => *count += 1,
+            _ => diagnosis.out_of_range.push(v),
+        }
+    }
+    diagnosis.repeated = (1..seen.len()).filter(|&v| seen[v] > 1).map(|v| v as u32).collect();
+
+    let target = magic_constant(n);
+    diagnosis.wrong_lines = Line::all(n)
+        .into_iter()
+        .map(|line| (line, line.sum(grid, n)))
+        .filter(|&(_, sum)| sum != target)
+        .collect();
+    diagnosis
+}
+
+/// Returns the sum shared by every row, column and both main diagonals, if they all agree.
+/// Unlike [`check_magic_properties`], the values need not be $1..n^2$.
+pub fn common_line_sum(grid: &[u32], n: usize) -> Option<u64> {
+    if n == 0 || grid.len() != n * n { return None; }
+    let target = Line::Row(0).sum(grid, n);
+    Line::all(n).into_iter().all(|line| line.sum(grid, n) == target).then_some(target)
+}
+
+/// Returns the product shared by every row, column and both main diagonals, if they all agree
+/// and none of them overflows a `u128`.
+pub fn common_product(grid: &[u128], n: usize) -> Option<u128> {
+    if n == 0 || grid.len() != n * n { return None; }
+    let product = |line: Line| line.cells(n).try_fold(1u128, |acc, i| acc.checked_mul(grid[i]));
+    let target = product(Line::Row(0))?;
+    Line::all(n).into_iter().all(|line| product(line) == Some(target)).then_some(target)
+}
+
+/// Verifies that a grid is a multiplicative magic square: its entries are distinct and every
+/// row, column and main diagonal has the same product (computed with checked `u128` arithmetic).
+pub fn is_multiplicative_magic(grid: &[u128], n: usize) -> bool {
+    let mut sorted = grid.to_vec();
+    sorted.sort_unstable();
+    sorted.windows(2).all(|w| w[0] != w[1]) && common_product(grid, n).is_some()
+}
+
+/// Whether the values of the grid are distinct and all drawn from `set`. This takes the place
+/// of the $1..n^2$ check for squares over other values, such as primes.
+pub fn has_distinct_values_from(grid: &[u32], set: &[u32]) -> bool {
+    let allowed: HashSet<u32> = set.iter().copied().collect();
+    let mut seen = HashSet::with_capacity(grid.len());
+    grid.iter().all(|v| allowed.contains(v) && seen.insert(*v))
+}
+
+/// Verifies that a grid is a magic square over the values in `set`: its values are distinct
+/// members of `set`, and every row, column and main diagonal has the same sum.
+pub fn check_magic_properties_from(grid: &[u32], n: usize, set: &[u32]) -> bool {
+    has_distinct_values_from(grid, set) && common_line_sum(grid, n).is_some()
+}
 
-    // Check Main Diagonal (Top-Left to Bottom-Right)
-    let diag1: u32 = (0..n).map(|i| grid[i * n + i]).sum();
-    if diag1 != magic_constant { return false; }
+/// Verifies that a flat `rows` x `cols` grid is a magic rectangle: it holds $1..N$ once each
+/// ($N$ = `rows * cols`), every row sums to `cols` $(N+1)/2$ and every column to
+/// `rows` $(N+1)/2$. There is no diagonal condition.
+pub fn check_magic_rectangle(grid: &[u32], rows: usize, cols: usize) -> bool {
+    if rows == 0 || cols == 0 || grid.len() != rows * cols { return false; }
+    if !is_permutation(grid) { return false; }
 
-    // Check Anti-Diagonal (Top-Right to Bottom-Left)
-    let diag2: u32 = (0..n).map(|i| grid[i * n + (n - 1 - i)]).sum();
-    if diag2 != magic_constant { return false; }
+    let total = (rows * cols) as u64 + 1;
+    let (row_target, col_target) = (cols as u64 * total / 2, rows as u64 * total / 2);
 
-    // Check Uniqueness (1..n^2)
-    // We clone the slice to sort it without modifying the original.
-    let mut flat = grid.to_vec();
-    flat.sort_unstable();
-    
-    for (i, &val) in flat.iter().enumerate() {
-        if val != (i + 1) as u32 { return false; }
+    let mut columns = vec![0; cols];
+    let rows_ok = grid.chunks(cols).all(|row| add_row(&mut columns, row) == row_target);
+    rows_ok && columns.iter().all(|&sum| sum == col_target)
+}
+
+/// Verifies that a grid is a semi-magic square: rows and columns sum to the magic constant and
+/// $1..n^2$ appear once each, with no condition on the diagonals.
+pub fn check_semi_magic_properties(grid: &[u32], n: usize) -> bool {
+    check_magic_rectangle(grid, n, n)
+}
+
+/// Verifies that a flat grid of $n^d$ cells is a magic hypercube of dimension $d$: it holds
+/// $1..n^d$ once each, and every line parallel to an axis and each of the $2^{d-1}$ space
+/// diagonals sums to $n(n^d+1)/2$. The cell $(x_0, ..., x_{d-1})$ is at index $\sum_i x_i n^i$.
+///
+/// For $d = 2$ this is the same check as [`check_magic_properties`].
+pub fn check_hypercube_properties(cells: &[u32], n: usize, d: usize) -> bool {
+    let Some(total) = (n as u64).checked_pow(d as u32) else { return false; };
+    if n == 0 || d == 0 || cells.len() as u64 != total { return false; }
+    if !is_permutation(cells) { return false; }
+
+    let target = n as u64 * (total + 1) / 2;
+    let line_sum = |start: usize, step: isize| -> u64 {
+        (0..n).map(|k| cells[(start as isize + k as isize * step) as usize] as u64).sum()
+    };
+
+    // Lines along each axis, starting from the cells whose coordinate on that axis is 0.
+    let mut stride = 1;
+    for _ in 0..d {
+        let starts = (0..cells.len()).filter(|&i| (i / stride).is_multiple_of(n));
+        for start in starts {
+            if line_sum(start, stride as isize) != target { return false; }
+        }
+        stride *= n;
+    }
+
+    // Space diagonals: each axis runs forwards or backwards, the last one always forwards.
+    for signs in 0..1usize << (d - 1) {
+        let (mut start, mut step, mut place) = (0, 0isize, 1);
+        for axis in 0..d {
+            if signs >> axis & 1 == 1 {
+                start += (n - 1) * place;
+                step -= place as isize;
+            } else {
+                step += place as isize;
+            }
+            place *= n;
+        }
+        if line_sum(start, step) != target { return false; }
     }
 
     true
 }
+
+/// Whether the grid holds each of $1..$ `grid.len()` exactly once.
+///
+/// The values seen are marked in a bitset of `grid.len()` bits, so the check takes linear
+/// time and an eighth of a byte per cell.
+pub fn is_permutation(grid: &[u32]) -> bool {
+    let len = grid.len() as u64;
+    let mut seen = vec![0u64; grid.len().div_ceil(64)];
+    grid.iter().all(|&v| {
+        let v = v as u64;
+        if v == 0 || v > len {
+            return false;
+        }
+        let (word, bit) = (((v - 1) / 64) as usize, 1u64 << ((v - 1) % 64));
+        let fresh = seen[word] & bit == 0;
+        seen[word] |= bit;
+        fresh
+    })
+}
+
+/// [`is_permutation`] with the grid split between `threads` threads (all cores for 0),
+/// which mark a shared bitset atomically.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn is_permutation_parallel(grid: &[u32], threads: usize) -> bool {
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+    use std::thread;
+
+    let threads = thread_count(threads);
+    if threads == 1 || grid.len() < PARALLEL_CELLS {
+        return is_permutation(grid);
+    }
+
+    let len = grid.len() as u64;
+    let seen: Vec<AtomicU64> = (0..grid.len().div_ceil(64)).map(|_| AtomicU64::new(0)).collect();
+    let failed = AtomicBool::new(false);
+    thread::scope(|scope| {
+        for chunk in grid.chunks(grid.len().div_ceil(threads)) {
+            let (seen, failed) = (&seen, &failed);
+            scope.spawn(move || {
+                // Check for a failure elsewhere every 64K cells, so that workers stop early.
+                for block in chunk.chunks(1 << 16) {
+                    if failed.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    for &v in block {
+                        let v = v as u64;
+                        if v == 0 || v > len {
+                            failed.store(true, Ordering::Relaxed);
+                            return;
+                        }
+                        let bit = 1u64 << ((v - 1) % 64);
+                        if seen[((v - 1) / 64) as usize].fetch_or(bit, Ordering::Relaxed) & bit != 0 {
+                            failed.store(true, Ordering::Relaxed);
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+    });
+    !failed.into_inner()
+}
+
+/// One of the $2n + 2$ lines of a square that must sum to the magic constant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Line {
+    /// Row `r` (0-based).
+    Row(usize),
+    /// Column `c` (0-based).
+    Column(usize),
+    /// Main diagonal, top-left to bottom-right.
+    Diagonal,
+    /// Anti-diagonal, top-right to bottom-left.
+    AntiDiagonal,
+}
+
+impl Line {
+    /// Every line of an order-$n$ square: rows, then columns, then both diagonals.
+    pub fn all(n: usize) -> Vec<Line> {
+        (0..n)
+            .map(Line::Row)
+            .chain((0..n).map(Line::Column))
+            .chain([Line::Diagonal, Line::AntiDiagonal])
+            .collect()
+    }
+
+    /// Position of the line in [`Line::all`].
+    pub fn index(self, n: usize) -> usize {
+        match self {
+            Line::Row(r) => r,
+            Line::Column(c) => n + c,
+            Line::Diagonal => 2 * n,
+            Line::AntiDiagonal => 2 * n + 1,
+        }
+    }
+
+    /// The line at position `index` of [`Line::all`].
+    pub fn from_index(n: usize, index: usize) -> Line {
+        match index {
+            i if i < n => Line::Row(i),
+            i if i < 2 * n => Line::Column(i - n),
+            i if i == 2 * n => Line::Diagonal,
+            _ => Line::AntiDiagonal,
+        }
+    }
+
+    /// The 2 to 4 lines through a cell.
+    pub fn through(n: usize, cell: usize) -> impl Iterator<Item = Line> + Clone {
+        let (r, c) = (cell / n, cell % n);
+        let diagonal = (r == c).then_some(Line::Diagonal);
+        let anti = (r + c == n - 1).then_some(Line::AntiDiagonal);
+        [Some(Line::Row(r)), Some(Line::Column(c)), diagonal, anti].into_iter().flatten()
+    }
+
+    /// Flat index of the `i`-th cell of the line.
+    pub fn cell(self, n: usize, i: usize) -> usize {
+        match self {
+            Line::Row(r) => r * n + i,
+            Line::Column(c) => i * n + c,
+            Line::Diagonal => i * n + i,
+            Line::AntiDiagonal => i * n + (n - 1 - i),
+        }
+    }
+
+    /// Flat indices of the cells of the line.
+    pub fn cells(self, n: usize) -> impl Iterator<Item = usize> {
+        (0..n).map(move |i| self.cell(n, i))
+    }
+
+    /// Sum of the line in a flat grid.
+    pub fn sum(self, grid: &[u32], n: usize) -> u64 {
+        self.cells(n).map(|i| grid[i] as u64).sum()
+    }
+}
+
+impl std::fmt::Display for Line {
+    /// Lines are shown 1-based, the way they are numbered for users.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Line::Row(r) => write!(f, "row {}", r + 1),
+            Line::Column(c) => write!(f, "column {}", c + 1),
+            Line::Diagonal => write!(f, "main diagonal"),
+            Line::AntiDiagonal => write!(f, "anti-diagonal"),
+        }
+    }
+}
+
+impl std::str::FromStr for Line {
+    type Err = ();
+
+    /// Parses the 1-based names users type: `row 2` or `row:2`, `column 3` (or `col`),
+    /// `diagonal` and `anti-diagonal`.
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let s = s.trim().to_ascii_lowercase();
+        match s.as_str() {
+            "diagonal" | "main diagonal" | "main-diagonal" => return Ok(Line::Diagonal),
+            "anti-diagonal" | "antidiagonal" | "anti diagonal" => return Ok(Line::AntiDiagonal),
+            _ => {}
+        }
+        let (kind, number) = s.split_once([' ', ':']).ok_or(())?;
+        let index = number.trim().parse::<usize>().ok().and_then(|i| i.checked_sub(1)).ok_or(())?;
+        match kind {
+            "row" => Ok(Line::Row(index)),
+            "column" | "col" => Ok(Line::Column(index)),
+            _ => Err(()),
+        }
+    }
+}
+
+/// State of a line in a partially filled square.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineStatus {
+    /// Some cells are empty and the sum does not exceed the magic constant yet.
+    Incomplete = 0,
+    /// Every cell is filled and the sum is the magic constant.
+    Correct = 1,
+    /// Every cell is filled but the sum is not the magic constant.
+    Wrong = 2,
+    /// The sum already exceeds the magic constant.
+    Over = 3,
+}
+
+/// Running sums of the lines of a square that is filled in one cell at a time.
+///
+/// Filling or clearing a cell only touches the 2 to 4 lines through it, so the state of every
+/// line is known without summing the grid again.
+#[derive(Debug, Clone)]
+pub struct LineTally {
+    n: usize,
+    target: u64,
+    sums: Vec<u64>,
+    filled: Vec<usize>,
+}
+
+impl LineTally {
+    /// Tally of an empty order-$n$ square.
+    pub fn new(n: usize) -> Self {
+        Self::with_target(n, magic_constant(n))
+    }
+
+    /// Tally of an empty order-$n$ square whose lines should sum to `target`.
+    pub fn with_target(n: usize, target: u64) -> Self {
+        Self { n, target, sums: vec![0; 2 * n + 2], filled: vec![0; 2 * n + 2] }
+    }
+
+    /// Records `value` in an empty cell.
+    pub fn add(&mut self, cell: usize, value: u32) {
+        for line in Line::through(self.n, cell) {
+            let i = line.index(self.n);
+            self.sums[i] += value as u64;
+            self.filled[i] += 1;
+        }
+    }
+
+    /// Clears a cell that held `value`.
+    pub fn remove(&mut self, cell: usize, value: u32) {
+        for line in Line::through(self.n, cell) {
+            let i = line.index(self.n);
+            self.sums[i] -= value as u64;
+            self.filled[i] -= 1;
+        }
+    }
+
+    /// The magic constant the lines are checked against.
+    pub fn target(&self) -> u64 {
+        self.target
+    }
+
+    /// Current sum of the line at position `index` of [`Line::all`].
+    pub fn sum(&self, index: usize) -> u64 {
+        self.sums[index]
+    }
+
+    /// Number of empty cells on the line at position `index` of [`Line::all`].
+    pub fn empty(&self, index: usize) -> usize {
+        self.n - self.filled[index]
+    }
+
+    /// State of the line at position `index` of [`Line::all`].
+    pub fn status(&self, index: usize) -> LineStatus {
+        if self.sums[index] > self.target {
+            LineStatus::Over
+        } else if self.filled[index] < self.n {
+            LineStatus::Incomplete
+        } else if self.sums[index] == self.target {
+            LineStatus::Correct
+        } else {
+            LineStatus::Wrong
+        }
+    }
+
+    /// States of every line, in the order of [`Line::all`].
+    pub fn statuses(&self) -> Vec<LineStatus> {
+        (0..self.sums.len()).map(|i| self.status(i)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator;
+    use crate::rng::Lcg;
+
+    #[test]
+    fn checks_uniqueness_with_a_bitset() {
+        let lo_shu = [4, 9, 2, 3, 5, 7, 8, 1, 6];
+        assert!(is_permutation(&lo_shu) && check_magic_properties(&lo_shu, 3));
+        assert!(!is_permutation(&[4, 9, 2, 3, 5, 7, 8, 1, 1]));
+        assert!(!is_permutation(&[4, 9, 2, 3, 5, 7, 8, 0, 6]));
+        assert!(!is_permutation(&[4, 9, 2, 3, 5, 7, 8, 10, 6]));
+        assert!(is_permutation(&[]));
+
+        // Values 64 apart share a bit position in different words.
+        let mut shifted: Vec<u32> = (1..=200).collect();
+        assert!(is_permutation(&shifted));
+        shifted[0] = 65;
+        assert!(!is_permutation(&shifted));
+    }
+
+    #[test]
+    fn sums_lines_in_one_pass() {
+        // Orders on both sides of a multiple of LANES, so that rows end in a partial chunk.
+        for n in [1, 3, 4, 8, 9, 16, 17, 23] {
+            let grid = generator::create(n, &mut Lcg::new_with_seed(n as u64)).generate(n);
+            assert!(check_magic_sums(&grid, n), "order {}", n);
+            // Raising a cell of the last column breaks its row and column, even in the
+            // partial chunk at the end of a row.
+            let mut broken = grid.clone();
+            broken[n * n - 1] += 1;
+            assert!(!check_magic_sums(&broken, n), "order {}", n);
+            // Swapping the first two cells keeps every row sum but breaks two columns.
+            if n > 1 {
+                let mut swapped = grid.clone();
+                swapped.swap(0, 1);
+                assert!(!check_magic_sums(&swapped, n), "order {}", n);
+            }
+        }
+        // A constant grid has the right sums but not the right values.
+        let flat = vec![5; 9];
+        assert!(check_magic_sums(&flat, 3) && !check_magic_properties(&flat, 3));
+        assert!(!check_magic_sums(&[1, 2, 3], 2));
+    }
+
+    #[test]
+    fn checks_large_pandiagonal_squares() {
+        // nA + B + 1 with A = r + 2c and B = 2r + c (mod n) is pandiagonal when n is prime to
+        // 6. At order 1703, n(n^2 + 1) no longer fits in a u32.
+        let n = 1703;
+        let mut grid: Vec<u32> = (0..n * n)
+            .map(|i| {
+                let (r, c) = (i / n, i % n);
+                (n * ((r + 2 * c) % n) + (2 * r + c) % n + 1) as u32
+            })
+            .collect();
+        assert!(is_pandiagonal(&grid, n));
+        // Swapping the first two columns keeps the rows and columns but breaks the diagonals.
+        for r in 0..n {
+            grid.swap(r * n, r * n + 1);
+        }
+        assert!(!is_pandiagonal(&grid, n));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn checks_squares_in_parallel() {
+        let n = 1030;
+        let mut grid = generator::create(n, &mut Lcg::new_with_seed(1)).generate(n);
+        for threads in [0, 1, 3, 7] {
+            assert!(check_magic_properties_parallel(&grid, n, threads), "{} threads", threads);
+        }
+        // Swapping two cells of the last row keeps every row sum, but not the columns.
+        grid.swap(n * n - 1, n * n - 2);
+        assert!(!check_magic_sums_parallel(&grid, n, 4));
+        grid.swap(n * n - 1, n * n - 2);
+        grid[n * n / 2] += 1;
+        assert!(!check_magic_properties_parallel(&grid, n, 4));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn checks_uniqueness_in_parallel() {
+        let mut grid: Vec<u32> = (1..=1 << 21).rev().collect();
+        assert!(is_permutation_parallel(&grid, 4));
+        grid[5] = grid[1 << 20];
+        assert!(!is_permutation_parallel(&grid, 4));
+        grid[5] = 0;
+        assert!(!is_permutation_parallel(&grid, 3));
+        assert!(is_permutation_parallel(&[2, 1], 0));
+    }
+}